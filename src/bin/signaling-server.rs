@@ -0,0 +1,43 @@
+use std::collections::HashMap;
+use std::env;
+use std::io::{Error, ErrorKind};
+
+use cgrs::signaling_server::server::SignalingServer;
+use cgrs::signaling_server::server_const::DEFAULT_SERVER_ADDR;
+
+/// Starts a local signaling server.
+///
+/// Usage: `signaling-server [--addr HOST:PORT] [--game NAME=PATH]...`
+#[tokio::main]
+async fn main() -> Result<(), Error> {
+    env_logger::builder().format_target(false).init();
+
+    let mut addr = DEFAULT_SERVER_ADDR.to_owned();
+    let mut games = HashMap::new();
+
+    let mut args = env::args().skip(1);
+    while let Some(arg) = args.next() {
+        match (arg.as_str(), args.next()) {
+            ("--addr", Some(value)) => addr = value,
+            ("--game", Some(value)) => match value.split_once('=') {
+                Some((name, path)) => {
+                    games.insert(name.to_owned(), path.to_owned());
+                }
+                None => {
+                    return Err(Error::new(
+                        ErrorKind::InvalidInput,
+                        "Games must be provided as NAME=PATH",
+                    ))
+                }
+            },
+            _ => {
+                return Err(Error::new(
+                    ErrorKind::InvalidInput,
+                    format!("Invalid argument {}", arg),
+                ))
+            }
+        }
+    }
+
+    SignalingServer::new(games).run(&addr).await
+}
@@ -0,0 +1,10 @@
+// File loaded when no path is provided, if present in the working directory
+pub const DEFAULT_CONFIG_FILE: &str = "cgrs.toml";
+// File holding the TURN credentials, read if present in the working directory
+pub const DEFAULT_SECRETS_FILE: &str = "cgrs.secrets.toml";
+// Value shown instead of secrets when the configuration is printed
+pub const REDACTED: &str = "<redacted>";
+// Prefix of the environment variables that override the configuration
+pub const ENV_PREFIX: &str = "CGRS_";
+// Variable read for the signaling URL before the configuration file existed
+pub const LEGACY_SIGNALING_URL_VAR: &str = "CGRS_SOCKET_URL";
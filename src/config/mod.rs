@@ -0,0 +1,2 @@
+pub mod config_const;
+pub mod node_config;
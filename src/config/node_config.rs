@@ -0,0 +1,875 @@
+use serde::Deserialize;
+use std::borrow::Cow;
+use std::fmt;
+use std::path::Path;
+
+use super::config_const::{
+    DEFAULT_CONFIG_FILE, DEFAULT_SECRETS_FILE, ENV_PREFIX, LEGACY_SIGNALING_URL_VAR, REDACTED,
+};
+use crate::front_connection::front_protocol_const::{FRONT_PORT, FRONT_TOKEN_FILE};
+use crate::gstreamer_pipeline::capture_backend::{AudioSource, BackendKind, VideoSource};
+use crate::gstreamer_pipeline::player_backend::{PlayerKind, VideoSink};
+use crate::input::input_const::{MAX_MOUSE_RATE, MOUSE_RATE};
+use crate::input::input_event::MouseMode;
+use crate::input::input_source::InputSourceKind;
+use crate::utils::webrtc_const::{
+    READ_TRACK_LIMIT, READ_TRACK_THRESHOLD, RECONNECT_GRACE_PERIOD, RECONNECT_TIMEOUT,
+    SEND_TRACK_LIMIT, SEND_TRACK_THRESHOLD, STUN_ADRESS, TURN_ADRESS, TURN_CREDENTIAL_TTL,
+};
+use crate::video::video_const::{
+    ENCODER_BITRATE, GSTREAMER_FRAMES, TEST_HEIGHT, TEST_PATTERN, TEST_WIDTH,
+};
+use crate::websocketprotocol::socket_protocol_const::SOCKET_URL;
+
+/// Runtime configuration of the node.
+///
+/// Values are taken, from lowest to highest priority, from the compile-time defaults,
+/// the TOML configuration file, `CGRS_*` environment variables and command line overrides.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub webrtc: WebRtcConfig,
+    pub signaling: SignalingConfig,
+    pub front: FrontConfig,
+    pub video: VideoConfig,
+    pub capture: CaptureConfig,
+    pub player: PlayerConfig,
+    pub input: InputConfig,
+    pub error_tracker: ErrorTrackerConfig,
+    pub reconnect: ReconnectConfig,
+}
+
+/// ICE servers used to establish the peer connection.
+///
+/// TURN secrets are rejected in the configuration file and in command line overrides, they
+/// are read from the environment or from the secrets file instead.
+#[derive(Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct WebRtcConfig {
+    pub stun_address: String,
+    pub turn_address: String,
+    pub turn_user: Option<String>,
+    pub turn_pass: Option<String>,
+    /// Secret shared with the TURN server to generate TURN REST credentials.
+    pub turn_shared_secret: Option<String>,
+    /// Lifetime in seconds of the TURN REST credentials.
+    pub turn_credential_ttl: u64,
+    /// File holding the TURN credentials. Defaults to `cgrs.secrets.toml` if present.
+    pub secrets_file: Option<String>,
+    /// Whether to exchange ICE candidates as they are gathered when the peer supports it.
+    pub trickle_ice: bool,
+}
+
+/// Contents of the secrets file.
+#[derive(Default, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+struct Secrets {
+    turn_user: Option<String>,
+    turn_pass: Option<String>,
+    turn_shared_secret: Option<String>,
+}
+
+/// Intermediary server connection.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SignalingConfig {
+    pub url: String,
+}
+
+/// Local port where the front-end connects.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct FrontConfig {
+    pub port: u16,
+    /// File where the token the front-end authenticates with is written at startup.
+    pub token_file: String,
+}
+
+/// Video capture and encoding parameters.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct VideoConfig {
+    pub framerate: i32,
+    pub bitrate: u32,
+}
+
+/// Backend and sources used to capture the game.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct CaptureConfig {
+    /// `windows`, `linux`, `test` or `auto` to use the one of the current platform.
+    pub backend: BackendKind,
+    /// Video source of the Linux backend: `x11`, `pipewire` or `test`.
+    pub video_source: VideoSource,
+    /// Audio source of the Linux backend: `pulse`, `pipewire` or `test`.
+    pub audio_source: AudioSource,
+    /// `videotestsrc` pattern streamed by the test sources.
+    pub test_pattern: String,
+    /// Resolution of the test video source, its framerate is `video.framerate`.
+    pub test_width: i32,
+    pub test_height: i32,
+}
+
+/// Backend used to play the game received.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct PlayerConfig {
+    /// `windows`, `linux`, `headless` or `auto` to use the one of the current platform.
+    pub backend: PlayerKind,
+    /// Video sink of the Linux backend: `auto` or `gl`.
+    pub video_sink: VideoSink,
+}
+
+/// Source of the input sent by the client to the offerer.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct InputConfig {
+    /// `windows`, `linux`, `script` or `auto` to use the one of the current platform.
+    pub source: InputSourceKind,
+    /// Events replayed by the `script` source.
+    pub script_file: Option<String>,
+    /// Whether to forward the controllers of the client.
+    pub gamepad: bool,
+    /// `relative` or `absolute`, sessions started by the front end can choose another.
+    pub mouse_mode: MouseMode,
+    /// Mouse movements sent per second, the ones read in between are added up.
+    pub mouse_rate: u32,
+}
+
+/// Thresholds of the `ErrorTracker`s used when reading and writing tracks.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ErrorTrackerConfig {
+    pub read_track_threshold: u32,
+    pub read_track_limit: u32,
+    pub send_track_threshold: u32,
+    pub send_track_limit: u32,
+}
+
+/// Recovery of the peer connection after a transient disconnection.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ReconnectConfig {
+    /// Seconds to wait for the connection to recover by itself before an ICE restart.
+    pub grace_period: u64,
+    /// Seconds after which the session is ended if the connection did not recover.
+    pub timeout: u64,
+}
+
+impl Default for WebRtcConfig {
+    fn default() -> Self {
+        Self {
+            stun_address: STUN_ADRESS.to_owned(),
+            turn_address: TURN_ADRESS.to_owned(),
+            turn_user: None,
+            turn_pass: None,
+            turn_shared_secret: None,
+            turn_credential_ttl: TURN_CREDENTIAL_TTL,
+            secrets_file: None,
+            trickle_ice: true,
+        }
+    }
+}
+
+impl fmt::Debug for WebRtcConfig {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("WebRtcConfig")
+            .field("stun_address", &self.stun_address)
+            .field("turn_address", &self.turn_address)
+            .field("turn_user", &self.turn_user)
+            .field("turn_pass", &self.turn_pass.as_ref().map(|_| REDACTED))
+            .field(
+                "turn_shared_secret",
+                &self.turn_shared_secret.as_ref().map(|_| REDACTED),
+            )
+            .field("turn_credential_ttl", &self.turn_credential_ttl)
+            .field("secrets_file", &self.secrets_file)
+            .field("trickle_ice", &self.trickle_ice)
+            .finish()
+    }
+}
+
+impl Default for SignalingConfig {
+    fn default() -> Self {
+        Self {
+            url: SOCKET_URL.to_owned(),
+        }
+    }
+}
+
+impl Default for FrontConfig {
+    fn default() -> Self {
+        Self {
+            port: FRONT_PORT,
+            token_file: FRONT_TOKEN_FILE.to_owned(),
+        }
+    }
+}
+
+impl Default for VideoConfig {
+    fn default() -> Self {
+        Self {
+            framerate: GSTREAMER_FRAMES,
+            bitrate: ENCODER_BITRATE,
+        }
+    }
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self {
+            backend: BackendKind::Auto,
+            video_source: VideoSource::X11,
+            audio_source: AudioSource::Pulse,
+            test_pattern: TEST_PATTERN.to_owned(),
+            test_width: TEST_WIDTH,
+            test_height: TEST_HEIGHT,
+        }
+    }
+}
+
+impl Default for PlayerConfig {
+    fn default() -> Self {
+        Self {
+            backend: PlayerKind::Auto,
+            video_sink: VideoSink::Auto,
+        }
+    }
+}
+
+impl Default for InputConfig {
+    fn default() -> Self {
+        Self {
+            source: InputSourceKind::Auto,
+            script_file: None,
+            gamepad: true,
+            mouse_mode: MouseMode::Relative,
+            mouse_rate: MOUSE_RATE,
+        }
+    }
+}
+
+impl Default for ReconnectConfig {
+    fn default() -> Self {
+        Self {
+            grace_period: RECONNECT_GRACE_PERIOD,
+            timeout: RECONNECT_TIMEOUT,
+        }
+    }
+}
+
+impl Default for ErrorTrackerConfig {
+    fn default() -> Self {
+        Self {
+            read_track_threshold: READ_TRACK_THRESHOLD,
+            read_track_limit: READ_TRACK_LIMIT,
+            send_track_threshold: SEND_TRACK_THRESHOLD,
+            send_track_limit: SEND_TRACK_LIMIT,
+        }
+    }
+}
+
+/// Keys that can be overridden through the environment or the command line.
+pub const CONFIG_KEYS: &[&str] = &[
+    "webrtc.stun_address",
+    "webrtc.turn_address",
+    "webrtc.turn_user",
+    "webrtc.turn_pass",
+    "webrtc.turn_shared_secret",
+    "webrtc.turn_credential_ttl",
+    "webrtc.secrets_file",
+    "webrtc.trickle_ice",
+    "signaling.url",
+    "front.port",
+    "front.token_file",
+    "video.framerate",
+    "video.bitrate",
+    "capture.backend",
+    "capture.video_source",
+    "capture.audio_source",
+    "capture.test_pattern",
+    "capture.test_width",
+    "capture.test_height",
+    "player.backend",
+    "player.video_sink",
+    "input.source",
+    "input.script_file",
+    "input.gamepad",
+    "input.mouse_mode",
+    "input.mouse_rate",
+    "error_tracker.read_track_threshold",
+    "error_tracker.read_track_limit",
+    "error_tracker.send_track_threshold",
+    "error_tracker.send_track_limit",
+    "reconnect.grace_period",
+    "reconnect.timeout",
+];
+
+/// Keys that can only be set through the environment or the secrets file, so they do not
+/// end up in a configuration file or in the list of processes.
+const SECRET_KEYS: [&str; 2] = ["webrtc.turn_pass", "webrtc.turn_shared_secret"];
+
+/// Errors found while loading the configuration.
+#[derive(Debug)]
+pub enum ConfigError {
+    /// The configuration file could not be read.
+    Io(String, std::io::Error),
+    /// The configuration file is not valid TOML or has unknown fields.
+    Parse(String, toml::de::Error),
+    /// An override refers to a key that does not exist.
+    UnknownKey(String),
+    /// A value could not be parsed or is out of range.
+    InvalidValue {
+        key: &'static str,
+        value: String,
+        reason: Cow<'static, str>,
+    },
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::Io(path, e) => write!(f, "could not read {path}: {e}"),
+            ConfigError::Parse(path, e) => write!(f, "could not parse {path}: {e}"),
+            ConfigError::UnknownKey(key) => write!(f, "unknown configuration key {key}"),
+            ConfigError::InvalidValue { key, value, reason } => {
+                write!(f, "invalid value {value:?} for {key}: {reason}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+impl From<ConfigError> for std::io::Error {
+    fn from(e: ConfigError) -> Self {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, e)
+    }
+}
+
+impl Config {
+    /// Loads the configuration.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The TOML file to read. When `None`, `cgrs.toml` is read if it exists.
+    /// * `overrides` - `key=value` pairs provided through the command line.
+    ///
+    /// # Returns
+    /// The validated configuration or the first error found.
+    pub fn load(path: Option<&Path>, overrides: &[String]) -> Result<Config, ConfigError> {
+        Self::load_with_env(path, overrides, |var| std::env::var(var).ok())
+    }
+
+    /// Loads the configuration reading the environment variables through `env`.
+    fn load_with_env(
+        path: Option<&Path>,
+        overrides: &[String],
+        env: impl Fn(&str) -> Option<String>,
+    ) -> Result<Config, ConfigError> {
+        let mut config = match path {
+            Some(path) => Config::from_file(path)?,
+            None if Path::new(DEFAULT_CONFIG_FILE).exists() => {
+                Config::from_file(Path::new(DEFAULT_CONFIG_FILE))?
+            }
+            None => Config::default(),
+        };
+
+        config.apply_env(env)?;
+
+        for pair in overrides {
+            match pair.split_once('=') {
+                Some((key, value)) => {
+                    let key = key.trim();
+                    if let Some(secret) = SECRET_KEYS.into_iter().find(|s| *s == key) {
+                        return Err(secret_not_allowed(secret));
+                    }
+                    config.set(key, value.trim())?
+                }
+                None => return Err(ConfigError::UnknownKey(pair.to_owned())),
+            }
+        }
+
+        config.apply_secrets()?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Overrides a single value given its `section.key` name.
+    ///
+    /// # Returns
+    /// An error if the key is not one of `CONFIG_KEYS` or the value cannot be parsed.
+    pub fn set(&mut self, key: &str, value: &str) -> Result<(), ConfigError> {
+        let key = match CONFIG_KEYS.iter().find(|k| **k == key) {
+            Some(key) => *key,
+            None => return Err(ConfigError::UnknownKey(key.to_owned())),
+        };
+
+        match key {
+            "webrtc.stun_address" => self.webrtc.stun_address = value.to_owned(),
+            "webrtc.turn_address" => self.webrtc.turn_address = value.to_owned(),
+            "webrtc.turn_user" => self.webrtc.turn_user = Some(value.to_owned()),
+            "webrtc.turn_pass" => self.webrtc.turn_pass = Some(value.to_owned()),
+            "webrtc.turn_shared_secret" => self.webrtc.turn_shared_secret = Some(value.to_owned()),
+            "webrtc.turn_credential_ttl" => self.webrtc.turn_credential_ttl = parse(key, value)?,
+            "webrtc.secrets_file" => self.webrtc.secrets_file = Some(value.to_owned()),
+            "webrtc.trickle_ice" => self.webrtc.trickle_ice = parse(key, value)?,
+            "signaling.url" => self.signaling.url = value.to_owned(),
+            "front.port" => self.front.port = parse(key, value)?,
+            "front.token_file" => self.front.token_file = value.to_owned(),
+            "video.framerate" => self.video.framerate = parse(key, value)?,
+            "video.bitrate" => self.video.bitrate = parse(key, value)?,
+            "capture.backend" => self.capture.backend = parse(key, value)?,
+            "capture.video_source" => self.capture.video_source = parse(key, value)?,
+            "capture.audio_source" => self.capture.audio_source = parse(key, value)?,
+            "capture.test_pattern" => self.capture.test_pattern = value.to_owned(),
+            "capture.test_width" => self.capture.test_width = parse(key, value)?,
+            "capture.test_height" => self.capture.test_height = parse(key, value)?,
+            "player.backend" => self.player.backend = parse(key, value)?,
+            "player.video_sink" => self.player.video_sink = parse(key, value)?,
+            "input.source" => self.input.source = parse(key, value)?,
+            "input.script_file" => self.input.script_file = Some(value.to_owned()),
+            "input.gamepad" => self.input.gamepad = parse(key, value)?,
+            "input.mouse_mode" => self.input.mouse_mode = parse(key, value)?,
+            "input.mouse_rate" => self.input.mouse_rate = parse(key, value)?,
+            "error_tracker.read_track_threshold" => {
+                self.error_tracker.read_track_threshold = parse(key, value)?
+            }
+            "error_tracker.read_track_limit" => {
+                self.error_tracker.read_track_limit = parse(key, value)?
+            }
+            "error_tracker.send_track_threshold" => {
+                self.error_tracker.send_track_threshold = parse(key, value)?
+            }
+            "error_tracker.send_track_limit" => {
+                self.error_tracker.send_track_limit = parse(key, value)?
+            }
+            "reconnect.grace_period" => self.reconnect.grace_period = parse(key, value)?,
+            "reconnect.timeout" => self.reconnect.timeout = parse(key, value)?,
+            _ => return Err(ConfigError::UnknownKey(key.to_owned())),
+        }
+        Ok(())
+    }
+
+    /// Fills the TURN credentials not provided through the environment or the command line
+    /// with the ones in the secrets file.
+    fn apply_secrets(&mut self) -> Result<(), ConfigError> {
+        let path = match &self.webrtc.secrets_file {
+            Some(path) => path.to_owned(),
+            None if Path::new(DEFAULT_SECRETS_FILE).exists() => DEFAULT_SECRETS_FILE.to_owned(),
+            None => return Ok(()),
+        };
+
+        let content =
+            std::fs::read_to_string(&path).map_err(|e| ConfigError::Io(path.clone(), e))?;
+        let secrets: Secrets = toml::from_str(&content).map_err(|e| ConfigError::Parse(path, e))?;
+
+        let webrtc = &mut self.webrtc;
+        webrtc.turn_user = webrtc.turn_user.take().or(secrets.turn_user);
+        webrtc.turn_pass = webrtc.turn_pass.take().or(secrets.turn_pass);
+        webrtc.turn_shared_secret = webrtc
+            .turn_shared_secret
+            .take()
+            .or(secrets.turn_shared_secret);
+        Ok(())
+    }
+
+    /// Reads a TOML configuration file. Missing fields take their default value.
+    ///
+    /// # Returns
+    /// The configuration, or an error if the file cannot be read, is not valid or holds a
+    /// TURN secret.
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let name = path.display().to_string();
+        let content =
+            std::fs::read_to_string(path).map_err(|e| ConfigError::Io(name.clone(), e))?;
+        let config: Config = toml::from_str(&content).map_err(|e| ConfigError::Parse(name, e))?;
+
+        if config.webrtc.turn_pass.is_some() {
+            return Err(secret_not_allowed(SECRET_KEYS[0]));
+        }
+        if config.webrtc.turn_shared_secret.is_some() {
+            return Err(secret_not_allowed(SECRET_KEYS[1]));
+        }
+        Ok(config)
+    }
+
+    /// Applies the `CGRS_<SECTION>_<KEY>` environment variables, e.g. `CGRS_SIGNALING_URL`.
+    ///
+    /// `CGRS_SOCKET_URL`, the name used before the configuration file existed, is still read
+    /// when `CGRS_SIGNALING_URL` is not set.
+    fn apply_env(&mut self, env: impl Fn(&str) -> Option<String>) -> Result<(), ConfigError> {
+        if let Some(url) = env(LEGACY_SIGNALING_URL_VAR) {
+            log::warn!(
+                "CONFIG | {} is deprecated, use {} instead",
+                LEGACY_SIGNALING_URL_VAR,
+                env_var("signaling.url")
+            );
+            self.set("signaling.url", &url)?;
+        }
+        for key in CONFIG_KEYS {
+            if let Some(value) = env(&env_var(key)) {
+                self.set(key, &value)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Checks that every value is usable.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        if !self.webrtc.stun_address.starts_with("stun:") {
+            return Err(invalid(
+                "webrtc.stun_address",
+                &self.webrtc.stun_address,
+                "must start with stun:",
+            ));
+        }
+        if !(self.webrtc.turn_address.starts_with("turn:")
+            || self.webrtc.turn_address.starts_with("turns:"))
+        {
+            return Err(invalid(
+                "webrtc.turn_address",
+                &self.webrtc.turn_address,
+                "must start with turn: or turns:",
+            ));
+        }
+        if self.webrtc.turn_shared_secret.is_none()
+            && self.webrtc.turn_user.is_some() != self.webrtc.turn_pass.is_some()
+        {
+            return Err(invalid(
+                "webrtc.turn_pass",
+                REDACTED,
+                "turn_user and turn_pass must be provided together",
+            ));
+        }
+        if self.webrtc.turn_credential_ttl == 0 {
+            return Err(invalid(
+                "webrtc.turn_credential_ttl",
+                "0",
+                "must be positive",
+            ));
+        }
+        if !(self.signaling.url.starts_with("ws://") || self.signaling.url.starts_with("wss://")) {
+            return Err(invalid(
+                "signaling.url",
+                &self.signaling.url,
+                "must start with ws:// or wss://",
+            ));
+        }
+        if self.front.port == 0 {
+            return Err(invalid("front.port", "0", "must not be 0"));
+        }
+        if self.front.token_file.is_empty() {
+            return Err(invalid("front.token_file", "", "must not be empty"));
+        }
+        if self.video.framerate <= 0 {
+            return Err(invalid(
+                "video.framerate",
+                &self.video.framerate.to_string(),
+                "must be positive",
+            ));
+        }
+        if self.video.bitrate == 0 {
+            return Err(invalid("video.bitrate", "0", "must be positive"));
+        }
+        if self.capture.test_pattern.is_empty() {
+            return Err(invalid("capture.test_pattern", "", "must not be empty"));
+        }
+        if self.capture.test_width <= 0 {
+            return Err(invalid(
+                "capture.test_width",
+                &self.capture.test_width.to_string(),
+                "must be positive",
+            ));
+        }
+        if self.capture.test_height <= 0 {
+            return Err(invalid(
+                "capture.test_height",
+                &self.capture.test_height.to_string(),
+                "must be positive",
+            ));
+        }
+        if self.input.source == InputSourceKind::Script && self.input.script_file.is_none() {
+            return Err(invalid(
+                "input.script_file",
+                "",
+                "must be set when input.source is script",
+            ));
+        }
+        if !(1..=MAX_MOUSE_RATE).contains(&self.input.mouse_rate) {
+            return Err(invalid(
+                "input.mouse_rate",
+                &self.input.mouse_rate.to_string(),
+                format!("must be between 1 and {}", MAX_MOUSE_RATE),
+            ));
+        }
+
+        let tracker = &self.error_tracker;
+        if tracker.read_track_threshold == 0
+            || tracker.read_track_threshold > tracker.read_track_limit
+        {
+            return Err(invalid(
+                "error_tracker.read_track_threshold",
+                &tracker.read_track_threshold.to_string(),
+                "must be positive and not greater than read_track_limit",
+            ));
+        }
+        if tracker.send_track_threshold == 0
+            || tracker.send_track_threshold > tracker.send_track_limit
+        {
+            return Err(invalid(
+                "error_tracker.send_track_threshold",
+                &tracker.send_track_threshold.to_string(),
+                "must be positive and not greater than send_track_limit",
+            ));
+        }
+        if self.reconnect.grace_period >= self.reconnect.timeout {
+            return Err(invalid(
+                "reconnect.grace_period",
+                &self.reconnect.grace_period.to_string(),
+                "must be lower than reconnect.timeout",
+            ));
+        }
+        Ok(())
+    }
+}
+
+/// Returns the environment variable that overrides a key, e.g. `CGRS_SIGNALING_URL` for
+/// `signaling.url`.
+fn env_var(key: &str) -> String {
+    format!("{}{}", ENV_PREFIX, key.replace('.', "_").to_uppercase())
+}
+
+fn parse<T: std::str::FromStr>(key: &'static str, value: &str) -> Result<T, ConfigError> {
+    value
+        .parse::<T>()
+        .map_err(|_| invalid(key, value, "could not be parsed"))
+}
+
+fn secret_not_allowed(key: &'static str) -> ConfigError {
+    invalid(
+        key,
+        REDACTED,
+        "must be set in the secrets file or the environment",
+    )
+}
+
+fn invalid(key: &'static str, value: &str, reason: impl Into<Cow<'static, str>>) -> ConfigError {
+    ConfigError::InvalidValue {
+        key,
+        value: value.to_owned(),
+        reason: reason.into(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::path::PathBuf;
+
+    /// Writes a configuration file only read by the calling test.
+    fn config_file(name: &str, content: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("cgrs-{}-{}.toml", std::process::id(), name));
+        std::fs::write(&path, content).unwrap();
+        path
+    }
+
+    fn env<'a>(vars: &'a [(&str, &str)]) -> impl Fn(&str) -> Option<String> + 'a {
+        let vars: HashMap<_, _> = vars.iter().copied().collect();
+        move |var| vars.get(var).map(|value| value.to_string())
+    }
+
+    fn load(file: &str, vars: &[(&str, &str)], overrides: &[&str]) -> Result<Config, ConfigError> {
+        let path = config_file(file, "");
+        let overrides: Vec<String> = overrides.iter().map(|o| o.to_string()).collect();
+        Config::load_with_env(Some(&path), &overrides, env(vars))
+    }
+
+    fn invalid_key(result: Result<impl fmt::Debug, ConfigError>) -> &'static str {
+        match result {
+            Err(ConfigError::InvalidValue { key, .. }) => key,
+            other => panic!("expected an invalid value, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn file_env_and_overrides_are_applied_in_order() {
+        let path = config_file(
+            "order",
+            "[video]\nframerate = 30\nbitrate = 1000\n\n[front]\nport = 3000\n",
+        );
+        let vars = [("CGRS_VIDEO_BITRATE", "2000"), ("CGRS_FRONT_PORT", "4000")];
+        let overrides = ["front.port=5000".to_owned()];
+
+        let config = Config::load_with_env(Some(&path), &overrides, env(&vars)).unwrap();
+        assert_eq!(config.video.framerate, 30);
+        assert_eq!(config.video.bitrate, 2000);
+        assert_eq!(config.front.port, 5000);
+    }
+
+    #[test]
+    fn env_vars_are_named_after_their_key() {
+        assert_eq!(env_var("signaling.url"), "CGRS_SIGNALING_URL");
+        assert_eq!(
+            env_var("error_tracker.read_track_limit"),
+            "CGRS_ERROR_TRACKER_READ_TRACK_LIMIT"
+        );
+
+        let vars = [
+            ("CGRS_INPUT_MOUSE_MODE", "absolute"),
+            ("CGRS_ERROR_TRACKER_SEND_TRACK_LIMIT", "20000"),
+            // Only the mapped names are read
+            ("CGRS_INPUT_MOUSERATE", "1"),
+        ];
+        let config = load("env", &vars, &[]).unwrap();
+        assert_eq!(config.input.mouse_mode, MouseMode::Absolute);
+        assert_eq!(config.error_tracker.send_track_limit, 20000);
+        assert_eq!(config.input.mouse_rate, MOUSE_RATE);
+    }
+
+    #[test]
+    fn legacy_signaling_url_is_read_when_the_new_one_is_missing() {
+        let legacy = [(LEGACY_SIGNALING_URL_VAR, "ws://127.0.0.1:8080")];
+        let config = load("legacy", &legacy, &[]).unwrap();
+        assert_eq!(config.signaling.url, "ws://127.0.0.1:8080");
+
+        let both = [
+            (LEGACY_SIGNALING_URL_VAR, "ws://127.0.0.1:8080"),
+            ("CGRS_SIGNALING_URL", "ws://127.0.0.1:9090"),
+        ];
+        let config = load("legacy-both", &both, &[]).unwrap();
+        assert_eq!(config.signaling.url, "ws://127.0.0.1:9090");
+    }
+
+    #[test]
+    fn every_key_can_be_set() {
+        let values = [
+            ("webrtc.stun_address", "stun:stun.example.com:3478"),
+            ("webrtc.turn_address", "turn:turn.example.com:3478"),
+            ("webrtc.turn_user", "user"),
+            ("webrtc.turn_pass", "pass"),
+            ("webrtc.turn_shared_secret", "secret"),
+            ("webrtc.turn_credential_ttl", "600"),
+            ("webrtc.secrets_file", "secrets.toml"),
+            ("webrtc.trickle_ice", "false"),
+            ("signaling.url", "ws://127.0.0.1:8080"),
+            ("front.port", "3000"),
+            ("front.token_file", "front.token"),
+            ("video.framerate", "60"),
+            ("video.bitrate", "4000"),
+            ("capture.backend", "test"),
+            ("capture.video_source", "x11"),
+            ("capture.audio_source", "pulse"),
+            ("capture.test_pattern", "ball"),
+            ("capture.test_width", "640"),
+            ("capture.test_height", "480"),
+            ("player.backend", "headless"),
+            ("player.video_sink", "gl"),
+            ("input.source", "script"),
+            ("input.script_file", "input.jsonl"),
+            ("input.gamepad", "false"),
+            ("input.mouse_mode", "absolute"),
+            ("input.mouse_rate", "250"),
+            ("error_tracker.read_track_threshold", "5"),
+            ("error_tracker.read_track_limit", "50"),
+            ("error_tracker.send_track_threshold", "5"),
+            ("error_tracker.send_track_limit", "50"),
+            ("reconnect.grace_period", "5"),
+            ("reconnect.timeout", "60"),
+        ];
+        let keys: Vec<_> = values.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, CONFIG_KEYS);
+
+        let mut config = Config::default();
+        for (key, value) in values {
+            config.set(key, value).unwrap();
+        }
+        assert_eq!(config.front.port, 3000);
+        assert_eq!(config.reconnect.timeout, 60);
+    }
+
+    #[test]
+    fn invalid_overrides_are_rejected() {
+        assert!(matches!(
+            Config::default().set("video.fps", "60"),
+            Err(ConfigError::UnknownKey(key)) if key == "video.fps"
+        ));
+        assert!(matches!(
+            load("no-value", &[], &["video.framerate"]),
+            Err(ConfigError::UnknownKey(_))
+        ));
+        assert_eq!(
+            invalid_key(load("not-a-number", &[], &["video.bitrate=fast"])),
+            "video.bitrate"
+        );
+        assert_eq!(
+            invalid_key(load("secret", &[], &["webrtc.turn_pass=pass"])),
+            "webrtc.turn_pass"
+        );
+    }
+
+    #[test]
+    fn turn_secrets_are_rejected_in_the_file() {
+        let path = config_file("file-secret", "[webrtc]\nturn_shared_secret = \"secret\"\n");
+        assert_eq!(
+            invalid_key(Config::from_file(&path)),
+            "webrtc.turn_shared_secret"
+        );
+    }
+
+    #[test]
+    fn default_config_is_valid() {
+        Config::default().validate().unwrap();
+    }
+
+    #[test]
+    fn validate_names_the_invalid_key() {
+        let too_fast = (MAX_MOUSE_RATE + 1).to_string();
+        let cases = [
+            (
+                "webrtc.stun_address",
+                "stun.example.com",
+                "webrtc.stun_address",
+            ),
+            (
+                "webrtc.turn_address",
+                "turn.example.com",
+                "webrtc.turn_address",
+            ),
+            ("webrtc.turn_user", "user", "webrtc.turn_pass"),
+            (
+                "webrtc.turn_credential_ttl",
+                "0",
+                "webrtc.turn_credential_ttl",
+            ),
+            ("signaling.url", "http://127.0.0.1:8080", "signaling.url"),
+            ("front.port", "0", "front.port"),
+            ("front.token_file", "", "front.token_file"),
+            ("video.framerate", "0", "video.framerate"),
+            ("video.bitrate", "0", "video.bitrate"),
+            ("capture.test_pattern", "", "capture.test_pattern"),
+            ("capture.test_width", "-1", "capture.test_width"),
+            ("capture.test_height", "0", "capture.test_height"),
+            ("input.source", "script", "input.script_file"),
+            ("input.mouse_rate", "0", "input.mouse_rate"),
+            ("input.mouse_rate", &too_fast, "input.mouse_rate"),
+            (
+                "error_tracker.read_track_threshold",
+                "0",
+                "error_tracker.read_track_threshold",
+            ),
+            (
+                "error_tracker.send_track_limit",
+                "0",
+                "error_tracker.send_track_threshold",
+            ),
+            ("reconnect.grace_period", "30", "reconnect.grace_period"),
+        ];
+        for (key, value, invalid) in cases {
+            let mut config = Config::default();
+            config.set(key, value).unwrap();
+            assert_eq!(invalid_key(config.validate()), invalid, "{key}={value}");
+        }
+    }
+}
@@ -0,0 +1,75 @@
+use std::fs;
+use std::io::Error;
+use std::path::Path;
+
+use rand::RngCore;
+
+use super::front_protocol_const::FRONT_TOKEN_LEN;
+
+/// Shared secret the front end must present before sending commands.
+///
+/// A new token is generated every time the node starts and written to a file that only
+/// the current user can read, so only local processes of that user can drive the node.
+///
+/// On Windows no ACL is set: the file gets the permissions of its directory, so
+/// `front.token_file` must point to a directory only that user can read, such as the
+/// user's profile.
+pub struct FrontToken {
+    token: String,
+}
+
+impl FrontToken {
+    /// Generates a random token and writes it to `path`, replacing any previous token.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - File where the token is written.
+    ///
+    /// # Returns
+    /// The token, or an error if it could not be written.
+    pub fn create(path: &Path) -> Result<FrontToken, Error> {
+        let mut bytes = [0u8; FRONT_TOKEN_LEN];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        let token: String = bytes.iter().map(|b| format!("{:02x}", b)).collect();
+
+        // Remove the previous file so the permissions below apply to a new one
+        let _ = fs::remove_file(path);
+        write_private(path, &token)?;
+        log::info!("FRONT | Authentication token written to {}", path.display());
+
+        Ok(FrontToken { token })
+    }
+
+    /// Checks a token sent by the front end, taking the same time whatever the input.
+    pub fn matches(&self, candidate: &str) -> bool {
+        let expected = self.token.as_bytes();
+        let candidate = candidate.as_bytes();
+        if expected.len() != candidate.len() {
+            return false;
+        }
+        expected
+            .iter()
+            .zip(candidate)
+            .fold(0u8, |diff, (a, b)| diff | (a ^ b))
+            == 0
+    }
+}
+
+#[cfg(unix)]
+fn write_private(path: &Path, token: &str) -> Result<(), Error> {
+    use std::io::Write;
+    use std::os::unix::fs::OpenOptionsExt;
+
+    let mut file = fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .mode(0o600)
+        .open(path)?;
+    file.write_all(token.as_bytes())
+}
+
+#[cfg(not(unix))]
+fn write_private(path: &Path, token: &str) -> Result<(), Error> {
+    // On Windows the file inherits the permissions of its directory
+    fs::write(path, token)
+}
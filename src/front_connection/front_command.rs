@@ -0,0 +1,328 @@
+use std::fmt;
+
+use super::front_protocol_const::*;
+use crate::input::input_event::MouseMode;
+use crate::utils::message_fields::{Fields, MissingField};
+
+/// Every command the front end can send to the node.
+///
+/// The wire representation is one line of pipe-delimited text, `<command>|<field>|<field>...`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrontCommand {
+    /// Offer the games of `username` to other users.
+    StartOffering { username: String },
+    /// Play `game_name` offered by `offerer_username` for the given amount of minutes.
+    StartGame {
+        username: String,
+        offerer_username: String,
+        game_name: String,
+        minutes: u32,
+        /// Overrides `input.mouse_mode` for this session.
+        mouse_mode: Option<MouseMode>,
+    },
+    /// End the current session.
+    Disconnect,
+    /// Stop sending media without ending the current session.
+    Pause,
+    /// Send media again after a pause.
+    Resume,
+    /// Report the state of the node with a `status` event.
+    Status,
+    /// First line of every connection, with the token written by the node at startup.
+    Auth { token: String },
+}
+
+/// Reasons why a line could not be turned into a [`FrontCommand`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CommandError {
+    /// The line was empty.
+    Empty,
+    /// The line is not valid UTF-8.
+    InvalidUtf8,
+    /// The command is not part of the protocol.
+    UnknownCommand(String),
+    /// The command is missing one of its fields.
+    MissingField {
+        command: &'static str,
+        field: &'static str,
+    },
+    /// One of the fields is present but empty.
+    EmptyField {
+        command: &'static str,
+        field: &'static str,
+    },
+    /// The minutes are not a positive whole number.
+    InvalidMinutes(String),
+    /// The mouse mode is not `relative` nor `absolute`.
+    InvalidMouseMode(String),
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::Empty => write!(f, "empty command"),
+            CommandError::InvalidUtf8 => write!(f, "command is not valid UTF-8"),
+            CommandError::UnknownCommand(c) => write!(f, "unknown command: {c}"),
+            CommandError::MissingField { command, field } => {
+                write!(f, "{command} command is missing field {field}")
+            }
+            CommandError::EmptyField { command, field } => {
+                write!(f, "{command} command has an empty {field}")
+            }
+            CommandError::InvalidMinutes(m) => {
+                write!(f, "minutes must be a positive whole number, got {m:?}")
+            }
+            CommandError::InvalidMouseMode(m) => {
+                write!(f, "mouse mode must be relative or absolute, got {m:?}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for CommandError {}
+
+impl FrontCommand {
+    /// Parses a line received from the front end.
+    ///
+    /// Trailing fields not known by this version of the protocol are ignored.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The raw line, with or without its line ending.
+    ///
+    /// # Returns
+    /// The command, or the reason why the line is not a valid command.
+    pub fn parse(line: &[u8]) -> Result<FrontCommand, CommandError> {
+        let line = std::str::from_utf8(line).map_err(|_| CommandError::InvalidUtf8)?;
+        let line = line.trim_end_matches(['\n', '\r']);
+        if line.is_empty() {
+            return Err(CommandError::Empty);
+        }
+
+        let parts: Vec<&str> = line.split(SEPARATOR).collect();
+        let fields = Fields::new(parts[0], COMMANDS, &parts[1..]);
+
+        match parts[0] {
+            START_OFFERING_MSG => Ok(FrontCommand::StartOffering {
+                username: required(&fields, 0, "username")?,
+            }),
+            START_GAME_MSG => Ok(FrontCommand::StartGame {
+                username: required(&fields, 0, "username")?,
+                offerer_username: required(&fields, 1, "offerer_username")?,
+                game_name: required(&fields, 2, "game_name")?,
+                minutes: parse_minutes(&required(&fields, 3, "minutes")?)?,
+                mouse_mode: fields.optional(4).map(parse_mouse_mode).transpose()?,
+            }),
+            DISCONNECT_MSG => Ok(FrontCommand::Disconnect),
+            PAUSE_MSG => Ok(FrontCommand::Pause),
+            RESUME_MSG => Ok(FrontCommand::Resume),
+            STATUS_MSG => Ok(FrontCommand::Status),
+            AUTH_MSG => Ok(FrontCommand::Auth {
+                token: required(&fields, 0, "token")?,
+            }),
+            other => Err(CommandError::UnknownCommand(other.to_string())),
+        }
+    }
+
+    /// Encodes the command as sent by the front end, without line ending.
+    pub fn encode(&self) -> String {
+        match self {
+            FrontCommand::StartOffering { username } => {
+                [START_OFFERING_MSG, username].join(&SEPARATOR.to_string())
+            }
+            FrontCommand::StartGame {
+                username,
+                offerer_username,
+                game_name,
+                minutes,
+                mouse_mode,
+            } => {
+                let mut parts = vec![
+                    START_GAME_MSG.to_string(),
+                    username.clone(),
+                    offerer_username.clone(),
+                    game_name.clone(),
+                    minutes.to_string(),
+                ];
+                if let Some(mode) = mouse_mode {
+                    parts.push(mode.name().to_string());
+                }
+                parts.join(&SEPARATOR.to_string())
+            }
+            FrontCommand::Disconnect => DISCONNECT_MSG.to_string(),
+            FrontCommand::Pause => PAUSE_MSG.to_string(),
+            FrontCommand::Resume => RESUME_MSG.to_string(),
+            FrontCommand::Status => STATUS_MSG.to_string(),
+            FrontCommand::Auth { token } => [AUTH_MSG, token].join(&SEPARATOR.to_string()),
+        }
+    }
+}
+
+/// Every command of the protocol, so errors can name the command as `'static`.
+const COMMANDS: &[&str] = &[
+    START_OFFERING_MSG,
+    START_GAME_MSG,
+    DISCONNECT_MSG,
+    PAUSE_MSG,
+    RESUME_MSG,
+    STATUS_MSG,
+    AUTH_MSG,
+];
+
+impl From<MissingField> for CommandError {
+    fn from(missing: MissingField) -> Self {
+        CommandError::MissingField {
+            command: missing.kind,
+            field: missing.field,
+        }
+    }
+}
+
+/// Returns a field the command requires, which must not be empty.
+fn required(fields: &Fields, index: usize, field: &'static str) -> Result<String, CommandError> {
+    let value = fields.get(index, field)?;
+    if value.trim().is_empty() {
+        return Err(CommandError::EmptyField {
+            command: fields.kind(),
+            field,
+        });
+    }
+    Ok(value)
+}
+
+fn parse_mouse_mode(mode: &str) -> Result<MouseMode, CommandError> {
+    mode.trim()
+        .parse()
+        .map_err(|_| CommandError::InvalidMouseMode(mode.to_string()))
+}
+
+fn parse_minutes(minutes: &str) -> Result<u32, CommandError> {
+    match minutes.trim().parse::<u32>() {
+        Ok(m) if m > 0 => Ok(m),
+        _ => Err(CommandError::InvalidMinutes(minutes.to_string())),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// A field that can be sent: not blank and without separators or line endings.
+    fn field() -> impl Strategy<Value = String> {
+        "[^|\r\n\\s][^|\r\n]{0,20}"
+    }
+
+    fn command() -> impl Strategy<Value = FrontCommand> {
+        let mouse_mode = prop_oneof![
+            Just(None),
+            Just(Some(MouseMode::Relative)),
+            Just(Some(MouseMode::Absolute)),
+        ];
+        prop_oneof![
+            field().prop_map(|username| FrontCommand::StartOffering { username }),
+            (field(), field(), field(), 1..=u32::MAX, mouse_mode).prop_map(
+                |(username, offerer_username, game_name, minutes, mouse_mode)| {
+                    FrontCommand::StartGame {
+                        username,
+                        offerer_username,
+                        game_name,
+                        minutes,
+                        mouse_mode,
+                    }
+                }
+            ),
+            Just(FrontCommand::Disconnect),
+            Just(FrontCommand::Pause),
+            Just(FrontCommand::Resume),
+            Just(FrontCommand::Status),
+            field().prop_map(|token| FrontCommand::Auth { token }),
+        ]
+    }
+
+    /// Lines made of known commands and arbitrary fields, to reach the parsing of each field.
+    fn line() -> impl Strategy<Value = String> {
+        let commands = [
+            START_OFFERING_MSG,
+            START_GAME_MSG,
+            DISCONNECT_MSG,
+            PAUSE_MSG,
+            RESUME_MSG,
+            STATUS_MSG,
+            AUTH_MSG,
+        ];
+        (
+            proptest::sample::select(commands.to_vec()),
+            // Line endings are only dropped at the end of the line, so fields are left without
+            proptest::collection::vec("[^\r\n]{0,12}", 0..7),
+        )
+            .prop_map(|(command, fields)| {
+                std::iter::once(command.to_string())
+                    .chain(fields)
+                    .collect::<Vec<_>>()
+                    .join(&SEPARATOR.to_string())
+            })
+    }
+
+    proptest! {
+        #[test]
+        fn encoded_commands_parse_back(command in command()) {
+            let line = command.encode();
+            prop_assert_eq!(FrontCommand::parse(line.as_bytes()), Ok(command.clone()));
+            let line = format!("{line}\r\n");
+            prop_assert_eq!(FrontCommand::parse(line.as_bytes()), Ok(command));
+        }
+
+        #[test]
+        fn arbitrary_bytes_do_not_panic(bytes in proptest::collection::vec(any::<u8>(), 0..64)) {
+            let _ = FrontCommand::parse(&bytes);
+        }
+
+        #[test]
+        fn arbitrary_fields_do_not_panic(line in line()) {
+            if let Ok(command) = FrontCommand::parse(line.as_bytes()) {
+                // Whatever is accepted can be sent again
+                prop_assert_eq!(FrontCommand::parse(command.encode().as_bytes()), Ok(command));
+            }
+        }
+    }
+
+    #[test]
+    fn missing_and_empty_fields_are_named() {
+        assert_eq!(
+            FrontCommand::parse(b"startGameWithUser|user|offerer|game"),
+            Err(CommandError::MissingField {
+                command: START_GAME_MSG,
+                field: "minutes",
+            })
+        );
+        assert_eq!(
+            FrontCommand::parse(b"auth| "),
+            Err(CommandError::EmptyField {
+                command: AUTH_MSG,
+                field: "token",
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_values_are_rejected() {
+        assert_eq!(
+            FrontCommand::parse(b"startGameWithUser|user|offerer|game|0"),
+            Err(CommandError::InvalidMinutes("0".to_owned()))
+        );
+        assert_eq!(
+            FrontCommand::parse(b"startGameWithUser|user|offerer|game|30|mouse"),
+            Err(CommandError::InvalidMouseMode("mouse".to_owned()))
+        );
+        assert_eq!(FrontCommand::parse(b"\r\n"), Err(CommandError::Empty));
+        assert_eq!(
+            FrontCommand::parse(&[0xff, 0xfe]),
+            Err(CommandError::InvalidUtf8)
+        );
+        assert_eq!(
+            FrontCommand::parse(b"jump"),
+            Err(CommandError::UnknownCommand("jump".to_owned()))
+        );
+    }
+}
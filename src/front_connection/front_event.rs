@@ -0,0 +1,66 @@
+use serde::Serialize;
+use tokio::sync::mpsc::UnboundedSender;
+
+use crate::services::session_state::SessionState;
+
+/// Events pushed by the node to the front end.
+///
+/// Each event is written as a single JSON object followed by a newline, with the kind of
+/// event in the `event` field:
+///
+/// ```text
+/// {"event":"sessionStarting"}
+/// {"event":"peerConnected"}
+/// {"event":"streamStats","rttMs":12.5,"bytesSent":1048576,"bytesReceived":2048}
+/// {"event":"sessionEnded","reason":"stopped"}
+/// {"event":"error","code":"signaling","message":"signaling error: connection reset"}
+/// {"event":"status","state":"streaming","paused":false}
+/// ```
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(
+    tag = "event",
+    rename_all = "camelCase",
+    rename_all_fields = "camelCase"
+)]
+pub enum FrontEvent {
+    /// A session was requested and the peers are negotiating.
+    SessionStarting,
+    /// The peers are connected and media is flowing.
+    PeerConnected,
+    /// Periodic statistics of the peer connection while streaming.
+    StreamStats {
+        /// Current round trip time in milliseconds.
+        rtt_ms: f64,
+        /// Bytes sent over the connection since it was established.
+        bytes_sent: u64,
+        /// Bytes received over the connection since it was established.
+        bytes_received: u64,
+    },
+    /// The session ended. `reason` is `stopped` when it ended normally.
+    SessionEnded { reason: String },
+    /// The session failed. `code` is one of the codes of
+    /// [`NodeError::code`](crate::utils::node_error::NodeError::code).
+    Error { code: &'static str, message: String },
+    /// Reply to the `status` command. `state` is `idle` when there is no session.
+    Status { state: SessionState, paused: bool },
+}
+
+/// Sends events to the front end. Can be cloned and shared between tasks.
+#[derive(Clone)]
+pub struct FrontEventSender {
+    tx: UnboundedSender<FrontEvent>,
+}
+
+impl FrontEventSender {
+    pub fn new(tx: UnboundedSender<FrontEvent>) -> FrontEventSender {
+        FrontEventSender { tx }
+    }
+
+    /// Queues an event to be written to the front end. Events sent after the front end
+    /// disconnected are dropped.
+    pub fn send(&self, event: FrontEvent) {
+        if self.tx.send(event).is_err() {
+            log::warn!("FRONT | Front end disconnected, event dropped");
+        }
+    }
+}
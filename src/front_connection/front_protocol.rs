@@ -1,114 +1,628 @@
 use std::io::Error;
+use std::net::SocketAddr;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
 use tokio::io::AsyncBufReadExt;
+use tokio::io::AsyncWriteExt;
 use tokio::io::BufReader;
-use tokio::net::TcpListener;
+use tokio::net::tcp::{OwnedReadHalf, OwnedWriteHalf};
+use tokio::net::{TcpListener, TcpStream};
 use tokio::sync::mpsc;
+use tokio::sync::mpsc::error::TrySendError;
+use tokio::task::JoinHandle;
+use tokio::time::timeout;
 
+use crate::config::node_config::FrontConfig;
+use crate::front_connection::front_auth::FrontToken;
+use crate::front_connection::front_command::FrontCommand;
+use crate::front_connection::front_event::{FrontEvent, FrontEventSender};
 use crate::front_connection::front_protocol_const::*;
+use crate::services::session_state::{Session, SessionState};
+use crate::utils::node_error::NodeError;
+
+/// Control channel with the front end.
+///
+/// The node listens on a single local port for the whole run. Every connection must first
+/// authenticate with `auth|<token>`, using the token the node writes to
+/// `front.token_file` at startup; connections that do not are closed. The front end can
+/// disconnect and connect again at any moment; a new authenticated connection replaces
+/// the previous one. Commands that start a session are queued until the node is ready for them, the
+/// rest are handled as soon as they arrive.
 pub struct FrontConnection {
-    rx: mpsc::Receiver<Client>,
+    rx: mpsc::Receiver<FrontCommand>,
     rx_disconnect: mpsc::Receiver<bool>,
+    events: FrontEventSender,
+    session: Arc<Mutex<Option<Session>>>,
 }
 
-pub enum ClientType {
-    SENDER,
-    RECEIVER,
-}
-
-pub struct Client {
-    pub client_type: ClientType,
-    pub username: String,
-    pub user_to_connect: Option<String>,
-    pub game_name: Option<String>,
-    pub minutes: Option<String>,
+/// Handles the commands received on the control channel.
+struct Control {
+    tx: mpsc::Sender<FrontCommand>,
+    tx_disconnect: mpsc::Sender<bool>,
+    events: FrontEventSender,
+    session: Arc<Mutex<Option<Session>>>,
 }
 
 impl FrontConnection {
-    pub async fn new(port: &str) -> Result<FrontConnection, Error> {
-        let listener = TcpListener::bind(FRONT_IP.to_string() + port).await?;
+    pub async fn new(config: &FrontConfig) -> Result<FrontConnection, NodeError> {
+        let token =
+            FrontToken::create(Path::new(&config.token_file)).map_err(NodeError::FrontProtocol)?;
+        let listener = TcpListener::bind(FRONT_IP.to_string() + &config.port.to_string())
+            .await
+            .map_err(NodeError::FrontProtocol)?;
+
+        let (tx, rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let (tx_disconnect, rx_disconnect) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let (tx_events, rx_events) = mpsc::unbounded_channel();
+
+        let events = FrontEventSender::new(tx_events);
+        let session = Arc::new(Mutex::new(None));
+
+        let control = Control {
+            tx,
+            tx_disconnect,
+            events: events.clone(),
+            session: session.clone(),
+        };
+        tokio::spawn(serve(listener, Arc::new(token), control, rx_events));
+
+        Ok(FrontConnection {
+            rx,
+            rx_disconnect,
+            events,
+            session,
+        })
+    }
 
-        let mut socket = listener.accept().await?.0;
+    /// Creates a connection without front end, used when the node is run from the command
+    /// line.
+    ///
+    /// Events are printed to the standard output as JSON lines and Ctrl-C is handled as a
+    /// `disconnect` command.
+    pub fn headless() -> FrontConnection {
+        let (_tx, rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let (tx_disconnect, rx_disconnect) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let (tx_events, mut rx_events) = mpsc::unbounded_channel();
 
-        let (tx, rx) = mpsc::channel(100);
-        let (tx_disconnect, rx_disconnect) = mpsc::channel(100);
+        tokio::spawn(async move {
+            while let Some(event) = rx_events.recv().await {
+                match serde_json::to_string(&event) {
+                    Ok(line) => println!("{line}"),
+                    Err(e) => log::error!("FRONT | Error serializing event {:?} | {}", event, e),
+                }
+            }
+        });
 
         tokio::spawn(async move {
-            let (socket_reader, _socket_writer) = socket.split();
-            let mut reader = BufReader::new(socket_reader);
-            loop {
-                let mut buffer = Vec::new();
-                let bytes_read = reader.read_until(b'\n', &mut buffer).await;
-                if bytes_read.expect("Failed to read until newline") == 0 {
+            while tokio::signal::ctrl_c().await.is_ok() {
+                log::info!("FRONT | Ctrl-C received, ending the session");
+                if tx_disconnect.send(true).await.is_err() {
                     return;
-                };
-                let msg = String::from_utf8(buffer).expect("Failed to convert to string");
-                let msg = msg.trim_end_matches('\n').to_string();
-                handle_message(tx.clone(), tx_disconnect.clone(), msg).await;
+                }
             }
         });
 
-        Ok(FrontConnection { rx, rx_disconnect })
+        FrontConnection {
+            rx,
+            rx_disconnect,
+            events: FrontEventSender::new(tx_events),
+            session: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    /// Returns a sender to push events to the front end.
+    pub fn events(&self) -> FrontEventSender {
+        self.events.clone()
     }
 
-    pub async fn waiting_to_start(&mut self) -> Result<Client, Error> {
+    /// Reports the progress of a session to the front end until it ends. The session is
+    /// also the target of the `disconnect`, `pause`, `resume` and `status` commands.
+    ///
+    /// # Arguments
+    ///
+    /// * `session` - The session to follow.
+    pub fn follow_session(&mut self, session: &Session) {
+        if let Ok(mut current) = self.session.lock() {
+            *current = Some(session.clone());
+        }
+        // Drop disconnections received for a previous session
+        while self.rx_disconnect.try_recv().is_ok() {}
+
+        let mut watcher = session.subscribe();
+        let events = self.events();
+        tokio::spawn(async move {
+            while let Some(state) = watcher.changed().await {
+                match state {
+                    SessionState::Negotiating => events.send(FrontEvent::SessionStarting),
+                    SessionState::Streaming => events.send(FrontEvent::PeerConnected),
+                    SessionState::Ended => return,
+                    _ => {}
+                }
+            }
+        });
+    }
+
+    /// Stops targeting the followed session with the front end commands. Called once the
+    /// session is over, even if it failed before reaching its end.
+    pub fn release_session(&self) {
+        if let Ok(mut current) = self.session.lock() {
+            *current = None;
+        }
+    }
+
+    /// Waits for the front end to request a session.
+    ///
+    /// # Returns
+    /// A [`FrontCommand::StartOffering`] or a [`FrontCommand::StartGame`].
+    pub async fn waiting_to_start(&mut self) -> Result<FrontCommand, NodeError> {
         match self.rx.recv().await {
-            Some(client) => Ok(client),
-            None => Err(Error::new(
-                std::io::ErrorKind::Other,
+            Some(command) => Ok(command),
+            None => Err(NodeError::FrontProtocol(Error::new(
+                std::io::ErrorKind::ConnectionAborted,
                 "Failed to receive client.",
-            )),
+            ))),
         }
     }
 
-    pub async fn waiting_to_disconnect(&mut self) -> Result<(), Error> {
+    pub async fn waiting_to_disconnect(&mut self) -> Result<(), NodeError> {
         match self.rx_disconnect.recv().await {
             Some(_b) => Ok(()),
-            None => Err(Error::new(
-                std::io::ErrorKind::Other,
+            None => Err(NodeError::FrontProtocol(Error::new(
+                std::io::ErrorKind::ConnectionAborted,
                 "Failed to receive disconnect.",
-            )),
+            ))),
         }
     }
 }
 
-pub async fn handle_message(
-    tx: mpsc::Sender<Client>,
-    tx_disconnect: mpsc::Sender<bool>,
-    msg: String,
+impl Control {
+    /// Parses a line received from the front end and handles the command.
+    ///
+    /// Invalid lines, commands that need a session when there is none and commands that do
+    /// not fit in the queue are answered with an `error` event and otherwise ignored. It
+    /// never waits, so the events keep being written while the node is busy.
+    ///
+    /// # Arguments
+    ///
+    /// * `line` - The raw line received.
+    ///
+    /// # Returns
+    /// `false` if the node is no longer waiting for commands.
+    fn handle_message(&self, line: &[u8]) -> bool {
+        let command = match FrontCommand::parse(line) {
+            Ok(command) => command,
+            Err(e) => {
+                log::warn!("FRONT | Invalid command | {}", e);
+                self.reply_error(&e.to_string());
+                return true;
+            }
+        };
+
+        match command {
+            FrontCommand::Status => {
+                let (state, paused) = match self.running_session() {
+                    Some(session) => (session.state(), session.is_paused()),
+                    None => (SessionState::Idle, false),
+                };
+                self.events.send(FrontEvent::Status { state, paused });
+                true
+            }
+            FrontCommand::Disconnect | FrontCommand::Pause | FrontCommand::Resume => {
+                let Some(session) = self.running_session() else {
+                    self.reply_error("no session running");
+                    return true;
+                };
+                match command {
+                    FrontCommand::Disconnect => self.forward(self.tx_disconnect.try_send(true)),
+                    _ => {
+                        session.set_paused(command == FrontCommand::Pause);
+                        true
+                    }
+                }
+            }
+            // The connection was already authenticated when it was accepted
+            FrontCommand::Auth { .. } => true,
+            command => self.forward(self.tx.try_send(command)),
+        }
+    }
+
+    /// Returns the session being followed, if it has not ended.
+    fn running_session(&self) -> Option<Session> {
+        let current = self.session.lock().ok()?;
+        current
+            .as_ref()
+            .filter(|session| session.state() != SessionState::Ended)
+            .cloned()
+    }
+
+    /// Reports the outcome of queueing a command.
+    ///
+    /// # Returns
+    /// `false` if the node is no longer listening.
+    fn forward<T>(&self, sent: Result<(), TrySendError<T>>) -> bool {
+        match sent {
+            Ok(()) => true,
+            Err(TrySendError::Full(_)) => {
+                log::warn!("FRONT | Command queue full, command dropped");
+                self.reply_error("too many commands pending, try again later");
+                true
+            }
+            Err(TrySendError::Closed(_)) => {
+                log::error!("FRONT | Command received but the node is not listening");
+                false
+            }
+        }
+    }
+
+    fn reply_error(&self, message: &str) {
+        self.events.send(protocol_error(message));
+    }
+}
+
+/// Builds the `error` event sent when the front end breaks the protocol.
+fn protocol_error(message: &str) -> FrontEvent {
+    let error = NodeError::FrontProtocol(Error::new(std::io::ErrorKind::InvalidData, message));
+    FrontEvent::Error {
+        code: error.code(),
+        message: error.to_string(),
+    }
+}
+
+/// Accepts the front end connections, handles their commands and writes the events to the
+/// one currently connected.
+///
+/// # Arguments
+///
+/// * `listener` - The listener of the control port.
+/// * `token` - The token the connections must authenticate with.
+/// * `control` - Handles the commands received.
+/// * `rx_events` - A channel from which the events are received.
+async fn serve(
+    listener: TcpListener,
+    token: Arc<FrontToken>,
+    control: Control,
+    mut rx_events: mpsc::UnboundedReceiver<FrontEvent>,
 ) {
-    let parts: Vec<&str> = msg.split('|').collect();
-    match parts[0] {
-        START_OFFERING_MSG => {
-            let username = parts[1].trim_end_matches('\n').to_string();
-            let client = Client {
-                client_type: ClientType::SENDER,
-                username,
-                user_to_connect: None,
-                game_name: None,
-                minutes: None,
-            };
-            tx.send(client).await.expect("Failed to send client."); //TODO: Handle error
-        }
-        START_GAME_MSG => {
-            let username = parts[1].to_string();
-            let user_to_connect = parts[2].to_string();
-            let game_name = parts[3].to_string();
-            let minutes = parts[4].trim_end_matches('\n').to_string();
-            let client = Client {
-                client_type: ClientType::RECEIVER,
-                username,
-                user_to_connect: Some(user_to_connect),
-                game_name: Some(game_name),
-                minutes: Some(minutes),
-            };
-            tx.send(client).await.expect("Failed to send client."); //TODO: Handle error
-        }
-        DISCONNECT_MSG => {
-            tx_disconnect
-                .send(true)
+    let (tx_lines, mut rx_lines) = mpsc::channel(COMMAND_QUEUE_SIZE);
+    let (tx_authenticated, mut rx_authenticated) = mpsc::channel(1);
+    let mut writer: Option<OwnedWriteHalf> = None;
+    let mut reader: Option<JoinHandle<()>> = None;
+
+    loop {
+        tokio::select! {
+            accepted = listener.accept() => match accepted {
+                Ok((socket, address)) => {
+                    tokio::spawn(authenticate(
+                        socket,
+                        address,
+                        token.clone(),
+                        tx_authenticated.clone(),
+                    ));
+                }
+                Err(e) => log::error!("FRONT | Error accepting front end connection | {}", e),
+            },
+            Some((socket_reader, socket_writer)) = rx_authenticated.recv() => {
+                // Only one front end is served, a new connection replaces the previous one
+                if let Some(previous) = reader.take() {
+                    previous.abort();
+                }
+                writer = Some(socket_writer);
+                reader = Some(tokio::spawn(read_lines(socket_reader, tx_lines.clone())));
+            }
+            Some(line) = rx_lines.recv() => {
+                if !control.handle_message(&line) {
+                    return;
+                }
+            }
+            Some(event) = rx_events.recv() => {
+                if let Some(socket_writer) = writer.as_mut() {
+                    if !write_event(socket_writer, &event).await {
+                        writer = None;
+                    }
+                } else {
+                    log::debug!("FRONT | No front end connected, event dropped | {:?}", event);
+                }
+            }
+        }
+    }
+}
+
+/// Waits for a new connection to authenticate and hands it to the control loop.
+///
+/// Connections that send anything other than a valid `auth|<token>` line, or nothing at
+/// all within [`AUTH_TIMEOUT`] seconds, receive an `error` event and are closed.
+///
+/// # Arguments
+///
+/// * `socket` - The connection accepted.
+/// * `address` - Address of the peer, used for logging.
+/// * `token` - The token the connection must authenticate with.
+/// * `tx_authenticated` - A channel to hand the connection to the control loop.
+async fn authenticate(
+    socket: TcpStream,
+    address: SocketAddr,
+    token: Arc<FrontToken>,
+    tx_authenticated: mpsc::Sender<(BufReader<OwnedReadHalf>, OwnedWriteHalf)>,
+) {
+    let (socket_reader, mut socket_writer) = socket.into_split();
+    let mut reader = BufReader::new(socket_reader);
+    let mut line = Vec::new();
+
+    let read = timeout(
+        Duration::from_secs(AUTH_TIMEOUT),
+        reader.read_until(b'\n', &mut line),
+    )
+    .await;
+    let authenticated = match (read, FrontCommand::parse(&line)) {
+        (Ok(Ok(n)), Ok(FrontCommand::Auth { token: candidate })) if n > 0 => {
+            token.matches(&candidate)
+        }
+        _ => false,
+    };
+
+    if !authenticated {
+        log::warn!(
+            "FRONT | Connection from {} rejected, not authenticated",
+            address
+        );
+        write_event(
+            &mut socket_writer,
+            &protocol_error("authentication required"),
+        )
+        .await;
+        return;
+    }
+
+    log::info!("FRONT | Front end connected from {}", address);
+    let _ = tx_authenticated.send((reader, socket_writer)).await;
+}
+
+/// Reads lines from a front end connection until it is closed.
+///
+/// # Arguments
+///
+/// * `reader` - The read half of the front end socket.
+/// * `tx_lines` - A channel to send the lines read.
+async fn read_lines(mut reader: BufReader<OwnedReadHalf>, tx_lines: mpsc::Sender<Vec<u8>>) {
+    loop {
+        let mut buffer = Vec::new();
+        match reader.read_until(b'\n', &mut buffer).await {
+            Ok(0) => {
+                log::info!("FRONT | Front end disconnected");
+                return;
+            }
+            Ok(_) => {}
+            Err(e) => {
+                log::error!("FRONT | Error reading from front end | {}", e);
+                return;
+            }
+        }
+        if tx_lines.send(buffer).await.is_err() {
+            return;
+        }
+    }
+}
+
+/// Writes an event to the front end as a JSON object in a single line.
+///
+/// # Arguments
+///
+/// * `writer` - The write half of the front end socket.
+/// * `event` - The event to write.
+///
+/// # Returns
+/// `false` if the front end is no longer connected.
+async fn write_event(writer: &mut OwnedWriteHalf, event: &FrontEvent) -> bool {
+    let mut line = match serde_json::to_string(event) {
+        Ok(line) => line,
+        Err(e) => {
+            log::error!("FRONT | Error serializing event {:?} | {}", event, e);
+            return true;
+        }
+    };
+    line.push('\n');
+    if let Err(e) = writer.write_all(line.as_bytes()).await {
+        log::error!("FRONT | Error writing event | {}", e);
+        return false;
+    }
+    true
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::Value;
+    use tokio::io::Lines;
+
+    struct Harness {
+        control: Control,
+        rx: mpsc::Receiver<FrontCommand>,
+        rx_events: mpsc::UnboundedReceiver<FrontEvent>,
+    }
+
+    /// Builds a control whose command queue holds `queue_size` commands.
+    fn control(queue_size: usize) -> Harness {
+        let (tx, rx) = mpsc::channel(queue_size);
+        let (tx_disconnect, _) = mpsc::channel(queue_size);
+        let (tx_events, rx_events) = mpsc::unbounded_channel();
+        Harness {
+            control: Control {
+                tx,
+                tx_disconnect,
+                events: FrontEventSender::new(tx_events),
+                session: Arc::new(Mutex::new(None)),
+            },
+            rx,
+            rx_events,
+        }
+    }
+
+    #[test]
+    fn full_queue_is_answered_with_an_error() {
+        let mut harness = control(1);
+
+        assert!(harness.control.handle_message(b"startOffering|first\n"));
+        assert!(harness.control.handle_message(b"startOffering|second\n"));
+
+        assert_eq!(
+            harness.rx.try_recv(),
+            Ok(FrontCommand::StartOffering {
+                username: "first".to_owned(),
+            })
+        );
+        assert!(harness.rx.try_recv().is_err());
+        assert!(matches!(
+            harness.rx_events.try_recv(),
+            Ok(FrontEvent::Error {
+                code: "front_protocol",
+                ..
+            })
+        ));
+    }
+
+    #[test]
+    fn closed_queue_stops_the_control() {
+        let harness = control(1);
+        drop(harness.rx);
+
+        assert!(!harness.control.handle_message(b"startOffering|user\n"));
+    }
+
+    #[test]
+    fn commands_without_session_are_answered_with_an_error() {
+        let mut harness = control(1);
+
+        assert!(harness.control.handle_message(b"pause\n"));
+        assert!(harness.control.handle_message(b"disconnect\n"));
+
+        for _ in 0..2 {
+            assert!(matches!(
+                harness.rx_events.try_recv(),
+                Ok(FrontEvent::Error { message, .. }) if message.contains("no session running")
+            ));
+        }
+    }
+
+    struct Served {
+        address: SocketAddr,
+        token: String,
+        rx: mpsc::Receiver<FrontCommand>,
+    }
+
+    /// Serves the control port on a free port, with a new token.
+    async fn serve_control(name: &str) -> Served {
+        let path = std::env::temp_dir().join(format!("cgrs-{}-{}.token", name, std::process::id()));
+        let token = FrontToken::create(&path).expect("token not written");
+        let token_text = std::fs::read_to_string(&path).expect("token not readable");
+        let _ = std::fs::remove_file(&path);
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.expect("bind failed");
+        let address = listener.local_addr().expect("no local address");
+        let (tx, rx) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let (tx_disconnect, _) = mpsc::channel(COMMAND_QUEUE_SIZE);
+        let (tx_events, rx_events) = mpsc::unbounded_channel();
+        let control = Control {
+            tx,
+            tx_disconnect,
+            events: FrontEventSender::new(tx_events),
+            session: Arc::new(Mutex::new(None)),
+        };
+        tokio::spawn(serve(listener, Arc::new(token), control, rx_events));
+
+        Served {
+            address,
+            token: token_text,
+            rx,
+        }
+    }
+
+    struct Client {
+        lines: Lines<BufReader<OwnedReadHalf>>,
+        writer: OwnedWriteHalf,
+    }
+
+    impl Client {
+        async fn connect(address: SocketAddr) -> Client {
+            let socket = TcpStream::connect(address).await.expect("connect failed");
+            let (reader, writer) = socket.into_split();
+            Client {
+                lines: BufReader::new(reader).lines(),
+                writer,
+            }
+        }
+
+        async fn send(&mut self, line: &str) {
+            self.writer
+                .write_all(format!("{line}\n").as_bytes())
                 .await
-                .expect("Failed to send disconnect.");
+                .expect("write failed");
         }
-        _ => (),
+
+        /// Returns the next event written by the node, `None` once the connection is closed.
+        async fn next_event(&mut self) -> Option<Value> {
+            let line = timeout(Duration::from_secs(AUTH_TIMEOUT), self.lines.next_line())
+                .await
+                .expect("no reply from the node")
+                .ok()??;
+            Some(serde_json::from_str(&line).expect("event is not JSON"))
+        }
+    }
+
+    /// Checks the connection is answered with the authentication error and closed.
+    async fn assert_rejected(client: &mut Client) {
+        let event = client.next_event().await.expect("no error event");
+        assert_eq!(event["event"], "error");
+        assert_eq!(event["code"], "front_protocol");
+        assert!(event["message"]
+            .as_str()
+            .is_some_and(|m| m.contains("authentication required")));
+        assert_eq!(client.next_event().await, None);
+    }
+
+    #[tokio::test]
+    async fn connection_without_token_is_closed() {
+        let mut served = serve_control("no-token").await;
+        let mut client = Client::connect(served.address).await;
+
+        client.send("startOffering|user").await;
+
+        assert_rejected(&mut client).await;
+        assert!(served.rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn wrong_token_is_rejected() {
+        let mut served = serve_control("wrong-token").await;
+        let mut client = Client::connect(served.address).await;
+
+        let wrong = "0".repeat(served.token.len());
+        client.send(&format!("auth|{wrong}")).await;
+
+        assert_rejected(&mut client).await;
+        assert!(served.rx.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn authenticated_connection_can_send_commands() {
+        let mut served = serve_control("valid-token").await;
+        let mut client = Client::connect(served.address).await;
+
+        client.send(&format!("auth|{}", served.token)).await;
+        client.send("status").await;
+        let event = client.next_event().await.expect("no status event");
+        assert_eq!(event["event"], "status");
+        assert_eq!(event["state"], "idle");
+
+        client.send("startOffering|user").await;
+        let command = timeout(Duration::from_secs(AUTH_TIMEOUT), served.rx.recv())
+            .await
+            .expect("command not received");
+        assert_eq!(
+            command,
+            Some(FrontCommand::StartOffering {
+                username: "user".to_owned(),
+            })
+        );
     }
 }
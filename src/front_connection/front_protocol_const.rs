@@ -1,5 +1,18 @@
 pub const FRONT_IP: &str = "127.0.0.1:";
-pub const FRONT_PORT: &str = "2930";
+pub const FRONT_PORT: u16 = 2930;
+// File where the token the front end must authenticate with is written at startup
+pub const FRONT_TOKEN_FILE: &str = "cgrs.front.token";
+// Bytes of randomness of the front end token
+pub const FRONT_TOKEN_LEN: usize = 32;
+// Seconds a new connection has to authenticate before it is closed
+pub const AUTH_TIMEOUT: u64 = 5;
 pub const START_OFFERING_MSG: &str = "startOffering";
 pub const START_GAME_MSG: &str = "startGameWithUser";
 pub const DISCONNECT_MSG: &str = "disconnect";
+pub const PAUSE_MSG: &str = "pause";
+pub const RESUME_MSG: &str = "resume";
+pub const STATUS_MSG: &str = "status";
+pub const AUTH_MSG: &str = "auth";
+// Commands received but not processed yet
+pub const COMMAND_QUEUE_SIZE: usize = 100;
+pub const SEPARATOR: char = '|';
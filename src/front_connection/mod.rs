@@ -1,2 +1,5 @@
+pub mod front_auth;
+pub mod front_command;
+pub mod front_event;
 pub mod front_protocol;
 pub mod front_protocol_const;
@@ -1,24 +1,22 @@
 use gstreamer::{prelude::*, Element, Pipeline};
 
-use std::{
-    collections::HashMap,
-    io::{self, Error},
-    sync::Arc,
-};
+use std::collections::HashMap;
 
-use tokio::sync::mpsc::Sender;
-use tokio::sync::Barrier;
+use tokio::sync::mpsc::{Sender, UnboundedReceiver};
 
 use crate::{
-    sound::audio_capture,
+    config::node_config::VideoConfig,
+    gstreamer_pipeline::capture_backend::CaptureBackend,
+    services::session_state::SessionWatcher,
     utils::{
         gstreamer_utils::{pull_sample, read_bus},
-        shutdown,
+        node_error::NodeError,
+        supervisor::Task,
     },
-    video::{video_capture, video_const::GSTREAMER_FRAMES},
 };
 
 pub const PIPELINE_NAME: &str = "AUDIO VIDEO CAPTURE";
+const VIDEO_SINK_NAME: &str = "video sink";
 
 /// Creates a GStreamer pipeline used for video and audio capture.
 ///
@@ -30,6 +28,7 @@ pub const PIPELINE_NAME: &str = "AUDIO VIDEO CAPTURE";
 /// * `audio_elements` - A HashMap containing the GStreamer video elements required for the pipeline.
 /// * `video_caps` - The capabilities of the video data to be captured.
 /// * `audio_caps` - The capabilities of the audio data to be captured.
+/// * `task` - Handle of the capture task, notified if a sample cannot be pulled.
 ///
 /// # Returns
 ///  A Result containing the constructed GStreamer pipeline in case of success. Otherwise
@@ -41,9 +40,10 @@ fn create_pipeline(
     tx_audio: Sender<Vec<u8>>,
     video_caps: gstreamer::Caps,
     audio_caps: gstreamer::Caps,
-    shutdown: shutdown::Shutdown,
-) -> Result<Pipeline, Error> {
+    task: Task,
+) -> Result<Pipeline, NodeError> {
     let video_sink = gstreamer_app::AppSink::builder()
+        .name(VIDEO_SINK_NAME)
         .caps(&gstreamer::Caps::builder("application/x-rtp").build())
         .build();
 
@@ -68,11 +68,14 @@ fn create_pipeline(
         &audio_elements["pay"],
         &audio_sink.upcast_ref(),
     ]) {
-        return Err(Error::new(io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer(
+            "Error adding elements to the capture pipeline",
+            e,
+        ));
     }
 
     if let Err(e) = video_elements["src"].link_filtered(&video_elements["queue"], &video_caps) {
-        return Err(Error::new(io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer("Error linking video source", e));
     };
 
     if let Err(e) = gstreamer::Element::link_many([
@@ -82,11 +85,11 @@ fn create_pipeline(
         &video_elements["pay"],
         &video_sink.upcast_ref(),
     ]) {
-        return Err(Error::new(io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer("Error linking video elements", e));
     };
 
     if let Err(e) = audio_elements["src"].link_filtered(&audio_elements["queue"], &audio_caps) {
-        return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer("Error linking audio source", e));
     }
 
     if let Err(e) = gstreamer::Element::link_many([
@@ -97,7 +100,7 @@ fn create_pipeline(
         &audio_elements["pay"],
         &audio_sink.upcast_ref(),
     ]) {
-        return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer("Error linking audio elements", e));
     }
 
     video_sink.set_callbacks(
@@ -107,13 +110,7 @@ fn create_pipeline(
                     Ok(_) => Ok(gstreamer::FlowSuccess::Ok),
                     Err(err) => {
                         log::error!("VIDEO CAPTURE | {}", err);
-                        let shutdown_cpy = shutdown.clone();
-                        tokio::spawn(async move {
-                            shutdown_cpy
-                                .notify_error(false, "Video capture Set callbacks")
-                                .await;
-                            log::error!("SENDER | Notify error sended");
-                        });
+                        task.fail("Error pulling video sample");
                         Err(gstreamer::FlowError::Error)
                     }
                 },
@@ -138,40 +135,53 @@ fn create_pipeline(
     Ok(pipeline)
 }
 
+/// Captures the game and sends the encoded audio and video once the session is streaming.
+///
+/// # Arguments
+///
+/// * `tx_video` - A `Sender<Vec<u8>>` used to send video frames.
+/// * `tx_audio` - A `Sender<Vec<u8>>` used to send audio frames.
+/// * `task` - Handle of the capture task.
+/// * `session` - Watches the session to start capturing when it is streaming.
+/// * `backend` - Creates the capture elements of the platform.
+/// * `game_id` - The handle of the game window.
+/// * `video_config` - The framerate and bitrate of the video.
+/// * `keyframe_rx` - Requests for the encoder to produce a keyframe.
+#[allow(clippy::too_many_arguments)]
 pub async fn start_capture(
     tx_video: Sender<Vec<u8>>,
     tx_audio: Sender<Vec<u8>>,
-    shutdown: &mut shutdown::Shutdown,
-    barrier: Arc<Barrier>,
+    task: Task,
+    mut session: SessionWatcher,
+    backend: Box<dyn CaptureBackend>,
     game_id: u64,
+    video_config: VideoConfig,
+    mut keyframe_rx: UnboundedReceiver<()>,
 ) {
-    shutdown.add_task("Capture").await;
-
     tokio::select! {
-        _ = shutdown.wait_for_error() => {
+        _ = task.cancelled() => {
             log::error!("CAPTURE | ERROR NOTIFIED");
             return;
         },
-        _ = barrier.wait() => {
-            println!("CAPTURE | Barrier passed");
+        streaming = session.wait_streaming() => {
+            if !streaming {
+                return;
+            }
+            println!("CAPTURE | Session streaming");
         }
     }
+    log::info!("CAPTURE | Using {} capture backend", backend.name());
 
-    let new_framerate = gstreamer::Fraction::new(GSTREAMER_FRAMES, 1);
-    let video_caps = gstreamer::Caps::builder("video/x-raw")
-        .field("framerate", new_framerate)
-        .build();
+    let video_caps = backend.video_caps(video_config.framerate);
 
-    let video_elements = match video_capture::create_elements(game_id) {
+    let video_elements = match backend.video_elements(game_id, video_config.bitrate) {
         Ok(e) => e,
         Err(e) => {
             log::error!(
                 "CAPTURE | Failed to create video elements: {}",
                 e.to_string()
             );
-            shutdown
-                .notify_error(false, "create elements video capture")
-                .await;
+            task.fail("Create elements video capture");
             return;
         }
     };
@@ -181,13 +191,11 @@ pub async fn start_capture(
         .field("channels", 2)
         .build();
 
-    let audio_elements = match audio_capture::create_elements() {
+    let audio_elements = match backend.audio_elements() {
         Ok(e) => e,
         Err(e) => {
             log::error!("CAPTURE | Error creating  audio elements: {}", e.message);
-            shutdown
-                .notify_error(false, "Create elements audio capture")
-                .await;
+            task.fail("Create elements audio capture");
             return;
         }
     };
@@ -199,13 +207,11 @@ pub async fn start_capture(
         tx_audio,
         video_caps,
         audio_caps,
-        shutdown.clone(),
+        task.clone(),
     ) {
         Ok(p) => p,
         Err(e) => {
-            shutdown
-                .notify_error(false, "crate pipeline video capture")
-                .await;
+            task.fail("Create pipeline video capture");
             log::error!("CAPTURE | Failed to create pipeline: {}", e.to_string());
             return;
         }
@@ -213,9 +219,7 @@ pub async fn start_capture(
 
     // Start playing Payload
     if let Err(e) = pipeline.set_state(gstreamer::State::Playing) {
-        shutdown
-            .notify_error(false, "failed set to playing audio video capture")
-            .await;
+        task.fail("Failed set to playing audio video capture");
         log::error!(
             "CAPTURE | Failed to set the pipeline to the `Playing` state: {}",
             e.to_string()
@@ -224,13 +228,20 @@ pub async fn start_capture(
     }
 
     let pipeline_cpy = pipeline.clone();
-    let mut shutdown_cpy = shutdown.clone();
-
-    let handle_read_bus = tokio::task::spawn(async move {
-        read_bus(pipeline_cpy, &mut shutdown_cpy).await;
-    });
+    task.supervisor()
+        .spawn("Capture read bus", move |task| read_bus(pipeline_cpy, task));
 
-    let _ = shutdown.wait_for_error().await;
+    let video_sink = pipeline.by_name(VIDEO_SINK_NAME);
+    loop {
+        tokio::select! {
+            _ = task.cancelled() => break,
+            Some(()) = keyframe_rx.recv() => {
+                if let Some(video_sink) = &video_sink {
+                    request_keyframe(video_sink);
+                }
+            }
+        }
+    }
     log::error!("PLAYER | start_capture | Shutdown received");
 
     if let Err(e) = pipeline.set_state(gstreamer::State::Null) {
@@ -239,8 +250,6 @@ pub async fn start_capture(
         println!("SE CAMBIA EL ESTADO A NULL");
     }
 
-    let _ = handle_read_bus.await;
-
     // tokio::select! {
     //     _ = shutdown.wait_for_error() => {
     //         log::error!("CAPTURE | ERROR NOTIFIED");
@@ -259,3 +268,20 @@ pub async fn start_capture(
     //     );
     // }
 }
+
+/// Asks the video encoder for a keyframe, so the receiver can decode the stream again after
+/// frames were dropped.
+///
+/// # Arguments
+///
+/// * `video_sink` - The sink of the video branch, the request travels upstream to the encoder.
+fn request_keyframe(video_sink: &Element) {
+    let event = gstreamer_video::UpstreamForceKeyUnitEvent::builder()
+        .all_headers(true)
+        .build();
+    if video_sink.send_event(event) {
+        log::info!("CAPTURE | Keyframe requested");
+    } else {
+        log::warn!("CAPTURE | Keyframe request not handled by the encoder");
+    }
+}
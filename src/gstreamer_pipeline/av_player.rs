@@ -1,20 +1,15 @@
-use std::{
-    collections::HashMap,
-    io::Error,
-    sync::{mpsc::Receiver, Arc},
-};
+use std::{collections::HashMap, sync::mpsc::Receiver};
 
-use gstreamer::{glib, prelude::*, Caps, Element};
-use tokio::sync::Barrier;
-use winapi::um::winuser::ShowCursor;
+use gstreamer::{prelude::*, Caps, Element};
 
 use crate::{
-    sound::audio_player,
+    gstreamer_pipeline::player_backend::PlayerBackend,
+    services::session_state::SessionWatcher,
     utils::{
         gstreamer_utils::{push_sample, read_bus},
-        shutdown,
+        node_error::NodeError,
+        supervisor::Task,
     },
-    video::video_player,
 };
 
 pub const PIPELINE_NAME: &str = "AUDIO VIDEO PLAYER";
@@ -25,24 +20,29 @@ pub const PIPELINE_NAME: &str = "AUDIO VIDEO PLAYER";
 ///
 /// * `rx_video` - A Receiver for receiving video frames.
 /// * `rx_audio` - A Receiver for receiving audio frames.
-/// * `shutdown` - A shutdown handle for managing the finalization of the thread.
+/// * `task` - Handle of the supervised player task.
+/// * `session` - Used to wait until the session is streaming.
+/// * `backend` - Creates the player elements of the platform.
 pub async fn start_player(
     rx_video: Receiver<(bool, Vec<u8>)>,
     rx_audio: Receiver<(bool, Vec<u8>)>,
-    shutdown: &mut shutdown::Shutdown,
-    barrier: Arc<Barrier>,
+    task: Task,
+    mut session: SessionWatcher,
+    backend: Box<dyn PlayerBackend>,
 ) {
-    shutdown.add_task("Start player").await;
-
     tokio::select! {
-        _ = shutdown.wait_for_error() => {
+        _ = task.cancelled() => {
             log::error!("CAPTURE | ERROR NOTIFIED");
             return;
         },
-        _ = barrier.wait() => {
-            println!("START PLAYER | Barrier passed");
+        streaming = session.wait_streaming() => {
+            if !streaming {
+                return;
+            }
+            println!("START PLAYER | Session streaming");
         }
     }
+    log::info!("PLAYER | Using {} player backend", backend.name());
 
     // Create the caps
     let video_caps = gstreamer::Caps::builder("application/x-rtp")
@@ -51,14 +51,15 @@ pub async fn start_player(
         .field("encoding-name", "H264")
         .build();
 
-    let video_elements = match video_player::create_elements() {
+    let video_elements = match backend.video_elements() {
         Ok(e) => e,
         Err(e) => {
-            shutdown.notify_error(false, "").await;
+            task.fail("Create elements video player");
             log::error!("PLAYER | Failed to create video elements: {}", e);
             return;
         }
     };
+    backend.hide_cursor(&video_elements["sink"]);
 
     // Create the caps
     let audio_caps = gstreamer::Caps::builder("application/x-rtp")
@@ -68,13 +69,11 @@ pub async fn start_player(
         .field("encoding-name", "OPUS")
         .build();
 
-    let audio_elements = match audio_player::create_elements() {
+    let audio_elements = match backend.audio_elements() {
         Ok(e) => e,
         Err(e) => {
             log::error!("AUDIO PLAYER | Error creating elements: {}", e.message);
-            shutdown
-                .notify_error(false, "Error creating elements audio player")
-                .await;
+            task.fail("Create elements audio player");
             return;
         }
     };
@@ -86,11 +85,11 @@ pub async fn start_player(
         audio_caps,
         rx_video,
         rx_audio,
-        shutdown.clone(),
+        task.clone(),
     ) {
         Ok(p) => p,
         Err(e) => {
-            shutdown.notify_error(false, "").await;
+            task.fail("Create pipeline video player");
             log::error!("PLAYER | Failed to create pipeline: {}", e);
             return;
         }
@@ -98,9 +97,7 @@ pub async fn start_player(
 
     // Start playing Payload
     if let Err(e) = pipeline.set_state(gstreamer::State::Playing) {
-        shutdown
-            .notify_error(false, "failed set to playing audio video player")
-            .await;
+        task.fail("Failed set to playing audio video player");
         log::error!(
             "PLAYER | Failed to set the pipeline to the `Playing` state: {}",
             e.to_string()
@@ -109,25 +106,17 @@ pub async fn start_player(
     }
 
     let pipeline_cpy = pipeline.clone();
-    let mut shutdown_cpy = shutdown.clone();
-
-    let handle_read_bus = tokio::task::spawn(async move {
-        read_bus(pipeline_cpy, &mut shutdown_cpy).await;
-    });
+    task.supervisor()
+        .spawn("Player read bus", move |task| read_bus(pipeline_cpy, task));
 
-    tokio::select! {
-        _ = shutdown.wait_for_error() => {
-            log::info!("PLAYER | Shutdown received");
-        },
-    }
+    task.cancelled().await;
+    log::info!("PLAYER | Shutdown received");
 
     if let Err(e) = pipeline.set_state(gstreamer::State::Null) {
         log::error!("PLAYER | Failed to set pipeline to null: {}", e);
     } else {
         println!("SE CAMBIA EL ESTADO A NULL");
     }
-
-    let _ = handle_read_bus.await;
 }
 
 /// Creates the pipeline for the audio and video player.
@@ -140,6 +129,7 @@ pub async fn start_player(
 /// * `audio_caps` - The Audio Caps for the pipeline.
 /// * `rx_video` - A Receiver for receiving video frames.
 /// * `rx_audio` - A Receiver for receiving audio frames.
+/// * `task` - Handle of the player task, used to spawn the tasks that push the samples.
 ///
 /// # Returns
 ///
@@ -151,8 +141,8 @@ fn create_pipeline(
     audio_caps: Caps,
     rx_video: Receiver<(bool, Vec<u8>)>,
     rx_audio: Receiver<(bool, Vec<u8>)>,
-    shutdown: shutdown::Shutdown,
-) -> Result<gstreamer::Pipeline, Error> {
+    task: Task,
+) -> Result<gstreamer::Pipeline, NodeError> {
     let video_source = gstreamer_app::AppSrc::builder()
         .caps(&video_caps)
         .block(true)
@@ -189,7 +179,10 @@ fn create_pipeline(
         &audio_elements["sample"],
         &audio_elements["sink"],
     ]) {
-        return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer(
+            "Error adding elements to the player pipeline",
+            e,
+        ));
     }
 
     if let Err(e) = gstreamer::Element::link_many([
@@ -201,7 +194,7 @@ fn create_pipeline(
         &video_elements["taginject"],
         &video_elements["sink"],
     ]) {
-        return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer("Error linking video elements", e));
     }
 
     if let Err(e) = gstreamer::Element::link_many([
@@ -214,53 +207,34 @@ fn create_pipeline(
         &audio_elements["sample"],
         &audio_elements["sink"],
     ]) {
-        return Err(Error::new(std::io::ErrorKind::Other, e.to_string()));
+        return Err(NodeError::gstreamer("Error linking audio elements", e));
     };
 
-    let mut shutdown_clone = shutdown.clone();
-    tokio::spawn(async move {
-        shutdown_clone.add_task("Video push sample").await;
-        loop {
-            if let Err(_e) = push_sample(&video_source, &rx_video).map_err(|err| {
-                log::error!("VIDEO PLAYER | {}", err);
-            }) {
-                shutdown_clone
-                    .notify_error(false, "failed pushing video sample")
-                    .await;
-                log::error!("RECEIVER | Failed pushing video sample");
-                break;
-            }
-        }
-    });
-
-    let mut shutdown_cpy = shutdown.clone();
-    tokio::spawn(async move {
-        shutdown_cpy.add_task("Audio push sample").await;
-        loop {
-            if let Err(_e) = push_sample(&audio_source, &rx_audio).map_err(|err| {
-                log::error!("AUDIO PLAYER | {}", err);
-            }) {
-                shutdown_cpy
-                    .notify_error(false, "failed pushing audio sample")
-                    .await;
-                log::error!("RECEIVER | Failed pushing audio sample");
-                break;
+    task.supervisor()
+        .spawn("Video push sample", move |task| async move {
+            loop {
+                if let Err(_e) = push_sample(&video_source, &rx_video).map_err(|err| {
+                    log::error!("VIDEO PLAYER | {}", err);
+                }) {
+                    task.fail("Failed pushing video sample");
+                    log::error!("RECEIVER | Failed pushing video sample");
+                    break;
+                }
             }
-        }
-    });
-
-    let videosink = &video_elements["sink"];
-    videosink.connect_closure(
-        "present",
-        false,
-        glib::closure!(move |_sink: &gstreamer::Element,
-                             _device: &gstreamer::Object,
-                             _rtv_raw: glib::Pointer| {
-            unsafe {
-                ShowCursor(0);
+        });
+
+    task.supervisor()
+        .spawn("Audio push sample", move |task| async move {
+            loop {
+                if let Err(_e) = push_sample(&audio_source, &rx_audio).map_err(|err| {
+                    log::error!("AUDIO PLAYER | {}", err);
+                }) {
+                    task.fail("Failed pushing audio sample");
+                    log::error!("RECEIVER | Failed pushing audio sample");
+                    break;
+                }
             }
-        }),
-    );
+        });
 
     Ok(pipeline)
 }
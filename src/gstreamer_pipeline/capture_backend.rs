@@ -0,0 +1,329 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use gstreamer::{glib, Caps, Element, Fraction};
+use serde::de::value;
+use serde::Deserialize;
+
+use crate::config::node_config::CaptureConfig;
+use crate::sound::audio_capture;
+use crate::utils::name_parse::from_name;
+use crate::video::video_capture;
+
+/// Platform whose capture elements are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BackendKind {
+    /// The backend of the platform the node was built for.
+    Auto,
+    Windows,
+    Linux,
+    /// Streams a test pattern and tone instead of a game, which is not launched.
+    Test,
+}
+
+/// Video source of the Linux backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoSource {
+    /// `ximagesrc`, for X11 sessions.
+    X11,
+    /// `pipewiresrc`, for Wayland sessions.
+    Pipewire,
+    /// `videotestsrc`, to test the pipeline without a screen.
+    Test,
+}
+
+/// Audio source of the Linux backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum AudioSource {
+    /// `pulsesrc` recording the monitor of the default sink.
+    Pulse,
+    /// `pipewiresrc` recording the default sink.
+    Pipewire,
+    /// `audiotestsrc`, to test the pipeline without a sound card.
+    Test,
+}
+
+impl FromStr for BackendKind {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+impl FromStr for VideoSource {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+impl FromStr for AudioSource {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+/// Builds the platform specific elements of the capture pipeline.
+///
+/// Video elements are linked as `src ! queue ! convert ! enc ! pay` and audio elements as
+/// `src ! queue ! convert ! sample ! enc ! pay`, so every backend must return those keys.
+pub trait CaptureBackend: Send {
+    /// Name of the backend, used in the logs.
+    fn name(&self) -> &'static str;
+
+    /// Creates the video capture elements.
+    ///
+    /// # Arguments
+    ///
+    /// * `window_handle` - The handle of the window to capture, 0 if it is not known.
+    /// * `bitrate` - The bitrate of the encoder in kbit/sec.
+    fn video_elements(
+        &self,
+        window_handle: u64,
+        bitrate: u32,
+    ) -> Result<HashMap<&'static str, Element>, glib::BoolError>;
+
+    /// Creates the audio capture elements.
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError>;
+
+    /// Returns the caps the video source must produce.
+    ///
+    /// # Arguments
+    ///
+    /// * `framerate` - The frames per second captured.
+    fn video_caps(&self, framerate: i32) -> Caps {
+        Caps::builder("video/x-raw")
+            .field("framerate", Fraction::new(framerate, 1))
+            .build()
+    }
+}
+
+/// Captures the game window with Direct3D 11 and the system audio with WASAPI, encoding
+/// the video with AMF or Media Foundation.
+pub struct WindowsCapture;
+
+impl CaptureBackend for WindowsCapture {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn video_elements(
+        &self,
+        window_handle: u64,
+        bitrate: u32,
+    ) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        video_capture::create_elements(window_handle, bitrate)
+    }
+
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        audio_capture::create_elements(audio_capture::wasapi_source()?)
+    }
+}
+
+/// Captures the screen with X11 or PipeWire and the system audio with PulseAudio or
+/// PipeWire, encoding the video with x264.
+pub struct LinuxCapture {
+    video_source: VideoSource,
+    audio_source: AudioSource,
+    test_pattern: String,
+    test_width: i32,
+    test_height: i32,
+}
+
+impl LinuxCapture {
+    /// Creates the backend with the sources in the configuration.
+    pub fn new(config: &CaptureConfig) -> Self {
+        Self {
+            video_source: config.video_source,
+            audio_source: config.audio_source,
+            test_pattern: config.test_pattern.clone(),
+            test_width: config.test_width,
+            test_height: config.test_height,
+        }
+    }
+
+    /// Creates the backend with the test sources, whatever the ones in the configuration.
+    pub fn test(config: &CaptureConfig) -> Self {
+        Self {
+            video_source: VideoSource::Test,
+            audio_source: AudioSource::Test,
+            ..Self::new(config)
+        }
+    }
+}
+
+impl CaptureBackend for LinuxCapture {
+    fn name(&self) -> &'static str {
+        "linux"
+    }
+
+    fn video_elements(
+        &self,
+        window_handle: u64,
+        bitrate: u32,
+    ) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        let source = match self.video_source {
+            VideoSource::X11 => video_capture::x11_source(window_handle)?,
+            VideoSource::Pipewire => video_capture::pipewire_source()?,
+            VideoSource::Test => video_capture::test_source(&self.test_pattern)?,
+        };
+        video_capture::create_software_elements(source, bitrate)
+    }
+
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        let source = match self.audio_source {
+            AudioSource::Pulse => audio_capture::pulse_source()?,
+            AudioSource::Pipewire => audio_capture::pipewire_source()?,
+            AudioSource::Test => audio_capture::test_source()?,
+        };
+        audio_capture::create_elements(source)
+    }
+
+    fn video_caps(&self, framerate: i32) -> Caps {
+        let caps = Caps::builder("video/x-raw").field("framerate", Fraction::new(framerate, 1));
+        match self.video_source {
+            VideoSource::Test => caps
+                .field("width", self.test_width)
+                .field("height", self.test_height)
+                .build(),
+            _ => caps.build(),
+        }
+    }
+}
+
+/// Creates the capture backend selected in the configuration.
+///
+/// # Arguments
+///
+/// * `config` - The capture configuration.
+///
+/// # Returns
+/// The backend of the configured platform, or of the current one if it is `auto`.
+pub fn create_backend(config: &CaptureConfig) -> Box<dyn CaptureBackend> {
+    let kind = match config.backend {
+        BackendKind::Auto if cfg!(windows) => BackendKind::Windows,
+        BackendKind::Auto => BackendKind::Linux,
+        kind => kind,
+    };
+
+    match kind {
+        BackendKind::Windows => Box::new(WindowsCapture),
+        BackendKind::Test => Box::new(LinuxCapture::test(config)),
+        BackendKind::Linux | BackendKind::Auto => Box::new(LinuxCapture::new(config)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gstreamer::prelude::*;
+    use gstreamer::{ClockTime, ElementFactory, Pipeline, State};
+
+    const VIDEO_KEYS: [&str; 5] = ["src", "queue", "convert", "enc", "pay"];
+    const AUDIO_KEYS: [&str; 6] = ["src", "queue", "convert", "sample", "enc", "pay"];
+
+    fn assert_keys(elements: &HashMap<&'static str, Element>, keys: &[&str]) {
+        for key in keys {
+            assert!(elements.contains_key(key), "missing element {key}");
+        }
+    }
+
+    fn test_config() -> CaptureConfig {
+        CaptureConfig {
+            backend: BackendKind::Test,
+            ..CaptureConfig::default()
+        }
+    }
+
+    #[test]
+    fn auto_picks_the_platform_backend() {
+        let backend = create_backend(&CaptureConfig::default());
+        let expected = if cfg!(windows) { "windows" } else { "linux" };
+        assert_eq!(backend.name(), expected);
+    }
+
+    #[test]
+    fn configured_backend_is_used() {
+        for (kind, name) in [
+            (BackendKind::Windows, "windows"),
+            (BackendKind::Linux, "linux"),
+            (BackendKind::Test, "linux"),
+        ] {
+            let config = CaptureConfig {
+                backend: kind,
+                ..CaptureConfig::default()
+            };
+            assert_eq!(create_backend(&config).name(), name);
+        }
+    }
+
+    #[test]
+    fn test_backend_returns_the_linked_keys() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = create_backend(&test_config());
+
+        assert_keys(&backend.video_elements(0, 1000).unwrap(), &VIDEO_KEYS);
+        assert_keys(&backend.audio_elements().unwrap(), &AUDIO_KEYS);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_backend_returns_the_linked_keys() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = WindowsCapture;
+
+        assert_keys(&backend.video_elements(0, 1000).unwrap(), &VIDEO_KEYS);
+        assert_keys(&backend.audio_elements().unwrap(), &AUDIO_KEYS);
+    }
+
+    #[test]
+    fn test_backend_pipeline_plays() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = create_backend(&test_config());
+        let video = backend.video_elements(0, 1000).unwrap();
+        let audio = backend.audio_elements().unwrap();
+        let video_sink = ElementFactory::make("fakesink").build().unwrap();
+        let audio_sink = ElementFactory::make("fakesink").build().unwrap();
+
+        let pipeline = Pipeline::new();
+        pipeline
+            .add_many(video.values().chain(audio.values()))
+            .unwrap();
+        pipeline.add_many([&video_sink, &audio_sink]).unwrap();
+
+        video["src"]
+            .link_filtered(&video["queue"], &backend.video_caps(30))
+            .unwrap();
+        Element::link_many([
+            &video["queue"],
+            &video["convert"],
+            &video["enc"],
+            &video["pay"],
+            &video_sink,
+        ])
+        .unwrap();
+        Element::link_many([
+            &audio["src"],
+            &audio["queue"],
+            &audio["convert"],
+            &audio["sample"],
+            &audio["enc"],
+            &audio["pay"],
+            &audio_sink,
+        ])
+        .unwrap();
+
+        pipeline.set_state(State::Playing).unwrap();
+        let (result, current, _) = pipeline.state(ClockTime::from_seconds(10));
+        pipeline.set_state(State::Null).unwrap();
+        assert!(result.is_ok());
+        assert_eq!(current, State::Playing);
+    }
+}
@@ -1,2 +1,4 @@
 pub mod av_capture;
 pub mod av_player;
+pub mod capture_backend;
+pub mod player_backend;
@@ -0,0 +1,312 @@
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use gstreamer::{glib, Element};
+use serde::de::value;
+use serde::Deserialize;
+
+use crate::config::node_config::PlayerConfig;
+use crate::sound::audio_player;
+use crate::utils::name_parse::from_name;
+use crate::video::video_player;
+
+/// Platform whose player elements are used.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PlayerKind {
+    /// The backend of the platform the node was built for.
+    Auto,
+    Windows,
+    Linux,
+    /// Decodes the stream and drops it, to run the receiver without a display.
+    Headless,
+}
+
+/// Video sink of the Linux backend.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum VideoSink {
+    /// `autovideosink`.
+    Auto,
+    /// `glimagesink`.
+    Gl,
+}
+
+impl FromStr for PlayerKind {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+impl FromStr for VideoSink {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+/// Builds the platform specific elements of the player pipeline.
+///
+/// Video elements are linked as `depay ! parse ! dec ! queue ! taginject ! sink` and audio
+/// elements as `queue ! depay ! parse ! dec ! convert ! sample ! sink`, so every backend
+/// must return those keys.
+pub trait PlayerBackend: Send {
+    /// Name of the backend, used in the logs.
+    fn name(&self) -> &'static str;
+
+    /// Creates the video player elements.
+    fn video_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError>;
+
+    /// Creates the audio player elements.
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError>;
+
+    /// Hides the local cursor over the video, the one drawn in the video is the game's.
+    ///
+    /// # Arguments
+    ///
+    /// * `video_sink` - The `sink` returned by [`PlayerBackend::video_elements`].
+    fn hide_cursor(&self, _video_sink: &Element) {}
+}
+
+/// Decodes with Direct3D 11 and shows the video full screen.
+pub struct WindowsPlayer;
+
+impl PlayerBackend for WindowsPlayer {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn video_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        video_player::create_elements()
+    }
+
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        audio_player::create_elements(audio_player::auto_sink()?)
+    }
+
+    #[cfg(windows)]
+    fn hide_cursor(&self, video_sink: &Element) {
+        use gstreamer::prelude::*;
+        use winapi::um::winuser::ShowCursor;
+
+        // The cursor is hidden again every time a frame is presented
+        video_sink.connect_closure(
+            "present",
+            false,
+            glib::closure!(move |_sink: &gstreamer::Element,
+                                 _device: &gstreamer::Object,
+                                 _rtv_raw: glib::Pointer| {
+                unsafe {
+                    ShowCursor(0);
+                }
+            }),
+        );
+    }
+}
+
+/// Decodes in software and shows the video with `autovideosink` or `glimagesink`.
+pub struct LinuxPlayer {
+    video_sink: VideoSink,
+}
+
+impl LinuxPlayer {
+    pub fn new(video_sink: VideoSink) -> Self {
+        Self { video_sink }
+    }
+}
+
+impl PlayerBackend for LinuxPlayer {
+    fn name(&self) -> &'static str {
+        "linux"
+    }
+
+    fn video_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        let sink = match self.video_sink {
+            VideoSink::Auto => video_player::auto_sink()?,
+            VideoSink::Gl => video_player::gl_sink()?,
+        };
+        video_player::create_software_elements(sink)
+    }
+
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        audio_player::create_elements(audio_player::auto_sink()?)
+    }
+}
+
+/// Decodes in software and drops the audio and video with `fakesink`.
+pub struct HeadlessPlayer;
+
+impl PlayerBackend for HeadlessPlayer {
+    fn name(&self) -> &'static str {
+        "headless"
+    }
+
+    fn video_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        video_player::create_software_elements(video_player::fake_sink()?)
+    }
+
+    fn audio_elements(&self) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+        audio_player::create_elements(audio_player::fake_sink()?)
+    }
+}
+
+/// Creates the player backend selected in the configuration.
+///
+/// # Arguments
+///
+/// * `config` - The player configuration.
+///
+/// # Returns
+/// The backend configured, or the one of the current platform if it is `auto`.
+pub fn create_backend(config: &PlayerConfig) -> Box<dyn PlayerBackend> {
+    let kind = match config.backend {
+        PlayerKind::Auto if cfg!(windows) => PlayerKind::Windows,
+        PlayerKind::Auto => PlayerKind::Linux,
+        kind => kind,
+    };
+
+    match kind {
+        PlayerKind::Windows => Box::new(WindowsPlayer),
+        PlayerKind::Headless => Box::new(HeadlessPlayer),
+        PlayerKind::Linux | PlayerKind::Auto => Box::new(LinuxPlayer::new(config.video_sink)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use gstreamer::prelude::*;
+    use gstreamer::{ClockTime, ElementFactory, Pipeline, State};
+
+    const VIDEO_KEYS: [&str; 6] = ["depay", "parse", "dec", "queue", "taginject", "sink"];
+    const AUDIO_KEYS: [&str; 7] = [
+        "queue", "depay", "parse", "dec", "convert", "sample", "sink",
+    ];
+
+    fn assert_keys(elements: &HashMap<&'static str, Element>, keys: &[&str]) {
+        for key in keys {
+            assert!(elements.contains_key(key), "missing element {key}");
+        }
+    }
+
+    fn make(factory: &str) -> Element {
+        ElementFactory::make(factory)
+            .build()
+            .unwrap_or_else(|_| panic!("missing element {factory}"))
+    }
+
+    #[test]
+    fn auto_picks_the_platform_backend() {
+        let backend = create_backend(&PlayerConfig::default());
+        let expected = if cfg!(windows) { "windows" } else { "linux" };
+        assert_eq!(backend.name(), expected);
+    }
+
+    #[test]
+    fn configured_backend_is_used() {
+        for (kind, name) in [
+            (PlayerKind::Windows, "windows"),
+            (PlayerKind::Linux, "linux"),
+            (PlayerKind::Headless, "headless"),
+        ] {
+            let config = PlayerConfig {
+                backend: kind,
+                ..PlayerConfig::default()
+            };
+            assert_eq!(create_backend(&config).name(), name);
+        }
+    }
+
+    #[test]
+    fn headless_backend_returns_the_linked_keys() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = HeadlessPlayer;
+
+        assert_keys(&backend.video_elements().unwrap(), &VIDEO_KEYS);
+        assert_keys(&backend.audio_elements().unwrap(), &AUDIO_KEYS);
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn linux_backend_returns_the_linked_keys() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = LinuxPlayer::new(VideoSink::Auto);
+
+        assert_keys(&backend.video_elements().unwrap(), &VIDEO_KEYS);
+        assert_keys(&backend.audio_elements().unwrap(), &AUDIO_KEYS);
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn windows_backend_returns_the_linked_keys() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = WindowsPlayer;
+
+        assert_keys(&backend.video_elements().unwrap(), &VIDEO_KEYS);
+        assert_keys(&backend.audio_elements().unwrap(), &AUDIO_KEYS);
+    }
+
+    #[test]
+    fn headless_backend_plays_a_test_stream() {
+        gstreamer::init().expect("GStreamer not available");
+        let backend = HeadlessPlayer;
+        let video = backend.video_elements().unwrap();
+        let audio = backend.audio_elements().unwrap();
+
+        // RTP streams like the ones sent by the capture pipeline
+        let video_feed = [
+            make("videotestsrc"),
+            make("videoconvert"),
+            make("x264enc"),
+            make("rtph264pay"),
+        ];
+        video_feed[2].set_property_from_str("tune", "zerolatency");
+        let audio_feed = [
+            make("audiotestsrc"),
+            make("audioconvert"),
+            make("opusenc"),
+            make("rtpopuspay"),
+        ];
+
+        let pipeline = Pipeline::new();
+        pipeline
+            .add_many(video.values().chain(audio.values()))
+            .unwrap();
+        pipeline
+            .add_many(video_feed.iter().chain(audio_feed.iter()))
+            .unwrap();
+
+        Element::link_many(&video_feed).unwrap();
+        Element::link_many([
+            &video_feed[3],
+            &video["depay"],
+            &video["parse"],
+            &video["dec"],
+            &video["queue"],
+            &video["taginject"],
+            &video["sink"],
+        ])
+        .unwrap();
+        Element::link_many(&audio_feed).unwrap();
+        Element::link_many([
+            &audio_feed[3],
+            &audio["queue"],
+            &audio["depay"],
+            &audio["parse"],
+            &audio["dec"],
+            &audio["convert"],
+            &audio["sample"],
+            &audio["sink"],
+        ])
+        .unwrap();
+
+        pipeline.set_state(State::Playing).unwrap();
+        let (result, current, _) = pipeline.state(ClockTime::from_seconds(10));
+        pipeline.set_state(State::Null).unwrap();
+        assert!(result.is_ok());
+        assert_eq!(current, State::Playing);
+    }
+}
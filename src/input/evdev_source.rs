@@ -0,0 +1,175 @@
+use std::io::{Error, ErrorKind};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::thread;
+use std::time::Duration;
+
+use evdev::{Device, InputEventKind, Key, RelativeAxisType, Synchronization};
+
+use super::input_event::InputEvent;
+use super::input_source::InputSource;
+use super::linux_keys::{mouse_button, virtual_key};
+use crate::output::output_const::UINPUT_NAME_PREFIX;
+
+/// Keys that are not sent to the offerer, so they keep working on the client.
+const BLOCKED_KEYS: [Key; 2] = [Key::KEY_LEFTMETA, Key::KEY_RIGHTMETA];
+
+/// Value of a key event repeated while the key is held.
+const KEY_REPEAT: i32 = 2;
+
+/// Captures the keyboards and mice of the client through evdev.
+///
+/// Every device is read from its own thread. Requires read access to `/dev/input`, usually
+/// by being in the `input` group.
+pub struct EvdevSource {
+    rx_events: Receiver<InputEvent>,
+}
+
+impl EvdevSource {
+    /// Opens the keyboards and mice of the client.
+    ///
+    /// # Returns
+    /// The source, or an error if no keyboard or mouse can be opened.
+    pub fn new() -> Result<EvdevSource, Error> {
+        let (tx_events, rx_events) = mpsc::channel();
+        let mut devices = 0;
+
+        for (path, device) in evdev::enumerate() {
+            // The virtual devices of the node would send the input back to the offerer
+            let virtual_device = device
+                .name()
+                .is_some_and(|name| name.starts_with(UINPUT_NAME_PREFIX));
+            if virtual_device || !is_input_device(&device) {
+                continue;
+            }
+            log::info!(
+                "EVDEV | Reading {} ({})",
+                device.name().unwrap_or("unknown"),
+                path.display()
+            );
+            let tx_events = tx_events.clone();
+            thread::Builder::new()
+                .name("evdev-reader".to_string())
+                .spawn(move || read_device(device, tx_events))?;
+            devices += 1;
+        }
+
+        if devices == 0 {
+            return Err(Error::new(
+                ErrorKind::NotFound,
+                "No keyboard or mouse could be opened, check the access to /dev/input",
+            ));
+        }
+        Ok(EvdevSource { rx_events })
+    }
+}
+
+impl InputSource for EvdevSource {
+    fn name(&self) -> &'static str {
+        "linux"
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<InputEvent>, Error> {
+        match self.rx_events.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::new(
+                ErrorKind::NotConnected,
+                "Every input device was closed",
+            )),
+        }
+    }
+}
+
+/// Whether the device is a keyboard or a mouse.
+fn is_input_device(device: &Device) -> bool {
+    let keyboard = device
+        .supported_keys()
+        .is_some_and(|keys| keys.contains(Key::KEY_A));
+    let mouse = device
+        .supported_relative_axes()
+        .is_some_and(|axes| axes.contains(RelativeAxisType::REL_X));
+    keyboard || mouse
+}
+
+/// Reads the events of a device until it is closed or the source is dropped.
+///
+/// # Arguments
+///
+/// * `device` - The keyboard or mouse.
+/// * `tx_events` - Sender of the events read.
+fn read_device(mut device: Device, tx_events: Sender<InputEvent>) {
+    // Movements are sent once per report, as a single event
+    let mut motion = (0, 0);
+
+    loop {
+        let events = match device.fetch_events() {
+            Ok(events) => events,
+            Err(e) => {
+                log::warn!("EVDEV | Device closed | {}", e);
+                return;
+            }
+        };
+
+        for event in events {
+            let event = match event.kind() {
+                InputEventKind::Key(key) => key_event(key, event.value()),
+                InputEventKind::RelAxis(RelativeAxisType::REL_X) => {
+                    motion.0 += event.value();
+                    None
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_Y) => {
+                    motion.1 += event.value();
+                    None
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_WHEEL) => {
+                    Some(InputEvent::ScrollVertical(event.value() as f32))
+                }
+                InputEventKind::RelAxis(RelativeAxisType::REL_HWHEEL) => {
+                    Some(InputEvent::ScrollHorizontal(event.value() as f32))
+                }
+                InputEventKind::Synchronization(Synchronization::SYN_REPORT)
+                    if motion != (0, 0) =>
+                {
+                    let (dx, dy) = std::mem::take(&mut motion);
+                    Some(InputEvent::MouseMove { dx, dy })
+                }
+                _ => None,
+            };
+
+            if let Some(event) = event {
+                if tx_events.send(event).is_err() {
+                    // The source was dropped
+                    return;
+                }
+            }
+        }
+    }
+}
+
+/// Maps a key or button event to the input event sent to the offerer.
+///
+/// # Arguments
+///
+/// * `key` - The key or button.
+/// * `value` - 0 when released, 1 when pressed and 2 when repeated.
+fn key_event(key: Key, value: i32) -> Option<InputEvent> {
+    if BLOCKED_KEYS.contains(&key) {
+        return None;
+    }
+    let pressed = value == 1 || value == KEY_REPEAT;
+
+    if let Some(button) = mouse_button(key) {
+        // Buttons are not repeated
+        return match value {
+            0 => Some(InputEvent::MouseRelease(button)),
+            1 => Some(InputEvent::MousePress(button)),
+            _ => None,
+        };
+    }
+    let vk = virtual_key(key)?;
+    if pressed {
+        Some(InputEvent::KeyPress(vk))
+    } else {
+        Some(InputEvent::KeyRelease(vk))
+    }
+}
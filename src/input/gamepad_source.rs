@@ -0,0 +1,168 @@
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::sync::Arc;
+use std::thread;
+use std::time::Duration;
+
+use gilrs::{Axis, Button, EventType, Gamepad, GamepadId, Gilrs};
+
+use super::gamepad_state::GamepadState;
+use super::input_const::*;
+use super::input_event::InputEvent;
+use super::input_source::InputSource;
+
+/// Buttons of gilrs and their XInput equivalent.
+const BUTTONS: [(Button, u16); 15] = [
+    (Button::DPadUp, GAMEPAD_DPAD_UP),
+    (Button::DPadDown, GAMEPAD_DPAD_DOWN),
+    (Button::DPadLeft, GAMEPAD_DPAD_LEFT),
+    (Button::DPadRight, GAMEPAD_DPAD_RIGHT),
+    (Button::Start, GAMEPAD_START),
+    (Button::Select, GAMEPAD_BACK),
+    (Button::LeftThumb, GAMEPAD_LEFT_THUMB),
+    (Button::RightThumb, GAMEPAD_RIGHT_THUMB),
+    (Button::LeftTrigger, GAMEPAD_LEFT_SHOULDER),
+    (Button::RightTrigger, GAMEPAD_RIGHT_SHOULDER),
+    (Button::Mode, GAMEPAD_GUIDE),
+    (Button::South, GAMEPAD_A),
+    (Button::East, GAMEPAD_B),
+    (Button::West, GAMEPAD_X),
+    (Button::North, GAMEPAD_Y),
+];
+
+/// Captures the controllers of the client with gilrs.
+///
+/// Controllers are read from their own thread, which sends an update with the fields that
+/// changed every time one of them does.
+pub struct GamepadSource {
+    rx_events: Receiver<InputEvent>,
+    /// Tells the reading thread to stop.
+    stopped: Arc<AtomicBool>,
+}
+
+impl GamepadSource {
+    /// Starts reading the controllers, those connected later are read too.
+    ///
+    /// # Returns
+    /// The source, or an error if the controllers of the platform cannot be read.
+    pub fn new() -> Result<GamepadSource, Error> {
+        let (tx_events, rx_events) = mpsc::channel();
+        let (tx_started, rx_started) = mpsc::channel();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let reader_stopped = stopped.clone();
+
+        // Gilrs is not Send on every platform, so it lives in the reading thread
+        thread::Builder::new()
+            .name("gamepad-reader".to_string())
+            .spawn(move || match Gilrs::new() {
+                Ok(gilrs) => {
+                    let _ = tx_started.send(Ok(()));
+                    read_gamepads(gilrs, tx_events, reader_stopped);
+                }
+                Err(e) => {
+                    let _ = tx_started.send(Err(Error::new(ErrorKind::Other, e.to_string())));
+                }
+            })?;
+
+        rx_started
+            .recv()
+            .map_err(|_| Error::new(ErrorKind::Other, "The gamepad reader stopped"))??;
+        Ok(GamepadSource { rx_events, stopped })
+    }
+}
+
+impl InputSource for GamepadSource {
+    fn name(&self) -> &'static str {
+        "gamepad"
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<InputEvent>, Error> {
+        match self.rx_events.recv_timeout(timeout) {
+            Ok(event) => Ok(Some(event)),
+            Err(RecvTimeoutError::Timeout) => Ok(None),
+            Err(RecvTimeoutError::Disconnected) => Err(Error::new(
+                ErrorKind::NotConnected,
+                "The gamepad reader stopped",
+            )),
+        }
+    }
+}
+
+impl Drop for GamepadSource {
+    fn drop(&mut self) {
+        self.stopped.store(true, Ordering::Relaxed);
+    }
+}
+
+/// Sends the changes of the controllers until the source is dropped.
+///
+/// # Arguments
+///
+/// * `gilrs` - The controllers.
+/// * `tx_events` - Sender of the updates.
+/// * `stopped` - Set when the source is dropped.
+fn read_gamepads(mut gilrs: Gilrs, tx_events: Sender<InputEvent>, stopped: Arc<AtomicBool>) {
+    // Index sent for every controller, and the last state sent
+    let mut pads: HashMap<GamepadId, (u8, GamepadState)> = HashMap::new();
+
+    while !stopped.load(Ordering::Relaxed) {
+        let event = match gilrs.next_event_blocking(Some(INPUT_POLL_TIMEOUT)) {
+            Some(event) => event,
+            None => continue,
+        };
+
+        let state = match event.event {
+            // Released controllers are left at rest on the offerer
+            EventType::Disconnected => GamepadState::default(),
+            _ => gamepad_state(&gilrs.gamepad(event.id)),
+        };
+        let pad = match pads.get(&event.id) {
+            Some((pad, _)) => *pad,
+            None => match (0..MAX_GAMEPADS).find(|i| pads.values().all(|(pad, _)| pad != i)) {
+                Some(pad) => {
+                    log::info!("GAMEPAD | Controller {} connected", pad);
+                    pads.insert(event.id, (pad, GamepadState::default()));
+                    pad
+                }
+                None => continue,
+            },
+        };
+
+        let previous = &mut pads.get_mut(&event.id).expect("inserted above").1;
+        if let Some(update) = state.diff(pad, previous) {
+            *previous = state;
+            if tx_events.send(InputEvent::Gamepad(update)).is_err() {
+                return;
+            }
+        }
+        if event.event == EventType::Disconnected {
+            log::info!("GAMEPAD | Controller {} disconnected", pad);
+            pads.remove(&event.id);
+        }
+    }
+}
+
+/// Reads the state of a controller.
+fn gamepad_state(gamepad: &Gamepad) -> GamepadState {
+    let buttons = BUTTONS
+        .iter()
+        .filter(|(button, _)| gamepad.is_pressed(*button))
+        .fold(0, |mask, (_, bit)| mask | bit);
+    let trigger = |button: Button| {
+        let value = gamepad.button_data(button).map_or(0.0, |data| data.value());
+        (value.clamp(0.0, 1.0) * u8::MAX as f32).round() as u8
+    };
+    let thumb = |axis: Axis| (gamepad.value(axis).clamp(-1.0, 1.0) * i16::MAX as f32) as i16;
+
+    GamepadState {
+        buttons,
+        left_trigger: trigger(Button::LeftTrigger2),
+        right_trigger: trigger(Button::RightTrigger2),
+        thumb_lx: thumb(Axis::LeftStickX),
+        thumb_ly: thumb(Axis::LeftStickY),
+        thumb_rx: thumb(Axis::RightStickX),
+        thumb_ry: thumb(Axis::RightStickY),
+    }
+}
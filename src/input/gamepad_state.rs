@@ -0,0 +1,180 @@
+use serde::{Deserialize, Serialize};
+
+/// Buttons and axes of a controller, laid out as an XInput gamepad.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GamepadState {
+    /// Pressed buttons, a mask of the `GAMEPAD_*` constants of `input_const`.
+    pub buttons: u16,
+    /// Triggers, from 0 released to 255 fully pressed.
+    pub left_trigger: u8,
+    pub right_trigger: u8,
+    /// Sticks, positive to the right and up.
+    pub thumb_lx: i16,
+    pub thumb_ly: i16,
+    pub thumb_rx: i16,
+    pub thumb_ry: i16,
+}
+
+/// The fields of a controller that changed since its previous state.
+///
+/// Only the changes are sent, so a stick held still costs nothing while the other one moves.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct GamepadUpdate {
+    /// Index of the controller on the client.
+    pub pad: u8,
+    pub buttons: Option<u16>,
+    pub left_trigger: Option<u8>,
+    pub right_trigger: Option<u8>,
+    pub thumb_lx: Option<i16>,
+    pub thumb_ly: Option<i16>,
+    pub thumb_rx: Option<i16>,
+    pub thumb_ry: Option<i16>,
+}
+
+impl GamepadState {
+    /// Compares the state with the previous one of the same controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Index of the controller.
+    /// * `previous` - The last state sent.
+    ///
+    /// # Returns
+    /// The update to send, or `None` if nothing changed.
+    pub fn diff(&self, pad: u8, previous: &GamepadState) -> Option<GamepadUpdate> {
+        fn changed<T: PartialEq + Copy>(current: T, previous: T) -> Option<T> {
+            (current != previous).then_some(current)
+        }
+
+        let update = GamepadUpdate {
+            pad,
+            buttons: changed(self.buttons, previous.buttons),
+            left_trigger: changed(self.left_trigger, previous.left_trigger),
+            right_trigger: changed(self.right_trigger, previous.right_trigger),
+            thumb_lx: changed(self.thumb_lx, previous.thumb_lx),
+            thumb_ly: changed(self.thumb_ly, previous.thumb_ly),
+            thumb_rx: changed(self.thumb_rx, previous.thumb_rx),
+            thumb_ry: changed(self.thumb_ry, previous.thumb_ry),
+        };
+        (!update.is_empty()).then_some(update)
+    }
+
+    /// Applies the changes of an update.
+    pub fn apply(&mut self, update: &GamepadUpdate) {
+        self.buttons = update.buttons.unwrap_or(self.buttons);
+        self.left_trigger = update.left_trigger.unwrap_or(self.left_trigger);
+        self.right_trigger = update.right_trigger.unwrap_or(self.right_trigger);
+        self.thumb_lx = update.thumb_lx.unwrap_or(self.thumb_lx);
+        self.thumb_ly = update.thumb_ly.unwrap_or(self.thumb_ly);
+        self.thumb_rx = update.thumb_rx.unwrap_or(self.thumb_rx);
+        self.thumb_ry = update.thumb_ry.unwrap_or(self.thumb_ry);
+    }
+}
+
+impl GamepadUpdate {
+    /// Whether the update changes nothing.
+    pub fn is_empty(&self) -> bool {
+        *self
+            == GamepadUpdate {
+                pad: self.pad,
+                ..GamepadUpdate::default()
+            }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::input_const::{GAMEPAD_A, GAMEPAD_LEFT_SHOULDER};
+
+    fn held() -> GamepadState {
+        GamepadState {
+            buttons: GAMEPAD_A | GAMEPAD_LEFT_SHOULDER,
+            left_trigger: 200,
+            right_trigger: 0,
+            thumb_lx: -12000,
+            thumb_ly: 32767,
+            thumb_rx: 0,
+            thumb_ry: -32768,
+        }
+    }
+
+    #[test]
+    fn unchanged_state_gives_no_update() {
+        assert_eq!(held().diff(1, &held()), None);
+        assert_eq!(
+            GamepadState::default().diff(0, &GamepadState::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn only_changed_fields_are_sent() {
+        let previous = held();
+        let current = GamepadState {
+            buttons: GAMEPAD_A,
+            thumb_rx: 500,
+            ..previous
+        };
+
+        let update = current.diff(2, &previous).expect("the state changed");
+        assert_eq!(
+            update,
+            GamepadUpdate {
+                pad: 2,
+                buttons: Some(GAMEPAD_A),
+                thumb_rx: Some(500),
+                ..GamepadUpdate::default()
+            }
+        );
+        assert!(!update.is_empty());
+    }
+
+    #[test]
+    fn applying_the_diff_gives_the_new_state() {
+        let pairs = [
+            (held(), GamepadState::default()),
+            (GamepadState::default(), held()),
+            (
+                GamepadState {
+                    right_trigger: 255,
+                    thumb_ly: 0,
+                    ..held()
+                },
+                held(),
+            ),
+        ];
+
+        for (current, previous) in pairs {
+            let mut state = previous;
+            state.apply(&current.diff(0, &previous).expect("the state changed"));
+            assert_eq!(state, current);
+        }
+    }
+
+    #[test]
+    fn disconnect_resets_the_pad() {
+        // A disconnected controller is sent as the default state
+        let update = GamepadState::default()
+            .diff(3, &held())
+            .expect("the held state differs from the default one");
+
+        let mut offerer = held();
+        offerer.apply(&update);
+        assert_eq!(offerer, GamepadState::default());
+    }
+
+    #[test]
+    fn empty_update_changes_nothing() {
+        let update = GamepadUpdate {
+            pad: 1,
+            ..GamepadUpdate::default()
+        };
+        assert!(update.is_empty());
+
+        let mut state = held();
+        state.apply(&update);
+        assert_eq!(state, held());
+    }
+}
@@ -0,0 +1,159 @@
+use serde::{Deserialize, Serialize};
+
+use super::input_event::{InputEvent, MouseButton};
+
+/// Keys and mouse buttons held down, sent periodically so the offerer can release the ones
+/// it missed.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase", default, deny_unknown_fields)]
+pub struct HeldKeys {
+    /// One bit per Windows virtual key code.
+    pub keys: [u64; 4],
+    /// One bit per mouse button, by their code.
+    pub buttons: u8,
+}
+
+impl HeldKeys {
+    /// Updates the keys held with a press or a release, other events are ignored.
+    pub fn apply(&mut self, event: &InputEvent) {
+        match *event {
+            InputEvent::KeyPress(vk) => self.set_key(vk, true),
+            InputEvent::KeyRelease(vk) => self.set_key(vk, false),
+            InputEvent::MousePress(button) => self.buttons |= 1 << button.code(),
+            InputEvent::MouseRelease(button) => self.buttons &= !(1 << button.code()),
+            _ => {}
+        }
+    }
+
+    /// Whether a key is held down.
+    pub fn is_key_held(&self, vk: u8) -> bool {
+        self.keys[vk as usize / 64] & (1 << (vk % 64)) != 0
+    }
+
+    /// Whether a mouse button is held down.
+    pub fn is_button_held(&self, button: MouseButton) -> bool {
+        self.buttons & (1 << button.code()) != 0
+    }
+
+    /// Returns the presses and releases that turn these keys into `target`.
+    ///
+    /// Every release goes before the presses, so a missed release is never held together
+    /// with a new press. Keys go before mouse buttons, each ordered by their code.
+    ///
+    /// # Arguments
+    ///
+    /// * `target` - The keys that should be held.
+    pub fn reconcile(&self, target: &HeldKeys) -> Vec<InputEvent> {
+        let mut releases = Vec::new();
+        let mut presses = Vec::new();
+        for vk in 0..=u8::MAX {
+            match (self.is_key_held(vk), target.is_key_held(vk)) {
+                (true, false) => releases.push(InputEvent::KeyRelease(vk)),
+                (false, true) => presses.push(InputEvent::KeyPress(vk)),
+                _ => {}
+            }
+        }
+        for button in (0..=u8::MAX).map_while(MouseButton::from_code) {
+            match (self.is_button_held(button), target.is_button_held(button)) {
+                (true, false) => releases.push(InputEvent::MouseRelease(button)),
+                (false, true) => presses.push(InputEvent::MousePress(button)),
+                _ => {}
+            }
+        }
+        releases.extend(presses);
+        releases
+    }
+
+    fn set_key(&mut self, vk: u8, held: bool) {
+        let bit = 1 << (vk % 64);
+        if held {
+            self.keys[vk as usize / 64] |= bit;
+        } else {
+            self.keys[vk as usize / 64] &= !bit;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn held(keys: &[u8], buttons: &[MouseButton]) -> HeldKeys {
+        let mut held = HeldKeys::default();
+        for vk in keys {
+            held.apply(&InputEvent::KeyPress(*vk));
+        }
+        for button in buttons {
+            held.apply(&InputEvent::MousePress(*button));
+        }
+        held
+    }
+
+    #[test]
+    fn keys_at_word_boundaries_use_their_own_bit() {
+        let mut keys = held(&[63, 64, 255], &[]);
+
+        assert_eq!(keys.keys, [1 << 63, 1, 0, 1 << 63]);
+        for vk in [63, 64, 255] {
+            assert!(keys.is_key_held(vk));
+        }
+        for vk in [0, 62, 65, 127, 128, 254] {
+            assert!(!keys.is_key_held(vk));
+        }
+
+        keys.apply(&InputEvent::KeyRelease(64));
+        assert_eq!(keys.keys, [1 << 63, 0, 0, 1 << 63]);
+        assert!(keys.is_key_held(63));
+        assert!(!keys.is_key_held(64));
+    }
+
+    #[test]
+    fn mouse_buttons_use_their_code_bit() {
+        let mut keys = held(&[], &[MouseButton::Left, MouseButton::X2]);
+
+        assert_eq!(keys.buttons, 0b1_0001);
+        assert!(keys.is_button_held(MouseButton::Left));
+        assert!(keys.is_button_held(MouseButton::X2));
+        assert!(!keys.is_button_held(MouseButton::Right));
+        assert_eq!(keys.keys, [0; 4]);
+
+        keys.apply(&InputEvent::MouseRelease(MouseButton::Left));
+        assert_eq!(keys.buttons, 0b1_0000);
+    }
+
+    #[test]
+    fn other_events_are_ignored() {
+        let mut keys = held(&[0x41], &[MouseButton::Middle]);
+        let before = keys;
+
+        keys.apply(&InputEvent::MouseMove { dx: 1, dy: 1 });
+        keys.apply(&InputEvent::ScrollVertical(1.0));
+        assert_eq!(keys, before);
+    }
+
+    #[test]
+    fn reconcile_releases_before_pressing() {
+        let current = held(&[255, 0x41, 64], &[MouseButton::Right]);
+        let target = held(&[0x41, 63, 0x10], &[MouseButton::Left, MouseButton::X1]);
+
+        assert_eq!(
+            current.reconcile(&target),
+            vec![
+                InputEvent::KeyRelease(64),
+                InputEvent::KeyRelease(255),
+                InputEvent::MouseRelease(MouseButton::Right),
+                InputEvent::KeyPress(0x10),
+                InputEvent::KeyPress(63),
+                InputEvent::MousePress(MouseButton::Left),
+                InputEvent::MousePress(MouseButton::X1),
+            ]
+        );
+    }
+
+    #[test]
+    fn reconcile_with_the_same_keys_does_nothing() {
+        let keys = held(&[63, 64, 255], &[MouseButton::X2]);
+
+        assert!(keys.reconcile(&keys).is_empty());
+    }
+}
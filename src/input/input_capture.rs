@@ -1,329 +1,588 @@
-use std::ffi::OsStr;
-use std::io::{Error, ErrorKind};
-use std::os::windows::ffi::OsStrExt;
+use bytes::Bytes;
+use std::io::Error;
+use std::sync::atomic::{AtomicU32, Ordering};
 use std::sync::Arc;
-use std::thread::sleep;
-use std::time::Duration;
-use std::{iter, ptr};
+use std::time::{Duration, Instant};
+use tokio::sync::mpsc::{self, Sender};
+use tokio::time::{interval, interval_at, MissedTickBehavior};
+use webrtc::data_channel::data_channel_init::RTCDataChannelInit;
+use webrtc::data_channel::data_channel_state::RTCDataChannelState;
 use webrtc::data_channel::RTCDataChannel;
 use webrtc::peer_connection::RTCPeerConnection;
-use winapi::um::{libloaderapi, winuser};
-use winput::message_loop::{EventReceiver, MessageLoopError};
-use winput::{message_loop, Button, WheelDirection, WindowsError};
-use winput::{Action, Vk};
 
-use super::input_const::{KEYBOARD_CHANNEL_LABEL, MOUSE_CHANNEL_LABEL};
-use crate::output::output_const::*;
-use crate::utils::shutdown;
+use super::held_keys::HeldKeys;
+use super::input_codec::{encode_frame, InputFrame};
+use super::input_const::{
+    GAMEPAD_CHANNEL_LABEL, HELD_KEYS_SYNC_INTERVAL, INPUT_METRICS_INTERVAL, INPUT_POLL_TIMEOUT,
+    KEYBOARD_CHANNEL_LABEL, MOUSE_CHANNEL_LABEL, POINTER_SURFACE_HEIGHT, POINTER_SURFACE_WIDTH,
+};
+use super::input_event::{InputChannel, InputEvent, MouseMode};
+use super::input_source::InputSource;
+use super::motion_coalescer::MotionCoalescer;
+use crate::config::node_config::InputConfig;
+use crate::utils::node_error::NodeError;
+use crate::utils::supervisor::Task;
 
 /// # InputCapture
 ///
 /// The `InputCapture` struct represents a mechanism for capturing input events and send them via WebRTC data channels.
 pub struct InputCapture {
-    shutdown: shutdown::Shutdown,
+    task: Task,
     button_channel: Arc<RTCDataChannel>,
     mouse_channel: Arc<RTCDataChannel>,
+    gamepad_channel: Arc<RTCDataChannel>,
 }
 
 impl InputCapture {
     /// Creates a new `InputCapture`.
     ///
+    /// Keys, buttons and controller updates are sent through reliable ordered channels. Mouse
+    /// movements are neither ordered nor retransmitted, a lost movement is outdated by the next
+    /// one and waiting for it would delay them.
+    ///
     /// # Arguments
     ///
     /// * `pc` - An Arc reference to the RTCPeerConnection.
-    /// * `shutdown` - A shutdown handle for managing the finalization of the thread.
-    pub async fn new(
-        pc: Arc<RTCPeerConnection>,
-        shutdown: &mut shutdown::Shutdown,
-    ) -> Result<InputCapture, Error> {
-        let button_channel: Arc<RTCDataChannel> =
-            match pc.create_data_channel(KEYBOARD_CHANNEL_LABEL, None).await {
-                Ok(ch) => ch,
-                Err(_) => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        "Error creating latency data channel",
-                    ))
-                }
-            };
-        let mouse_channel: Arc<RTCDataChannel> =
-            match pc.create_data_channel(MOUSE_CHANNEL_LABEL, None).await {
-                Ok(ch) => ch,
-                Err(_) => {
-                    return Err(Error::new(
-                        ErrorKind::Other,
-                        "Error creating latency data channel",
-                    ))
-                }
-            };
+    /// * `task` - Handle of the supervised input capture task.
+    pub async fn new(pc: Arc<RTCPeerConnection>, task: Task) -> Result<InputCapture, NodeError> {
+        let button_channel: Arc<RTCDataChannel> = pc
+            .create_data_channel(KEYBOARD_CHANNEL_LABEL, None)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating keyboard data channel", e))?;
+        let mouse_options = RTCDataChannelInit {
+            ordered: Some(false),
+            max_retransmits: Some(0),
+            ..Default::default()
+        };
+        let mouse_channel: Arc<RTCDataChannel> = pc
+            .create_data_channel(MOUSE_CHANNEL_LABEL, Some(mouse_options))
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating mouse data channel", e))?;
+        let gamepad_channel: Arc<RTCDataChannel> = pc
+            .create_data_channel(GAMEPAD_CHANNEL_LABEL, None)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating gamepad data channel", e))?;
 
-        let shutdown_cpy = shutdown.clone();
         Ok(InputCapture {
-            shutdown: shutdown_cpy,
+            task,
             button_channel,
             mouse_channel,
+            gamepad_channel,
         })
     }
 
-    pub async fn start(&mut self) -> Result<(), Error> {
-        self.shutdown.add_task("Input Capture").await;
-
-        if message_loop::is_active() {
-            println!("MSG LOOP | YA ESTA ACTIVO");
-        }
-        let receiver: EventReceiver = match message_loop::start() {
-            Ok(receiver) => receiver,
-            Err(MessageLoopError::AlreadyActive) => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "INPUT CAPTURE | Failed to start: Already active",
-                ))
-            }
-            Err(MessageLoopError::OsError(e)) => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    std::format!("INPUT CAPTURE | Failed to start: Os Error {}", e),
-                ))
-            }
-        };
+    /// Sends the events of `sources` through the data channels until the task is cancelled.
+    ///
+    /// # Arguments
+    ///
+    /// * `sources` - Where the input events are read from.
+    /// * `config` - The mouse mode and the rate at which movements are sent.
+    ///
+    /// # Returns
+    /// An error if a source cannot be read anymore.
+    pub async fn start(
+        &mut self,
+        sources: Vec<Box<dyn InputSource>>,
+        config: &InputConfig,
+    ) -> Result<(), NodeError> {
+        let mouse_mode = config.mouse_mode;
+        log::info!(
+            "INPUT CAPTURE | Mouse mode {}, {} movements per second",
+            mouse_mode.name(),
+            config.mouse_rate
+        );
+        let mut pointer = Pointer::default();
+
+        let mut coalescer = MotionCoalescer::default();
+        let mut flush = interval(Duration::from_secs(1) / config.mouse_rate);
+        flush.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut report = interval_at(
+            tokio::time::Instant::now() + INPUT_METRICS_INTERVAL,
+            INPUT_METRICS_INTERVAL,
+        );
+        let mut held = HeldKeys::default();
+        let mut sync = interval_at(
+            tokio::time::Instant::now() + HELD_KEYS_SYNC_INTERVAL,
+            HELD_KEYS_SYNC_INTERVAL,
+        );
 
-        tokio::select! {
-            _ = self.shutdown.wait_for_error() => {
-                log::info!("INPUT CAPTURE | Shutdown received");
-            }
-            _ = start_handler(receiver, self.button_channel.clone(), self.mouse_channel.clone(),self.shutdown.clone()) => {
+        // Sources block while waiting for events, so each one is read from its own thread.
+        // Their events are numbered together, as a single stream.
+        let (tx_frames, mut rx_frames) = mpsc::channel(100);
+        let clock = Arc::new(FrameClock {
+            start: Instant::now(),
+            sequence: AtomicU32::new(0),
+        });
+        let readers: Vec<_> = sources
+            .into_iter()
+            .map(|source| {
+                log::info!(
+                    "INPUT CAPTURE | Reading input from the {} source",
+                    source.name()
+                );
+                let tx_frames = tx_frames.clone();
+                let clock = clock.clone();
+                let task = self.task.clone();
+                tokio::task::spawn_blocking(move || read_source(source, tx_frames, clock, task))
+            })
+            .collect();
+        drop(tx_frames);
+
+        loop {
+            tokio::select! {
+                _ = self.task.cancelled() => {
+                    log::info!("INPUT CAPTURE | Shutdown received");
+                    break;
+                }
+                _ = flush.tick() => {
+                    if let Some(frame) = coalescer.take() {
+                        self.send_frame(&frame).await;
+                    }
+                }
+                _ = report.tick() => coalescer.report(INPUT_METRICS_INTERVAL),
+                _ = sync.tick() => {
+                    // The keys sent so far, received before the sync by the ordered channel
+                    let frame = clock.frame(InputEvent::HeldKeys(held));
+                    self.send_frame(&frame).await;
+                }
+                frame = rx_frames.recv() => {
+                    let mut frame = match frame {
+                        Some(frame) => frame,
+                        // Every reader stopped, their results say why
+                        None => break,
+                    };
+                    if mouse_mode == MouseMode::Absolute {
+                        frame.event = pointer.absolute(frame.event);
+                    }
 
+                    if frame.event.channel() == InputChannel::Mouse {
+                        if let Some(previous) = coalescer.add(frame) {
+                            self.send_frame(&previous).await;
+                        }
+                    } else {
+                        // Pending movements go first, so the event happens where it did
+                        if let Some(movement) = coalescer.take() {
+                            self.send_frame(&movement).await;
+                        }
+                        held.apply(&frame.event);
+                        self.send_frame(&frame).await;
+                    }
+                }
             }
         }
 
-        unregister_class_w();
-
-        Ok(())
-    }
-}
-
-// Unregister the class created by the message loop
-// This is necessary to start the message loop again
-fn unregister_class_w() {
-    let mut attempts = 0;
-
-    while attempts < 5 {
-        // Retreives the module handle of the application.
-        unsafe {
-            let h_instance = libloaderapi::GetModuleHandleW(ptr::null());
-
-            // Create the window.
-            let class_name = OsStr::new("winput_message_loop")
-                .encode_wide()
-                .chain(iter::once(0))
-                .collect::<Vec<_>>();
-
-            let class = winuser::UnregisterClassW(class_name.as_ptr(), h_instance);
-            if class != 0 {
-                return; // Unregistration successful, exit the function
+        drop(rx_frames);
+        let mut result = Ok(());
+        for reader in readers {
+            match reader.await {
+                Ok(Err(e)) if result.is_ok() => result = Err(NodeError::Input(e)),
+                Ok(_) => {}
+                Err(e) => log::error!("INPUT CAPTURE | Input reader panicked | {}", e),
             }
         }
-
-        // Unregistration failed, print the error and try again
-        let error = std::format!(
-            "INPUT CAPTURE | Failed to start: Os Error {}",
-            WindowsError::from_last_error()
-        );
-        println!("UNREGISTER | Attempt {} failed: {}", attempts + 1, error);
-
-        // Sleep for a short duration before the next attempt
-        sleep(Duration::from_millis(1000));
-        attempts += 1;
+        result
     }
 
-    println!("UNREGISTER | All attempts failed");
-}
-
-/// Starts the input handler by listening for input events and sending them through the data channels.
-///
-/// # Arguments
-///
-/// * `receiver` - An EventReceiver for listening to input events.
-/// * `button_channel` - An Arc reference to the RTCDataChannel for the keyboard.
-/// * `mouse_channel` - An Arc reference to the RTCDataChannel for the mouse.
-/// * `shutdown` - A shutdown handle for managing the finalization of the thread.
-///
-/// # Returns
-///
-/// A Result containing () if the operation was successful, otherwise an Error is returned.
-async fn start_handler(
-    receiver: EventReceiver,
-    button_channel: Arc<RTCDataChannel>,
-    mouse_channel: Arc<RTCDataChannel>,
-    shutdown: shutdown::Shutdown,
-) {
-    // The List of keys that will be blocked by the APP:
-    let block_keys = [Vk::LeftWin, Vk::RightWin];
-
-    loop {
-        let button_channel = button_channel.clone();
-        let mouse_channel = mouse_channel.clone();
-        let shutdown_clone = shutdown.clone();
-
-        match receiver.next_event() {
-            message_loop::Event::Keyboard { vk, action, .. } if !block_keys.contains(&vk) => {
-                let action_str = if action == Action::Press {
-                    PRESS_KEYBOARD_ACTION
-                } else if action == Action::Release {
-                    RELEASE_KEYBOARD_ACTION
-                } else {
-                    continue;
-                };
-
-                let key = vk.into_u8().to_string();
-                match handle_button_action(button_channel, action_str, key, shutdown_clone).await {
-                    Ok(_) => (),
-                    Err(e) => log::error!(
-                        "INPUT CAPTURE | START HANDLER | Failed to handle button action: {}",
-                        e
-                    ),
-                }
-            }
-
-            message_loop::Event::MouseButton { action, button } => {
-                let action_str = if action == Action::Press {
-                    PRESS_MOUSE_ACTION
-                } else if action == Action::Release {
-                    RELEASE_MOUSE_ACTION
-                } else {
-                    continue;
-                };
-
-                match handle_button_action(
-                    button_channel,
-                    action_str,
-                    button_to_i32(button).to_string(),
-                    shutdown_clone,
-                )
-                .await
+    /// Sends a frame through the data channel of its event.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - The frame to send.
+    async fn send_frame(&self, frame: &InputFrame) {
+        let message = Bytes::from(encode_frame(frame));
+        match frame.event.channel() {
+            InputChannel::Button => {
+                if let Err(e) =
+                    handle_button_action(self.button_channel.clone(), message, self.task.clone())
+                        .await
                 {
-                    Ok(_) => (),
-                    Err(e) => log::error!(
+                    log::error!(
                         "INPUT CAPTURE | START HANDLER | Failed to handle button action: {}",
                         e
-                    ),
+                    );
                 }
             }
-
-            message_loop::Event::MouseWheel { delta, direction } => {
-                if delta == 0.0 {
-                    continue;
+            InputChannel::Mouse => {
+                if self.mouse_channel.ready_state() == RTCDataChannelState::Open {
+                    if let Err(e) = self.mouse_channel.send(&message).await {
+                        log::error!(
+                            "INPUT CAPTURE | START HANDLER | Failed to send mouse event: {}",
+                            e
+                        );
+                    }
                 }
-
-                let action_str = if direction == WheelDirection::Horizontal {
-                    SCROLL_HORIZONTAL_ACTION
-                } else if direction == WheelDirection::Vertical {
-                    SCROLL_VERTICAL_ACTION
-                } else {
-                    continue;
-                };
-
-                match handle_button_action(
-                    button_channel,
-                    action_str,
-                    delta.to_string(),
-                    shutdown_clone,
-                )
-                .await
+            }
+            InputChannel::Gamepad => {
+                if let Err(e) =
+                    handle_button_action(self.gamepad_channel.clone(), message, self.task.clone())
+                        .await
                 {
-                    Ok(_) => (),
-                    Err(e) => log::error!(
-                        "INPUT CAPTURE | START HANDLER | Failed to handle button action: {}",
+                    log::error!(
+                        "INPUT CAPTURE | START HANDLER | Failed to send gamepad update: {}",
                         e
-                    ),
+                    );
                 }
             }
+        }
+    }
+}
 
-            message_loop::Event::MouseMoveRelative { x, y } => {
-                if x == 0 && y == 0 {
-                    continue;
-                }
-                if mouse_channel.ready_state()
-                    == webrtc::data_channel::data_channel_state::RTCDataChannelState::Open
-                {
-                    let mouse_channel_cpy = mouse_channel.clone();
+/// Position of the mouse over the video in absolute mode.
+///
+/// Sources report movements, which are added up over a surface of
+/// `POINTER_SURFACE_WIDTH` by `POINTER_SURFACE_HEIGHT` pixels. The position starts at the
+/// center and stays inside the video.
+struct Pointer {
+    x: f32,
+    y: f32,
+}
 
-                    match mouse_channel_cpy
-                        .send_text(std::format!("{} {}", x, y).as_str())
-                        .await
-                    {
-                        Ok(_) => (),
-                        Err(e) => log::error!(
-                            "INPUT CAPTURE | START HANDLER | Failed to send mouse event: {}",
-                            e
-                        ),
-                    }
+impl Default for Pointer {
+    fn default() -> Self {
+        Self { x: 0.5, y: 0.5 }
+    }
+}
+
+impl Pointer {
+    /// Turns a movement into the new position, the rest of the events are kept.
+    fn absolute(&mut self, event: InputEvent) -> InputEvent {
+        match event {
+            InputEvent::MouseMove { dx, dy } => {
+                self.x = (self.x + dx as f32 / POINTER_SURFACE_WIDTH).clamp(0.0, 1.0);
+                self.y = (self.y + dy as f32 / POINTER_SURFACE_HEIGHT).clamp(0.0, 1.0);
+                InputEvent::MousePosition {
+                    x: self.x,
+                    y: self.y,
                 }
             }
-            _ => (),
+            InputEvent::MousePosition { x, y } => {
+                self.x = x;
+                self.y = y;
+                event
+            }
+            _ => event,
         }
+    }
+}
 
-        if shutdown.check_for_error().await {
-            log::error!("INPUT CAPTURE | Shutdown received on check for error");
-            receiver.clear();
-            drop(receiver);
-            break;
-        };
+/// Numbers and timestamps the events of every source.
+struct FrameClock {
+    start: Instant,
+    sequence: AtomicU32,
+}
 
-        tokio::task::yield_now().await;
+impl FrameClock {
+    /// Returns the frame of an event, with the next number and the current time.
+    fn frame(&self, event: InputEvent) -> InputFrame {
+        InputFrame {
+            sequence: self.sequence.fetch_add(1, Ordering::Relaxed),
+            timestamp_ms: self.start.elapsed().as_millis() as u32,
+            event,
+        }
+    }
+}
+
+/// Reads the events of the source until the task is cancelled or the events are not needed.
+///
+/// Events are numbered and timestamped as they are read.
+///
+/// # Arguments
+///
+/// * `source` - Where the input events are read from.
+/// * `tx_frames` - Sender of the frames read.
+/// * `clock` - Numbers and timestamps the events.
+/// * `task` - Handle of the input capture task.
+///
+/// # Returns
+/// An error if the source cannot be read anymore.
+fn read_source(
+    mut source: Box<dyn InputSource>,
+    tx_frames: Sender<InputFrame>,
+    clock: Arc<FrameClock>,
+    task: Task,
+) -> Result<(), Error> {
+    while !task.is_cancelled() {
+        let event = match source.next_event(INPUT_POLL_TIMEOUT) {
+            Ok(Some(event)) => event,
+            Ok(None) => continue,
+            Err(e) => {
+                log::error!(
+                    "INPUT CAPTURE | The {} source stopped | {}",
+                    source.name(),
+                    e
+                );
+                return Err(e);
+            }
+        };
+        if tx_frames.blocking_send(clock.frame(event)).is_err() {
+            break;
+        }
     }
+    Ok(())
 }
 
 /// Handles the button action by sending the corresponding message through the data channel.
 ///
 /// # Arguments
-///     
+///
 /// * `button_channel` - An Arc reference to the RTCDataChannel.
-/// * `action` - A string slice representing the action to be performed.
-/// * `text` - A string representing the text to be sent.
-/// * `shutdown` - A shutdown handle for managing the finalization of the thread.
+/// * `message` - The encoded frame to be sent.
+/// * `task` - Handle of the input capture task, notified if the message cannot be sent.
 ///
 /// # Returns
 ///
 /// A Result containing () if the operation was successful, otherwise an Error is returned.
 async fn handle_button_action(
     button_channel: Arc<RTCDataChannel>,
-    action: &str,
-    text: String,
-    shutdown: shutdown::Shutdown,
-) -> Result<(), Error> {
-    if button_channel.ready_state()
-        == webrtc::data_channel::data_channel_state::RTCDataChannelState::Open
-    {
-        if let Err(_e) = button_channel
-            .send_text(std::format!("{}{}", action, text).as_str())
-            .await
-        {
-            shutdown.notify_error(false, "Button action channel").await;
-            return Err(Error::new(
-                ErrorKind::Other,
+    message: Bytes,
+    task: Task,
+) -> Result<(), NodeError> {
+    if button_channel.ready_state() == RTCDataChannelState::Open {
+        if let Err(e) = button_channel.send(&message).await {
+            task.fail("Button action channel");
+            return Err(NodeError::WebRtc(
                 "Error sending message through data channel",
+                e,
             ));
         };
     };
     Ok(())
 }
 
-/// Maps the mouse button to the corresponding integer value.
-///
-/// # Arguments
-///
-/// * `button` - A Button value.
-///
-/// # Returns
-///
-/// An integer value corresponding to the button.
-fn button_to_i32(button: Button) -> i32 {
-    match button {
-        Button::Left => 0,
-        Button::Right => 1,
-        Button::Middle => 2,
-        Button::X1 => 3,
-        Button::X2 => 4,
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::atomic::AtomicUsize;
+    use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
+    use tokio::time::{sleep, timeout};
+    use webrtc::api::APIBuilder;
+    use webrtc::peer_connection::configuration::RTCConfiguration;
+
+    use crate::input::gamepad_state::GamepadUpdate;
+    use crate::input::input_codec::decode_frame;
+    use crate::input::input_source::ScriptedSource;
+    use crate::utils::supervisor::Supervisor;
+
+    const SCRIPT: &str = r#"
+        {"atMs": 0, "event": {"keyPress": 65}}
+        {"atMs": 10, "event": {"mouseMove": {"dx": 10, "dy": -3}}}
+        {"atMs": 12, "event": {"mouseMove": {"dx": 5, "dy": 3}}}
+        {"atMs": 20, "event": {"gamepad": {"pad": 0, "buttons": 4096}}}
+        {"atMs": 30, "event": {"keyRelease": 65}}
+    "#;
+    const DEADLINE: Duration = Duration::from_secs(10);
+
+    /// Creates a peer connection without ICE servers, only host candidates are used.
+    async fn peer_connection() -> Arc<RTCPeerConnection> {
+        let api = APIBuilder::new().build();
+        Arc::new(
+            api.new_peer_connection(RTCConfiguration::default())
+                .await
+                .unwrap(),
+        )
+    }
+
+    /// Exchanges the descriptions of two peer connections of the same process.
+    async fn connect(offerer: &RTCPeerConnection, answerer: &RTCPeerConnection) {
+        let offer = offerer.create_offer(None).await.unwrap();
+        let mut gathered = offerer.gathering_complete_promise().await;
+        offerer.set_local_description(offer).await.unwrap();
+        let _ = gathered.recv().await;
+        answerer
+            .set_remote_description(offerer.local_description().await.unwrap())
+            .await
+            .unwrap();
+
+        let answer = answerer.create_answer(None).await.unwrap();
+        let mut gathered = answerer.gathering_complete_promise().await;
+        answerer.set_local_description(answer).await.unwrap();
+        let _ = gathered.recv().await;
+        offerer
+            .set_remote_description(answerer.local_description().await.unwrap())
+            .await
+            .unwrap();
+    }
+
+    /// Forwards the events received by `pc`, with the label of their channel.
+    ///
+    /// # Returns
+    /// The receiver of the events and the number of channels opened so far.
+    fn receive_events(
+        pc: &RTCPeerConnection,
+    ) -> (UnboundedReceiver<(String, InputEvent)>, Arc<AtomicUsize>) {
+        let (tx, rx) = unbounded_channel();
+        let opened = Arc::new(AtomicUsize::new(0));
+        let opened_cpy = opened.clone();
+        pc.on_data_channel(Box::new(move |channel| {
+            let tx = tx.clone();
+            let label = channel.label().to_owned();
+            channel.on_message(Box::new(move |msg| {
+                let frame = decode_frame(&msg.data).expect("the frames sent are valid");
+                let _ = tx.send((label.clone(), frame.event));
+                Box::pin(async {})
+            }));
+            opened_cpy.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async {})
+        }));
+        (rx, opened)
+    }
+
+    /// Sum of the movements received, they may be merged in any way.
+    fn moved(events: &[InputEvent]) -> (i32, i32) {
+        events.iter().fold((0, 0), |(x, y), event| match event {
+            InputEvent::MouseMove { dx, dy } => (x + dx, y + dy),
+            _ => (x, y),
+        })
+    }
+
+    struct Connected {
+        client: Arc<RTCPeerConnection>,
+        offerer: Arc<RTCPeerConnection>,
+        rx: UnboundedReceiver<(String, InputEvent)>,
+        capture: InputCapture,
+    }
+
+    /// Creates an input capture whose channels are open to an offerer of the same process.
+    async fn connected_capture(supervisor: &Supervisor) -> Connected {
+        let client = peer_connection().await;
+        let offerer = peer_connection().await;
+        let (rx, opened) = receive_events(&offerer);
+
+        let capture = InputCapture::new(client.clone(), supervisor.task("Input capture"))
+            .await
+            .unwrap();
+        connect(&client, &offerer).await;
+
+        let channels = [
+            capture.button_channel.clone(),
+            capture.mouse_channel.clone(),
+            capture.gamepad_channel.clone(),
+        ];
+        timeout(DEADLINE, async {
+            while opened.load(Ordering::SeqCst) < channels.len()
+                || channels
+                    .iter()
+                    .any(|c| c.ready_state() != RTCDataChannelState::Open)
+            {
+                sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the data channels did not open");
+
+        Connected {
+            client,
+            offerer,
+            rx,
+            capture,
+        }
+    }
+
+    #[tokio::test]
+    async fn scripted_events_reach_the_offerer_through_their_channels() {
+        let supervisor = Supervisor::new();
+        let Connected {
+            client,
+            offerer,
+            mut rx,
+            mut capture,
+        } = connected_capture(&supervisor).await;
+
+        let source = ScriptedSource::parse(SCRIPT).unwrap();
+        let capturing = tokio::spawn(async move {
+            capture
+                .start(vec![Box::new(source)], &InputConfig::default())
+                .await
+        });
+
+        let mut received: HashMap<String, Vec<InputEvent>> = HashMap::new();
+        let all_received = |received: &HashMap<String, Vec<InputEvent>>| {
+            let events = |label: &str| received.get(label).cloned().unwrap_or_default();
+            events(KEYBOARD_CHANNEL_LABEL).contains(&InputEvent::KeyRelease(65))
+                && !events(GAMEPAD_CHANNEL_LABEL).is_empty()
+                && moved(&events(MOUSE_CHANNEL_LABEL)) == (15, 0)
+        };
+        timeout(DEADLINE, async {
+            while !all_received(&received) {
+                let (label, event) = rx.recv().await.unwrap();
+                received.entry(label).or_default().push(event);
+            }
+        })
+        .await
+        .expect("the scripted events were not received");
+
+        supervisor.stop();
+        capturing.await.unwrap().unwrap();
+        client.close().await.unwrap();
+        offerer.close().await.unwrap();
+
+        let keys: Vec<_> = received[KEYBOARD_CHANNEL_LABEL]
+            .iter()
+            .filter(|event| !matches!(event, InputEvent::HeldKeys(_)))
+            .copied()
+            .collect();
+        assert_eq!(keys, [InputEvent::KeyPress(65), InputEvent::KeyRelease(65)]);
+        assert_eq!(
+            received[GAMEPAD_CHANNEL_LABEL],
+            [InputEvent::Gamepad(GamepadUpdate {
+                pad: 0,
+                buttons: Some(4096),
+                ..Default::default()
+            })]
+        );
+        assert!(received[MOUSE_CHANNEL_LABEL]
+            .iter()
+            .all(|event| matches!(event, InputEvent::MouseMove { .. })));
+    }
+
+    #[tokio::test]
+    async fn pending_movement_is_sent_before_a_key() {
+        const FLUSH_SCRIPT: &str = r#"
+            {"atMs": 40, "event": {"mouseMove": {"dx": 4, "dy": 1}}}
+            {"atMs": 50, "event": {"mouseMove": {"dx": 6, "dy": 1}}}
+            {"atMs": 60, "event": {"keyPress": 70}}
+        "#;
+        let supervisor = Supervisor::new();
+        let Connected {
+            client,
+            offerer,
+            mut rx,
+            mut capture,
+        } = connected_capture(&supervisor).await;
+
+        // Movements are flushed once per second, the first time as soon as the capture starts
+        let config = InputConfig {
+            mouse_rate: 1,
+            ..InputConfig::default()
+        };
+        let source = ScriptedSource::parse(FLUSH_SCRIPT).unwrap();
+        let started = Instant::now();
+        let capturing =
+            tokio::spawn(async move { capture.start(vec![Box::new(source)], &config).await });
+
+        let mut mouse = Vec::new();
+        let mut key_received = false;
+        let received = timeout(DEADLINE, async {
+            while !key_received || moved(&mouse) != (10, 2) {
+                match rx.recv().await.unwrap() {
+                    (label, event) if label == MOUSE_CHANNEL_LABEL => mouse.push(event),
+                    (_, event) => key_received |= event == InputEvent::KeyPress(70),
+                }
+            }
+        })
+        .await;
+        let elapsed = started.elapsed();
+
+        // Stopped before checking, the source is read until the capture is cancelled
+        supervisor.stop();
+        capturing.await.unwrap().unwrap();
+        client.close().await.unwrap();
+        offerer.close().await.unwrap();
+
+        received.expect("the movement and the key were not received");
+        // Before the second flush, so the key press is what sent the movement
+        assert!(elapsed < Duration::from_secs(1));
+        assert_eq!(mouse, [InputEvent::MouseMove { dx: 10, dy: 2 }]);
     }
 }
@@ -0,0 +1,497 @@
+use std::io::{Error, ErrorKind};
+
+use super::gamepad_state::GamepadUpdate;
+use super::held_keys::HeldKeys;
+use super::input_const::{INPUT_FRAME_HEADER_LEN, INPUT_PROTOCOL_VERSION, MAX_GAMEPADS};
+use super::input_event::{InputEvent, MouseButton};
+
+const KEY_PRESS: u8 = 1;
+const KEY_RELEASE: u8 = 2;
+const MOUSE_PRESS: u8 = 3;
+const MOUSE_RELEASE: u8 = 4;
+const MOUSE_MOVE: u8 = 5;
+const SCROLL_VERTICAL: u8 = 6;
+const SCROLL_HORIZONTAL: u8 = 7;
+const GAMEPAD: u8 = 8;
+const MOUSE_POSITION: u8 = 9;
+const HELD_KEYS: u8 = 10;
+
+/// Four 64 bit masks of keys and one of mouse buttons.
+const HELD_KEYS_LEN: usize = 33;
+/// Mouse buttons that have a code.
+const MOUSE_BUTTONS: u8 = 0x1F;
+
+/// Fields of a gamepad update present in its payload, in the order they are written.
+const GAMEPAD_BUTTONS: u8 = 0x01;
+const GAMEPAD_LEFT_TRIGGER: u8 = 0x02;
+const GAMEPAD_RIGHT_TRIGGER: u8 = 0x04;
+const GAMEPAD_THUMB_LX: u8 = 0x08;
+const GAMEPAD_THUMB_LY: u8 = 0x10;
+const GAMEPAD_THUMB_RX: u8 = 0x20;
+const GAMEPAD_THUMB_RY: u8 = 0x40;
+const GAMEPAD_FIELDS: u8 = 0x7F;
+
+/// An input event with the metadata sent along with it.
+///
+/// Frames are sent as a 10 byte header, version, kind of event, sequence number and
+/// timestamp, followed by the payload of the event. Integers are big endian, the layout of
+/// every kind is described in the README.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct InputFrame {
+    /// Number of the event, incremented for every event sent by the client.
+    pub sequence: u32,
+    /// Milliseconds between the start of the capture and the event, wrapping.
+    pub timestamp_ms: u32,
+    pub event: InputEvent,
+}
+
+/// Encodes a frame.
+///
+/// # Arguments
+///
+/// * `frame` - The frame to send.
+///
+/// # Returns
+/// The bytes of the frame.
+pub fn encode_frame(frame: &InputFrame) -> Vec<u8> {
+    let mut data = Vec::with_capacity(INPUT_FRAME_HEADER_LEN + 8);
+    data.push(INPUT_PROTOCOL_VERSION);
+    data.push(kind(&frame.event));
+    data.extend_from_slice(&frame.sequence.to_be_bytes());
+    data.extend_from_slice(&frame.timestamp_ms.to_be_bytes());
+
+    match frame.event {
+        InputEvent::KeyPress(vk) | InputEvent::KeyRelease(vk) => data.push(vk),
+        InputEvent::MousePress(button) | InputEvent::MouseRelease(button) => {
+            data.push(button.code())
+        }
+        InputEvent::MouseMove { dx, dy } => {
+            data.extend_from_slice(&dx.to_be_bytes());
+            data.extend_from_slice(&dy.to_be_bytes());
+        }
+        InputEvent::ScrollVertical(delta) | InputEvent::ScrollHorizontal(delta) => {
+            data.extend_from_slice(&delta.to_be_bytes())
+        }
+        InputEvent::Gamepad(update) => encode_gamepad(&update, &mut data),
+        InputEvent::MousePosition { x, y } => {
+            data.extend_from_slice(&x.to_be_bytes());
+            data.extend_from_slice(&y.to_be_bytes());
+        }
+        InputEvent::HeldKeys(held) => {
+            for keys in held.keys {
+                data.extend_from_slice(&keys.to_be_bytes());
+            }
+            data.push(held.buttons);
+        }
+    }
+    data
+}
+
+/// Decodes a frame.
+///
+/// # Arguments
+///
+/// * `data` - The bytes received.
+///
+/// # Returns
+/// The frame, or an `InvalidData` error if the version is not supported or the frame is
+/// malformed.
+pub fn decode_frame(data: &[u8]) -> Result<InputFrame, Error> {
+    if data.len() < INPUT_FRAME_HEADER_LEN {
+        return Err(invalid(format!("Frame too short, {} bytes", data.len())));
+    }
+    let (header, payload) = data.split_at(INPUT_FRAME_HEADER_LEN);
+    if header[0] != INPUT_PROTOCOL_VERSION {
+        return Err(invalid(format!(
+            "Unsupported input protocol version {}",
+            header[0]
+        )));
+    }
+    let sequence = u32::from_be_bytes([header[2], header[3], header[4], header[5]]);
+    let timestamp_ms = u32::from_be_bytes([header[6], header[7], header[8], header[9]]);
+
+    let event = match header[1] {
+        KEY_PRESS => InputEvent::KeyPress(byte(payload)?),
+        KEY_RELEASE => InputEvent::KeyRelease(byte(payload)?),
+        MOUSE_PRESS => InputEvent::MousePress(button(payload)?),
+        MOUSE_RELEASE => InputEvent::MouseRelease(button(payload)?),
+        MOUSE_MOVE => {
+            let [dx, dy] = words(payload)?;
+            InputEvent::MouseMove {
+                dx: i32::from_be_bytes(dx),
+                dy: i32::from_be_bytes(dy),
+            }
+        }
+        SCROLL_VERTICAL => InputEvent::ScrollVertical(scroll(payload)?),
+        SCROLL_HORIZONTAL => InputEvent::ScrollHorizontal(scroll(payload)?),
+        GAMEPAD => InputEvent::Gamepad(decode_gamepad(payload)?),
+        MOUSE_POSITION => {
+            let [x, y] = words(payload)?;
+            InputEvent::MousePosition {
+                x: coordinate(f32::from_be_bytes(x))?,
+                y: coordinate(f32::from_be_bytes(y))?,
+            }
+        }
+        HELD_KEYS => InputEvent::HeldKeys(decode_held_keys(payload)?),
+        kind => return Err(invalid(format!("Unknown event kind {}", kind))),
+    };
+
+    Ok(InputFrame {
+        sequence,
+        timestamp_ms,
+        event,
+    })
+}
+
+fn kind(event: &InputEvent) -> u8 {
+    match event {
+        InputEvent::KeyPress(_) => KEY_PRESS,
+        InputEvent::KeyRelease(_) => KEY_RELEASE,
+        InputEvent::MousePress(_) => MOUSE_PRESS,
+        InputEvent::MouseRelease(_) => MOUSE_RELEASE,
+        InputEvent::MouseMove { .. } => MOUSE_MOVE,
+        InputEvent::ScrollVertical(_) => SCROLL_VERTICAL,
+        InputEvent::ScrollHorizontal(_) => SCROLL_HORIZONTAL,
+        InputEvent::Gamepad(_) => GAMEPAD,
+        InputEvent::MousePosition { .. } => MOUSE_POSITION,
+        InputEvent::HeldKeys(_) => HELD_KEYS,
+    }
+}
+
+/// Writes the index of the controller, the mask of the fields present and their values.
+fn encode_gamepad(update: &GamepadUpdate, data: &mut Vec<u8>) {
+    let mut mask = 0;
+    let mut fields = Vec::with_capacity(12);
+    if let Some(buttons) = update.buttons {
+        mask |= GAMEPAD_BUTTONS;
+        fields.extend_from_slice(&buttons.to_be_bytes());
+    }
+    for (bit, trigger) in [
+        (GAMEPAD_LEFT_TRIGGER, update.left_trigger),
+        (GAMEPAD_RIGHT_TRIGGER, update.right_trigger),
+    ] {
+        if let Some(trigger) = trigger {
+            mask |= bit;
+            fields.push(trigger);
+        }
+    }
+    for (bit, thumb) in [
+        (GAMEPAD_THUMB_LX, update.thumb_lx),
+        (GAMEPAD_THUMB_LY, update.thumb_ly),
+        (GAMEPAD_THUMB_RX, update.thumb_rx),
+        (GAMEPAD_THUMB_RY, update.thumb_ry),
+    ] {
+        if let Some(thumb) = thumb {
+            mask |= bit;
+            fields.extend_from_slice(&thumb.to_be_bytes());
+        }
+    }
+
+    data.push(update.pad);
+    data.push(mask);
+    data.extend_from_slice(&fields);
+}
+
+fn decode_gamepad(payload: &[u8]) -> Result<GamepadUpdate, Error> {
+    let (pad, mask, mut fields) = match payload {
+        [pad, mask, fields @ ..] => (*pad, *mask, fields),
+        _ => return Err(payload_len(payload, 2)),
+    };
+    if pad >= MAX_GAMEPADS {
+        return Err(invalid(format!("Unknown gamepad {}", pad)));
+    }
+    if mask & !GAMEPAD_FIELDS != 0 {
+        return Err(invalid(format!("Unknown gamepad fields {:#04x}", mask)));
+    }
+
+    let mut update = GamepadUpdate {
+        pad,
+        ..GamepadUpdate::default()
+    };
+    if mask & GAMEPAD_BUTTONS != 0 {
+        update.buttons = Some(u16::from_be_bytes(take(&mut fields)?));
+    }
+    if mask & GAMEPAD_LEFT_TRIGGER != 0 {
+        update.left_trigger = Some(u8::from_be_bytes(take(&mut fields)?));
+    }
+    if mask & GAMEPAD_RIGHT_TRIGGER != 0 {
+        update.right_trigger = Some(u8::from_be_bytes(take(&mut fields)?));
+    }
+    for (bit, thumb) in [
+        (GAMEPAD_THUMB_LX, &mut update.thumb_lx),
+        (GAMEPAD_THUMB_LY, &mut update.thumb_ly),
+        (GAMEPAD_THUMB_RX, &mut update.thumb_rx),
+        (GAMEPAD_THUMB_RY, &mut update.thumb_ry),
+    ] {
+        if mask & bit != 0 {
+            *thumb = Some(i16::from_be_bytes(take(&mut fields)?));
+        }
+    }
+
+    if !fields.is_empty() {
+        return Err(invalid(format!(
+            "{} extra bytes in gamepad payload",
+            fields.len()
+        )));
+    }
+    Ok(update)
+}
+
+fn decode_held_keys(payload: &[u8]) -> Result<HeldKeys, Error> {
+    if payload.len() != HELD_KEYS_LEN {
+        return Err(payload_len(payload, HELD_KEYS_LEN));
+    }
+    let (mut masks, buttons) = payload.split_at(HELD_KEYS_LEN - 1);
+    let buttons = buttons[0];
+    if buttons & !MOUSE_BUTTONS != 0 {
+        return Err(invalid(format!("Unknown mouse buttons {:#04x}", buttons)));
+    }
+
+    let mut held = HeldKeys {
+        buttons,
+        ..HeldKeys::default()
+    };
+    for keys in held.keys.iter_mut() {
+        *keys = u64::from_be_bytes(take(&mut masks)?);
+    }
+    Ok(held)
+}
+
+/// Takes the next `N` bytes of a payload.
+fn take<const N: usize>(fields: &mut &[u8]) -> Result<[u8; N], Error> {
+    if fields.len() < N {
+        return Err(invalid("Payload too short".to_owned()));
+    }
+    let (value, rest) = fields.split_at(N);
+    *fields = rest;
+    Ok(value.try_into().expect("the length was checked"))
+}
+
+fn byte(payload: &[u8]) -> Result<u8, Error> {
+    match payload {
+        [value] => Ok(*value),
+        _ => Err(payload_len(payload, 1)),
+    }
+}
+
+fn button(payload: &[u8]) -> Result<MouseButton, Error> {
+    let code = byte(payload)?;
+    MouseButton::from_code(code).ok_or_else(|| invalid(format!("Unknown mouse button {}", code)))
+}
+
+fn word(payload: &[u8]) -> Result<[u8; 4], Error> {
+    payload.try_into().map_err(|_| payload_len(payload, 4))
+}
+
+fn words(payload: &[u8]) -> Result<[[u8; 4]; 2], Error> {
+    if payload.len() != 8 {
+        return Err(payload_len(payload, 8));
+    }
+    let (first, second) = payload.split_at(4);
+    Ok([word(first)?, word(second)?])
+}
+
+fn scroll(payload: &[u8]) -> Result<f32, Error> {
+    let delta = f32::from_be_bytes(word(payload)?);
+    if !delta.is_finite() {
+        return Err(invalid(format!("Invalid scroll delta {}", delta)));
+    }
+    Ok(delta)
+}
+
+fn coordinate(value: f32) -> Result<f32, Error> {
+    if !(0.0..=1.0).contains(&value) {
+        return Err(invalid(format!("Coordinate {} out of range", value)));
+    }
+    Ok(value)
+}
+
+fn payload_len(payload: &[u8], expected: usize) -> Error {
+    invalid(format!(
+        "Payload of {} bytes, expected {}",
+        payload.len(),
+        expected
+    ))
+}
+
+fn invalid(message: String) -> Error {
+    Error::new(ErrorKind::InvalidData, message)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use proptest::prelude::*;
+
+    /// One event of every kind, with values that use every byte of their payload.
+    fn events() -> Vec<InputEvent> {
+        vec![
+            InputEvent::KeyPress(0x41),
+            InputEvent::KeyRelease(0xFF),
+            InputEvent::MousePress(MouseButton::Left),
+            InputEvent::MouseRelease(MouseButton::X2),
+            InputEvent::MouseMove {
+                dx: i32::MIN,
+                dy: -3,
+            },
+            InputEvent::ScrollVertical(-1.5),
+            InputEvent::ScrollHorizontal(120.0),
+            InputEvent::Gamepad(GamepadUpdate {
+                pad: MAX_GAMEPADS - 1,
+                buttons: Some(0x9001),
+                left_trigger: Some(255),
+                right_trigger: Some(0),
+                thumb_lx: Some(i16::MIN),
+                thumb_ly: Some(-1),
+                thumb_rx: Some(i16::MAX),
+                thumb_ry: Some(1),
+            }),
+            InputEvent::Gamepad(GamepadUpdate {
+                pad: 0,
+                thumb_ry: Some(-32),
+                ..GamepadUpdate::default()
+            }),
+            InputEvent::MousePosition { x: 0.0, y: 1.0 },
+            InputEvent::HeldKeys(HeldKeys {
+                keys: [1, u64::MAX, 0, 1 << 63],
+                buttons: MOUSE_BUTTONS,
+            }),
+        ]
+    }
+
+    fn frame(event: InputEvent) -> InputFrame {
+        InputFrame {
+            sequence: 0xDEAD_BEEF,
+            timestamp_ms: u32::MAX,
+            event,
+        }
+    }
+
+    /// Encodes a frame and replaces its payload.
+    fn with_payload(event: InputEvent, payload: &[u8]) -> Vec<u8> {
+        let mut data = encode_frame(&frame(event));
+        data.truncate(INPUT_FRAME_HEADER_LEN);
+        data.extend_from_slice(payload);
+        data
+    }
+
+    fn assert_invalid(data: &[u8]) {
+        let e = decode_frame(data).expect_err("the frame should be rejected");
+        assert_eq!(e.kind(), ErrorKind::InvalidData);
+    }
+
+    #[test]
+    fn every_kind_decodes_back() {
+        for event in events() {
+            let frame = frame(event);
+            assert_eq!(decode_frame(&encode_frame(&frame)).unwrap(), frame);
+        }
+    }
+
+    #[test]
+    fn truncated_frames_are_rejected() {
+        for event in events() {
+            let data = encode_frame(&frame(event));
+            for len in 0..data.len() {
+                assert_invalid(&data[..len]);
+            }
+        }
+    }
+
+    #[test]
+    fn other_versions_are_rejected() {
+        let mut data = encode_frame(&frame(InputEvent::KeyPress(0x41)));
+        for version in [0, INPUT_PROTOCOL_VERSION + 1, u8::MAX] {
+            data[0] = version;
+            assert_invalid(&data);
+        }
+    }
+
+    #[test]
+    fn unknown_kinds_are_rejected() {
+        let mut data = encode_frame(&frame(InputEvent::KeyPress(0x41)));
+        for kind in [0, HELD_KEYS + 1, u8::MAX] {
+            data[1] = kind;
+            assert_invalid(&data);
+        }
+    }
+
+    #[test]
+    fn scroll_that_is_not_finite_is_rejected() {
+        for delta in [f32::NAN, f32::INFINITY, f32::NEG_INFINITY] {
+            assert_invalid(&encode_frame(&frame(InputEvent::ScrollVertical(delta))));
+            assert_invalid(&encode_frame(&frame(InputEvent::ScrollHorizontal(delta))));
+        }
+    }
+
+    #[test]
+    fn positions_out_of_the_video_are_rejected() {
+        for value in [
+            f32::NAN,
+            f32::INFINITY,
+            f32::NEG_INFINITY,
+            -0.01,
+            1.01,
+            f32::MAX,
+        ] {
+            assert_invalid(&encode_frame(&frame(InputEvent::MousePosition {
+                x: value,
+                y: 0.5,
+            })));
+            assert_invalid(&encode_frame(&frame(InputEvent::MousePosition {
+                x: 0.5,
+                y: value,
+            })));
+        }
+    }
+
+    #[test]
+    fn trailing_bytes_are_rejected() {
+        for event in events() {
+            let mut data = encode_frame(&frame(event));
+            data.push(0);
+            assert_invalid(&data);
+        }
+    }
+
+    #[test]
+    fn malformed_gamepad_updates_are_rejected() {
+        let update = InputEvent::Gamepad(GamepadUpdate::default());
+        // Buttons announced by the mask and two more bytes after them
+        assert_invalid(&with_payload(
+            update,
+            &[0, GAMEPAD_BUTTONS, 0x10, 0x00, 1, 2],
+        ));
+        // A field that does not exist
+        assert_invalid(&with_payload(update, &[0, 0x80]));
+        // A controller past the last one
+        assert_invalid(&with_payload(update, &[MAX_GAMEPADS, 0]));
+    }
+
+    #[test]
+    fn unknown_buttons_are_rejected() {
+        let press = InputEvent::MousePress(MouseButton::Left);
+        assert_invalid(&with_payload(press, &[5]));
+        let mut held = with_payload(
+            InputEvent::HeldKeys(HeldKeys::default()),
+            &[0; HELD_KEYS_LEN],
+        );
+        *held.last_mut().unwrap() = 0x20;
+        assert_invalid(&held);
+    }
+
+    proptest! {
+        #[test]
+        fn arbitrary_frames_do_not_panic(
+            kind in 0..=HELD_KEYS + 1,
+            payload in proptest::collection::vec(any::<u8>(), 0..40),
+        ) {
+            let mut data = vec![INPUT_PROTOCOL_VERSION, kind, 0, 0, 0, 1, 0, 0, 0, 2];
+            data.extend_from_slice(&payload);
+            if let Ok(frame) = decode_frame(&data) {
+                // Every frame accepted has a single encoding
+                prop_assert_eq!(encode_frame(&frame), data);
+            }
+        }
+    }
+}
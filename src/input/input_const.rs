@@ -1,2 +1,45 @@
+use std::time::Duration;
+
 pub const MOUSE_CHANNEL_LABEL: &str = "MOUSE";
 pub const KEYBOARD_CHANNEL_LABEL: &str = "BUTTON";
+pub const GAMEPAD_CHANNEL_LABEL: &str = "GAMEPAD";
+
+/// Pixels of movement that cross the whole video in absolute mouse mode.
+pub const POINTER_SURFACE_WIDTH: f32 = 1920.0;
+pub const POINTER_SURFACE_HEIGHT: f32 = 1080.0;
+
+/// Mouse movements sent per second by default, the ones read in between are added up.
+pub const MOUSE_RATE: u32 = 125;
+pub const MAX_MOUSE_RATE: u32 = 1000;
+/// Time between two logs of the mouse movements read and sent.
+pub const INPUT_METRICS_INTERVAL: Duration = Duration::from_secs(5);
+
+/// Time between two syncs of the keys held, which release the keys stuck on the offerer.
+pub const HELD_KEYS_SYNC_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Longest time the input reader waits for an event before checking if it must stop.
+pub const INPUT_POLL_TIMEOUT: Duration = Duration::from_millis(100);
+
+/// Version of the binary input frames, the first byte of every frame.
+pub const INPUT_PROTOCOL_VERSION: u8 = 1;
+/// Version, kind, sequence number and timestamp.
+pub const INPUT_FRAME_HEADER_LEN: usize = 10;
+
+/// Controllers forwarded per client, as many as XInput supports.
+pub const MAX_GAMEPADS: u8 = 4;
+/// Buttons of `GamepadState::buttons`, with the values of XInput.
+pub const GAMEPAD_DPAD_UP: u16 = 0x0001;
+pub const GAMEPAD_DPAD_DOWN: u16 = 0x0002;
+pub const GAMEPAD_DPAD_LEFT: u16 = 0x0004;
+pub const GAMEPAD_DPAD_RIGHT: u16 = 0x0008;
+pub const GAMEPAD_START: u16 = 0x0010;
+pub const GAMEPAD_BACK: u16 = 0x0020;
+pub const GAMEPAD_LEFT_THUMB: u16 = 0x0040;
+pub const GAMEPAD_RIGHT_THUMB: u16 = 0x0080;
+pub const GAMEPAD_LEFT_SHOULDER: u16 = 0x0100;
+pub const GAMEPAD_RIGHT_SHOULDER: u16 = 0x0200;
+pub const GAMEPAD_GUIDE: u16 = 0x0400;
+pub const GAMEPAD_A: u16 = 0x1000;
+pub const GAMEPAD_B: u16 = 0x2000;
+pub const GAMEPAD_X: u16 = 0x4000;
+pub const GAMEPAD_Y: u16 = 0x8000;
@@ -0,0 +1,126 @@
+use std::str::FromStr;
+
+use serde::de::value;
+use serde::{Deserialize, Serialize};
+
+use super::gamepad_state::GamepadUpdate;
+use super::held_keys::HeldKeys;
+use crate::utils::name_parse::from_name;
+
+/// A mouse button, numbered as sent through the data channels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    X1,
+    X2,
+}
+
+impl MouseButton {
+    /// Maps the number sent through the data channel to the button.
+    pub fn from_code(code: u8) -> Option<MouseButton> {
+        match code {
+            0 => Some(MouseButton::Left),
+            1 => Some(MouseButton::Right),
+            2 => Some(MouseButton::Middle),
+            3 => Some(MouseButton::X1),
+            4 => Some(MouseButton::X2),
+            _ => None,
+        }
+    }
+
+    /// Returns the number sent through the data channel for the button.
+    pub fn code(self) -> u8 {
+        match self {
+            MouseButton::Left => 0,
+            MouseButton::Right => 1,
+            MouseButton::Middle => 2,
+            MouseButton::X1 => 3,
+            MouseButton::X2 => 4,
+        }
+    }
+}
+
+/// An input event sent by the client to be replayed on the offerer.
+///
+/// Keys are identified by their Windows virtual key code whatever the platform, backends
+/// map them to their own codes.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub enum InputEvent {
+    KeyPress(u8),
+    KeyRelease(u8),
+    MousePress(MouseButton),
+    MouseRelease(MouseButton),
+    /// Relative movement of the mouse in pixels.
+    MouseMove {
+        dx: i32,
+        dy: i32,
+    },
+    /// Position of the mouse over the video, from 0 to 1 left to right and top to bottom.
+    MousePosition {
+        x: f32,
+        y: f32,
+    },
+    /// Wheel movement in notches, positive away from the user.
+    ScrollVertical(f32),
+    /// Wheel movement in notches, positive to the right.
+    ScrollHorizontal(f32),
+    /// Changes of the buttons and axes of a controller.
+    Gamepad(GamepadUpdate),
+    /// Every key and mouse button held on the client, to release the ones the offerer missed.
+    HeldKeys(HeldKeys),
+}
+
+impl InputEvent {
+    /// Returns the data channel the event is sent through.
+    pub fn channel(&self) -> InputChannel {
+        match self {
+            InputEvent::MouseMove { .. } | InputEvent::MousePosition { .. } => InputChannel::Mouse,
+            InputEvent::Gamepad(_) => InputChannel::Gamepad,
+            _ => InputChannel::Button,
+        }
+    }
+}
+
+/// How the movements of the mouse are sent to the offerer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MouseMode {
+    /// Movements in pixels, for games that capture the mouse such as shooters.
+    Relative,
+    /// Positions over the video, mapped to the game window by the offerer. For games
+    /// played with a visible cursor, such as menu driven or point and click games.
+    Absolute,
+}
+
+impl MouseMode {
+    /// Name of the mode, as written in the configuration.
+    pub fn name(self) -> &'static str {
+        match self {
+            MouseMode::Relative => "relative",
+            MouseMode::Absolute => "absolute",
+        }
+    }
+}
+
+impl FromStr for MouseMode {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+/// Data channel through which an event is sent.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum InputChannel {
+    /// Keys, mouse buttons and wheel, reliable.
+    Button,
+    /// Mouse movements, unordered and not retransmitted since only the latest ones matter.
+    Mouse,
+    /// Controller updates, reliable so every change is applied.
+    Gamepad,
+}
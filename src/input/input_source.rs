@@ -0,0 +1,209 @@
+use std::collections::VecDeque;
+use std::io::{Error, ErrorKind};
+use std::path::Path;
+use std::str::FromStr;
+use std::thread::sleep;
+use std::time::{Duration, Instant};
+
+use serde::de::value;
+use serde::Deserialize;
+
+use super::gamepad_source::GamepadSource;
+use super::input_event::InputEvent;
+use crate::config::node_config::InputConfig;
+use crate::utils::name_parse::from_name;
+use crate::utils::node_error::NodeError;
+
+/// Where the client reads the input sent to the offerer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum InputSourceKind {
+    /// The source of the platform the node was built for.
+    Auto,
+    Windows,
+    Linux,
+    /// Replays the events of `input.script_file`.
+    Script,
+}
+
+impl FromStr for InputSourceKind {
+    type Err = value::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        from_name(s)
+    }
+}
+
+/// Produces the input events of the client.
+///
+/// Sources are read from a blocking thread, so they may block while waiting for events.
+pub trait InputSource: Send {
+    /// Name of the source, used in the logs.
+    fn name(&self) -> &'static str;
+
+    /// Waits for the next event.
+    ///
+    /// # Arguments
+    ///
+    /// * `timeout` - Longest time to wait, so the reader can check if it must stop.
+    ///
+    /// # Returns
+    /// The event, `None` if there was none before the timeout, or an error if the source
+    /// cannot be read anymore.
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<InputEvent>, Error>;
+}
+
+/// An event of a script and when it is replayed.
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct ScriptedEvent {
+    /// Milliseconds since the source started.
+    at_ms: u64,
+    event: InputEvent,
+}
+
+/// Replays the events of a script, used to test the input path without a keyboard or mouse.
+///
+/// Scripts have one JSON object per line, in order, e.g.
+/// `{"atMs": 500, "event": {"keyPress": 65}}` or
+/// `{"atMs": 520, "event": {"mouseMove": {"dx": 10, "dy": -3}}}`. Empty lines and lines
+/// starting with `#` are skipped. Once every event is replayed the source stays idle.
+pub struct ScriptedSource {
+    events: VecDeque<ScriptedEvent>,
+    start: Option<Instant>,
+}
+
+impl ScriptedSource {
+    /// Reads a script.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - The script file.
+    ///
+    /// # Returns
+    /// The source, or an error if the file cannot be read or has an invalid line.
+    pub fn from_file(path: &Path) -> Result<ScriptedSource, Error> {
+        Self::parse(&std::fs::read_to_string(path)?)
+    }
+
+    /// Parses the contents of a script.
+    pub fn parse(script: &str) -> Result<ScriptedSource, Error> {
+        let mut events = VecDeque::new();
+        let mut last_ms = 0;
+        for (number, line) in script.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let event: ScriptedEvent = serde_json::from_str(line).map_err(|e| {
+                Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {}: {}", number + 1, e),
+                )
+            })?;
+            if event.at_ms < last_ms {
+                return Err(Error::new(
+                    ErrorKind::InvalidData,
+                    format!("line {}: events must be in order", number + 1),
+                ));
+            }
+            last_ms = event.at_ms;
+            events.push_back(event);
+        }
+        Ok(ScriptedSource {
+            events,
+            start: None,
+        })
+    }
+}
+
+impl InputSource for ScriptedSource {
+    fn name(&self) -> &'static str {
+        "script"
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<InputEvent>, Error> {
+        let start = *self.start.get_or_insert_with(Instant::now);
+        let due = match self.events.front() {
+            Some(next) => start + Duration::from_millis(next.at_ms),
+            None => {
+                sleep(timeout);
+                return Ok(None);
+            }
+        };
+
+        let wait = due.saturating_duration_since(Instant::now());
+        if wait > timeout {
+            sleep(timeout);
+            return Ok(None);
+        }
+        sleep(wait);
+        Ok(self.events.pop_front().map(|next| next.event))
+    }
+}
+
+/// Creates the input source selected in the configuration.
+///
+/// # Arguments
+///
+/// * `config` - The input configuration.
+///
+/// # Returns
+/// The source, or `NodeError::Input` if it cannot be opened.
+pub fn create_source(config: &InputConfig) -> Result<Box<dyn InputSource>, NodeError> {
+    let kind = match config.source {
+        InputSourceKind::Auto if cfg!(windows) => InputSourceKind::Windows,
+        InputSourceKind::Auto => InputSourceKind::Linux,
+        kind => kind,
+    };
+
+    let source: Result<Box<dyn InputSource>, Error> = match kind {
+        InputSourceKind::Script => {
+            let path = config.script_file.as_deref().unwrap_or_default();
+            ScriptedSource::from_file(Path::new(path))
+                .map(|source| Box::new(source) as Box<dyn InputSource>)
+        }
+        InputSourceKind::Windows => windows_source(),
+        InputSourceKind::Linux | InputSourceKind::Auto => linux_source(),
+    };
+    source.map_err(NodeError::Input)
+}
+
+/// Creates the source of the controllers.
+///
+/// # Returns
+/// The source, or `NodeError::Input` if the controllers cannot be read.
+pub fn create_gamepad_source() -> Result<Box<dyn InputSource>, NodeError> {
+    match GamepadSource::new() {
+        Ok(source) => Ok(Box::new(source)),
+        Err(e) => Err(NodeError::Input(e)),
+    }
+}
+
+#[cfg(windows)]
+fn windows_source() -> Result<Box<dyn InputSource>, Error> {
+    Ok(Box::new(super::windows_source::WindowsHookSource::new()?))
+}
+
+#[cfg(not(windows))]
+fn windows_source() -> Result<Box<dyn InputSource>, Error> {
+    Err(unsupported("windows"))
+}
+
+#[cfg(target_os = "linux")]
+fn linux_source() -> Result<Box<dyn InputSource>, Error> {
+    Ok(Box::new(super::evdev_source::EvdevSource::new()?))
+}
+
+#[cfg(not(target_os = "linux"))]
+fn linux_source() -> Result<Box<dyn InputSource>, Error> {
+    Err(unsupported("linux"))
+}
+
+#[allow(dead_code)]
+fn unsupported(source: &str) -> Error {
+    Error::new(
+        ErrorKind::Unsupported,
+        format!("The {source} input source is not available on this platform"),
+    )
+}
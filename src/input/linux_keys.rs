@@ -0,0 +1,144 @@
+use evdev::Key;
+
+use super::input_event::MouseButton;
+
+/// Maps a mouse button to its Linux button.
+pub fn linux_button(button: MouseButton) -> Key {
+    match button {
+        MouseButton::Left => Key::BTN_LEFT,
+        MouseButton::Right => Key::BTN_RIGHT,
+        MouseButton::Middle => Key::BTN_MIDDLE,
+        MouseButton::X1 => Key::BTN_SIDE,
+        MouseButton::X2 => Key::BTN_EXTRA,
+    }
+}
+
+/// Maps a Windows virtual key code to the Linux key in the same position of a US keyboard.
+pub fn linux_key(vk: u8) -> Option<Key> {
+    let key = match vk {
+        0x08 => Key::KEY_BACKSPACE,
+        0x09 => Key::KEY_TAB,
+        0x0D => Key::KEY_ENTER,
+        0x10 | 0xA0 => Key::KEY_LEFTSHIFT,
+        0xA1 => Key::KEY_RIGHTSHIFT,
+        0x11 | 0xA2 => Key::KEY_LEFTCTRL,
+        0xA3 => Key::KEY_RIGHTCTRL,
+        0x12 | 0xA4 => Key::KEY_LEFTALT,
+        0xA5 => Key::KEY_RIGHTALT,
+        0x13 => Key::KEY_PAUSE,
+        0x14 => Key::KEY_CAPSLOCK,
+        0x1B => Key::KEY_ESC,
+        0x20 => Key::KEY_SPACE,
+        0x21 => Key::KEY_PAGEUP,
+        0x22 => Key::KEY_PAGEDOWN,
+        0x23 => Key::KEY_END,
+        0x24 => Key::KEY_HOME,
+        0x25 => Key::KEY_LEFT,
+        0x26 => Key::KEY_UP,
+        0x27 => Key::KEY_RIGHT,
+        0x28 => Key::KEY_DOWN,
+        0x2C => Key::KEY_SYSRQ,
+        0x2D => Key::KEY_INSERT,
+        0x2E => Key::KEY_DELETE,
+        0x30 => Key::KEY_0,
+        0x31 => Key::KEY_1,
+        0x32 => Key::KEY_2,
+        0x33 => Key::KEY_3,
+        0x34 => Key::KEY_4,
+        0x35 => Key::KEY_5,
+        0x36 => Key::KEY_6,
+        0x37 => Key::KEY_7,
+        0x38 => Key::KEY_8,
+        0x39 => Key::KEY_9,
+        0x41 => Key::KEY_A,
+        0x42 => Key::KEY_B,
+        0x43 => Key::KEY_C,
+        0x44 => Key::KEY_D,
+        0x45 => Key::KEY_E,
+        0x46 => Key::KEY_F,
+        0x47 => Key::KEY_G,
+        0x48 => Key::KEY_H,
+        0x49 => Key::KEY_I,
+        0x4A => Key::KEY_J,
+        0x4B => Key::KEY_K,
+        0x4C => Key::KEY_L,
+        0x4D => Key::KEY_M,
+        0x4E => Key::KEY_N,
+        0x4F => Key::KEY_O,
+        0x50 => Key::KEY_P,
+        0x51 => Key::KEY_Q,
+        0x52 => Key::KEY_R,
+        0x53 => Key::KEY_S,
+        0x54 => Key::KEY_T,
+        0x55 => Key::KEY_U,
+        0x56 => Key::KEY_V,
+        0x57 => Key::KEY_W,
+        0x58 => Key::KEY_X,
+        0x59 => Key::KEY_Y,
+        0x5A => Key::KEY_Z,
+        0x5B => Key::KEY_LEFTMETA,
+        0x5C => Key::KEY_RIGHTMETA,
+        0x5D => Key::KEY_COMPOSE,
+        0x60 => Key::KEY_KP0,
+        0x61 => Key::KEY_KP1,
+        0x62 => Key::KEY_KP2,
+        0x63 => Key::KEY_KP3,
+        0x64 => Key::KEY_KP4,
+        0x65 => Key::KEY_KP5,
+        0x66 => Key::KEY_KP6,
+        0x67 => Key::KEY_KP7,
+        0x68 => Key::KEY_KP8,
+        0x69 => Key::KEY_KP9,
+        0x6A => Key::KEY_KPASTERISK,
+        0x6B => Key::KEY_KPPLUS,
+        0x6D => Key::KEY_KPMINUS,
+        0x6E => Key::KEY_KPDOT,
+        0x6F => Key::KEY_KPSLASH,
+        0x70 => Key::KEY_F1,
+        0x71 => Key::KEY_F2,
+        0x72 => Key::KEY_F3,
+        0x73 => Key::KEY_F4,
+        0x74 => Key::KEY_F5,
+        0x75 => Key::KEY_F6,
+        0x76 => Key::KEY_F7,
+        0x77 => Key::KEY_F8,
+        0x78 => Key::KEY_F9,
+        0x79 => Key::KEY_F10,
+        0x7A => Key::KEY_F11,
+        0x7B => Key::KEY_F12,
+        0x90 => Key::KEY_NUMLOCK,
+        0x91 => Key::KEY_SCROLLLOCK,
+        0xBA => Key::KEY_SEMICOLON,
+        0xBB => Key::KEY_EQUAL,
+        0xBC => Key::KEY_COMMA,
+        0xBD => Key::KEY_MINUS,
+        0xBE => Key::KEY_DOT,
+        0xBF => Key::KEY_SLASH,
+        0xC0 => Key::KEY_GRAVE,
+        0xDB => Key::KEY_LEFTBRACE,
+        0xDC => Key::KEY_BACKSLASH,
+        0xDD => Key::KEY_RIGHTBRACE,
+        0xDE => Key::KEY_APOSTROPHE,
+        0xE2 => Key::KEY_102ND,
+        _ => return None,
+    };
+    Some(key)
+}
+
+/// Maps a Linux key to the Windows virtual key code sent through the data channels.
+pub fn virtual_key(key: Key) -> Option<u8> {
+    (0..=u8::MAX).find(|vk| linux_key(*vk) == Some(key))
+}
+
+/// Maps a Linux button to the mouse button, `None` if it is not a mouse button.
+pub fn mouse_button(key: Key) -> Option<MouseButton> {
+    [
+        MouseButton::Left,
+        MouseButton::Right,
+        MouseButton::Middle,
+        MouseButton::X1,
+        MouseButton::X2,
+    ]
+    .into_iter()
+    .find(|button| linux_button(*button) == key)
+}
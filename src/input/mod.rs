@@ -1,2 +1,16 @@
+pub mod gamepad_source;
+pub mod gamepad_state;
+pub mod held_keys;
 pub mod input_capture;
+pub mod input_codec;
 pub mod input_const;
+pub mod input_event;
+pub mod input_source;
+pub mod motion_coalescer;
+
+#[cfg(target_os = "linux")]
+pub mod evdev_source;
+#[cfg(target_os = "linux")]
+pub mod linux_keys;
+#[cfg(windows)]
+pub mod windows_source;
@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use super::input_codec::InputFrame;
+use super::input_event::InputEvent;
+
+/// Merges the mouse movements read between two flushes into a single frame.
+///
+/// Sources can report movements at the polling rate of the mouse, 1000 per second or more,
+/// which would flood the mouse channel. Movements are added up and sent at a fixed rate
+/// instead, or before any other event so the offerer receives them in order.
+#[derive(Default)]
+pub struct MotionCoalescer {
+    pending: Option<InputFrame>,
+    /// Movements read and frames sent since the last report.
+    received: u32,
+    sent: u32,
+}
+
+impl MotionCoalescer {
+    /// Adds a movement to the pending frame.
+    ///
+    /// # Arguments
+    ///
+    /// * `frame` - A `MouseMove` or `MousePosition` frame.
+    ///
+    /// # Returns
+    /// The pending frame if it cannot be merged with this one, to be sent first.
+    pub fn add(&mut self, frame: InputFrame) -> Option<InputFrame> {
+        self.received += 1;
+        let pending = match self.pending.take() {
+            Some(pending) => pending,
+            None => {
+                self.pending = Some(frame);
+                return None;
+            }
+        };
+
+        let merged = match (pending.event, frame.event) {
+            (InputEvent::MouseMove { dx, dy }, InputEvent::MouseMove { dx: x, dy: y }) => {
+                Some(InputEvent::MouseMove {
+                    dx: dx.saturating_add(x),
+                    dy: dy.saturating_add(y),
+                })
+            }
+            // Only the last position matters
+            (InputEvent::MousePosition { .. }, InputEvent::MousePosition { .. }) => {
+                Some(frame.event)
+            }
+            _ => None,
+        };
+
+        match merged {
+            Some(event) => {
+                // The merged frame keeps the number and time of the last movement
+                self.pending = Some(InputFrame { event, ..frame });
+                None
+            }
+            None => {
+                self.pending = Some(frame);
+                self.sent += 1;
+                Some(pending)
+            }
+        }
+    }
+
+    /// Takes the pending frame to send it.
+    pub fn take(&mut self) -> Option<InputFrame> {
+        let pending = self.pending.take();
+        if pending.is_some() {
+            self.sent += 1;
+        }
+        pending
+    }
+
+    /// Logs how many movements were read and how many frames were sent per second, and starts
+    /// counting again.
+    ///
+    /// # Arguments
+    ///
+    /// * `elapsed` - Time since the last report.
+    pub fn report(&mut self, elapsed: Duration) {
+        if self.received > 0 {
+            let seconds = elapsed.as_secs_f64();
+            log::info!(
+                "INPUT CAPTURE | Mouse movements | {:.0}/s read, {:.0}/s sent",
+                self.received as f64 / seconds,
+                self.sent as f64 / seconds
+            );
+        }
+        self.received = 0;
+        self.sent = 0;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn frame(sequence: u32, event: InputEvent) -> InputFrame {
+        InputFrame {
+            sequence,
+            timestamp_ms: sequence * 10,
+            event,
+        }
+    }
+
+    fn moved(sequence: u32, dx: i32, dy: i32) -> InputFrame {
+        frame(sequence, InputEvent::MouseMove { dx, dy })
+    }
+
+    fn positioned(sequence: u32, x: f32, y: f32) -> InputFrame {
+        frame(sequence, InputEvent::MousePosition { x, y })
+    }
+
+    #[test]
+    fn relative_moves_add_up() {
+        let mut coalescer = MotionCoalescer::default();
+
+        assert_eq!(coalescer.add(moved(1, 3, -2)), None);
+        assert_eq!(coalescer.add(moved(2, 4, -5)), None);
+        assert_eq!(coalescer.add(moved(3, -1, 1)), None);
+
+        // The merged frame keeps the number and time of the last movement
+        assert_eq!(coalescer.take(), Some(moved(3, 6, -6)));
+        assert_eq!(coalescer.take(), None);
+    }
+
+    #[test]
+    fn relative_moves_saturate() {
+        let mut coalescer = MotionCoalescer::default();
+
+        coalescer.add(moved(1, i32::MAX - 1, i32::MIN + 1));
+        coalescer.add(moved(2, 5, -5));
+
+        assert_eq!(coalescer.take(), Some(moved(2, i32::MAX, i32::MIN)));
+    }
+
+    #[test]
+    fn only_the_last_position_is_kept() {
+        let mut coalescer = MotionCoalescer::default();
+
+        assert_eq!(coalescer.add(positioned(1, 0.1, 0.2)), None);
+        assert_eq!(coalescer.add(positioned(2, 0.5, 0.5)), None);
+        assert_eq!(coalescer.add(positioned(3, 0.9, 0.3)), None);
+
+        assert_eq!(coalescer.take(), Some(positioned(3, 0.9, 0.3)));
+    }
+
+    #[test]
+    fn moves_and_positions_are_not_merged() {
+        let mut coalescer = MotionCoalescer::default();
+
+        assert_eq!(coalescer.add(moved(1, 3, 3)), None);
+        assert_eq!(coalescer.add(positioned(2, 0.5, 0.5)), Some(moved(1, 3, 3)));
+        assert_eq!(coalescer.add(moved(3, 1, 1)), Some(positioned(2, 0.5, 0.5)));
+
+        assert_eq!(coalescer.take(), Some(moved(3, 1, 1)));
+    }
+
+    #[test]
+    fn counters_track_received_and_sent() {
+        let mut coalescer = MotionCoalescer::default();
+
+        coalescer.add(moved(1, 1, 0));
+        coalescer.add(moved(2, 1, 0));
+        coalescer.add(positioned(3, 0.5, 0.5));
+        coalescer.add(positioned(4, 0.6, 0.5));
+        coalescer.take();
+        coalescer.take();
+        assert_eq!((coalescer.received, coalescer.sent), (4, 2));
+
+        coalescer.report(Duration::from_secs(1));
+        assert_eq!((coalescer.received, coalescer.sent), (0, 0));
+    }
+}
@@ -0,0 +1,146 @@
+use std::ffi::OsStr;
+use std::io::{Error, ErrorKind};
+use std::os::windows::ffi::OsStrExt;
+use std::thread::sleep;
+use std::time::Duration;
+use std::{iter, ptr};
+
+use winapi::um::{libloaderapi, winuser};
+use winput::message_loop::{self, EventReceiver, MessageLoopError};
+use winput::{Action, Button, Vk, WheelDirection, WindowsError};
+
+use super::input_event::{InputEvent, MouseButton};
+use super::input_source::InputSource;
+
+/// Keys that are not sent to the offerer, so they keep working on the client.
+const BLOCKED_KEYS: [Vk; 2] = [Vk::LeftWin, Vk::RightWin];
+
+/// Captures the keyboard and mouse with the low level hooks of the winput message loop.
+pub struct WindowsHookSource {
+    receiver: Option<EventReceiver>,
+}
+
+impl WindowsHookSource {
+    /// Starts the message loop.
+    ///
+    /// # Returns
+    /// The source, or an error if the message loop is already active or cannot be started.
+    pub fn new() -> Result<WindowsHookSource, Error> {
+        let receiver = match message_loop::start() {
+            Ok(receiver) => receiver,
+            Err(MessageLoopError::AlreadyActive) => {
+                return Err(Error::new(
+                    ErrorKind::AlreadyExists,
+                    "The input message loop is already active",
+                ))
+            }
+            Err(MessageLoopError::OsError(e)) => {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Error starting the input message loop: {}", e),
+                ))
+            }
+        };
+        Ok(WindowsHookSource {
+            receiver: Some(receiver),
+        })
+    }
+}
+
+impl InputSource for WindowsHookSource {
+    fn name(&self) -> &'static str {
+        "windows"
+    }
+
+    fn next_event(&mut self, timeout: Duration) -> Result<Option<InputEvent>, Error> {
+        let receiver = match &self.receiver {
+            Some(receiver) => receiver,
+            None => return Err(Error::new(ErrorKind::NotConnected, "Message loop stopped")),
+        };
+
+        let event = match receiver.next_event_timeout(timeout) {
+            Some(event) => event,
+            None => return Ok(None),
+        };
+        let event = match event {
+            message_loop::Event::Keyboard { vk, action, .. } if !BLOCKED_KEYS.contains(&vk) => {
+                match action {
+                    Action::Press => Some(InputEvent::KeyPress(vk.into_u8())),
+                    Action::Release => Some(InputEvent::KeyRelease(vk.into_u8())),
+                }
+            }
+            message_loop::Event::MouseButton { action, button } => match action {
+                Action::Press => Some(InputEvent::MousePress(mouse_button(button))),
+                Action::Release => Some(InputEvent::MouseRelease(mouse_button(button))),
+            },
+            message_loop::Event::MouseWheel { delta, .. } if delta == 0.0 => None,
+            message_loop::Event::MouseWheel { delta, direction } => match direction {
+                WheelDirection::Vertical => Some(InputEvent::ScrollVertical(delta)),
+                WheelDirection::Horizontal => Some(InputEvent::ScrollHorizontal(delta)),
+            },
+            message_loop::Event::MouseMoveRelative { x, y } if x != 0 || y != 0 => {
+                Some(InputEvent::MouseMove { dx: x, dy: y })
+            }
+            _ => None,
+        };
+        Ok(event)
+    }
+}
+
+impl Drop for WindowsHookSource {
+    fn drop(&mut self) {
+        if let Some(receiver) = self.receiver.take() {
+            receiver.clear();
+            drop(receiver);
+        }
+        unregister_class_w();
+    }
+}
+
+/// Maps a winput button to the button sent through the data channel.
+fn mouse_button(button: Button) -> MouseButton {
+    match button {
+        Button::Left => MouseButton::Left,
+        Button::Right => MouseButton::Right,
+        Button::Middle => MouseButton::Middle,
+        Button::X1 => MouseButton::X1,
+        Button::X2 => MouseButton::X2,
+    }
+}
+
+// Unregister the class created by the message loop
+// This is necessary to start the message loop again
+fn unregister_class_w() {
+    let mut attempts = 0;
+
+    while attempts < 5 {
+        // Retreives the module handle of the application.
+        unsafe {
+            let h_instance = libloaderapi::GetModuleHandleW(ptr::null());
+
+            // Create the window.
+            let class_name = OsStr::new("winput_message_loop")
+                .encode_wide()
+                .chain(iter::once(0))
+                .collect::<Vec<_>>();
+
+            let class = winuser::UnregisterClassW(class_name.as_ptr(), h_instance);
+            if class != 0 {
+                return; // Unregistration successful, exit the function
+            }
+        }
+
+        // Unregistration failed, print the error and try again
+        let error = std::format!(
+            "INPUT CAPTURE | Failed to start: Os Error {}",
+            WindowsError::from_last_error()
+        );
+        println!("UNREGISTER | Attempt {} failed: {}", attempts + 1, error);
+
+        // Sleep for a short duration before the next attempt
+        sleep(Duration::from_millis(1000));
+        attempts += 1;
+    }
+
+    println!("UNREGISTER | All attempts failed");
+}
@@ -0,0 +1,12 @@
+pub mod config;
+pub mod front_connection;
+pub mod gstreamer_pipeline;
+pub mod input;
+pub mod output;
+pub mod services;
+pub mod signaling_server;
+pub mod sound;
+pub mod utils;
+pub mod video;
+pub mod webrtcommunication;
+pub mod websocketprotocol;
@@ -1,36 +1,121 @@
-pub mod front_connection;
-pub mod gstreamer_pipeline;
-pub mod input;
-pub mod output;
-pub mod services;
-pub mod sound;
-pub mod utils;
-pub mod video;
-pub mod webrtcommunication;
-pub mod websocketprotocol;
-
-use front_connection::front_protocol_const::FRONT_PORT;
-
-use crate::front_connection::front_protocol::{ClientType, FrontConnection};
-use crate::services::receiver::ReceiverSide;
-use crate::services::sender::SenderSide;
-use crate::websocketprotocol::socket_protocol::WsProtocol;
-
-use std::io::Error;
+use cgrs::config::node_config::Config;
+use cgrs::front_connection::front_command::FrontCommand;
+use cgrs::front_connection::front_event::FrontEvent;
+use cgrs::front_connection::front_protocol::FrontConnection;
+use cgrs::input::input_event::MouseMode;
+use cgrs::services::receiver::ReceiverSide;
+use cgrs::services::sender::SenderSide;
+use cgrs::services::session_context::SessionContext;
+use cgrs::services::session_state::Session;
+use cgrs::utils::node_error::NodeError;
+use cgrs::utils::supervisor::ShutdownReport;
+use cgrs::websocketprotocol::signaling_transport::SignalingTransport;
+use cgrs::websocketprotocol::socket_protocol::WsProtocol;
+
+use clap::{Parser, Subcommand};
+use std::path::PathBuf;
+
+/// Processing node of the cloud gaming platform.
+#[derive(Parser)]
+#[command(name = "cgrs", version)]
+struct Cli {
+    /// Configuration file, cgrs.toml in the working directory by default.
+    #[arg(long, global = true, value_name = "FILE")]
+    config: Option<PathBuf>,
+    /// Overrides a configuration value. Can be repeated.
+    #[arg(long = "set", global = true, value_name = "SECTION.KEY=VALUE")]
+    overrides: Vec<String>,
+    #[command(subcommand)]
+    command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Waits for the front end to start sessions. Used when no command is given.
+    Serve,
+    /// Offers the games of a user until a client plays one or Ctrl-C is pressed.
+    Offer {
+        /// User offering the games.
+        #[arg(long)]
+        user: String,
+    },
+    /// Plays a game offered by another user. Ctrl-C ends the session.
+    Play {
+        /// User playing the game.
+        #[arg(long)]
+        user: String,
+        /// User offering the game.
+        #[arg(long)]
+        host: String,
+        /// Name of the game.
+        #[arg(long)]
+        game: String,
+        /// Length of the session in minutes.
+        #[arg(long, value_parser = clap::value_parser!(u32).range(1..))]
+        minutes: u32,
+        /// `relative` or `absolute`, `input.mouse_mode` by default.
+        #[arg(long)]
+        mouse_mode: Option<MouseMode>,
+    },
+}
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() -> Result<(), NodeError> {
     env_logger::builder().format_target(false).init();
+    let cli = Cli::parse();
+
     // Initialize GStreamer
-    gstreamer::init().unwrap();
+    gstreamer::init().map_err(|e| NodeError::gstreamer("Error initializing GStreamer", e))?;
+
+    let config = Config::load(cli.config.as_deref(), &cli.overrides)?;
+
+    let command = match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => None,
+        Command::Offer { user } => Some(FrontCommand::StartOffering { username: user }),
+        Command::Play {
+            user,
+            host,
+            game,
+            minutes,
+            mouse_mode,
+        } => Some(FrontCommand::StartGame {
+            username: user,
+            offerer_username: host,
+            game_name: game,
+            minutes,
+            mouse_mode,
+        }),
+    };
+
+    match command {
+        Some(command) => {
+            // Without front end, a single session is run and its result is the exit status
+            let mut front_connection = FrontConnection::headless();
+            let mut ws = connect(&config).await?;
+            run_session(&config, &mut front_connection, &mut ws, command).await?;
+        }
+        None => serve(&config).await?,
+    }
 
-    let mut front_connection = FrontConnection::new(FRONT_PORT).await?;
+    unsafe {
+        gstreamer::deinit();
+    }
+    Ok(())
+}
+
+/// Runs the sessions requested by the front end until it closes the control channel.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the node.
+async fn serve(config: &Config) -> Result<(), NodeError> {
+    let mut front_connection = FrontConnection::new(&config.front).await?;
 
     loop {
-        let mut ws: WsProtocol = WsProtocol::ws_protocol().await?;
+        let mut ws = connect(config).await?;
         println!("Ready to start");
 
-        let client = match front_connection.waiting_to_start().await {
+        let command = match front_connection.waiting_to_start().await {
             Ok(c) => c,
             Err(_) => {
                 println!("Se termino la conexion flutter");
@@ -38,45 +123,107 @@ async fn main() -> Result<(), Error> {
             }
         };
 
-        match client.client_type {
-            ClientType::RECEIVER => {
-                let offerer_username = client
-                    .user_to_connect
-                    .expect("Missing offerer name parameter.");
-
-                let game_name = client.game_name.expect("Missign game name parameter.");
-                let minutes = client.minutes.expect("Missing parameter minutes");
-                if (ReceiverSide::init(
-                    &client.username,
-                    &offerer_username,
-                    &game_name,
-                    &minutes,
-                    &mut front_connection,
-                )
-                .await)
-                    .is_err()
-                {
-                    println!("Connection Missed. \nRestarting...");
-                    ws.close_connection().await?;
-                    continue;
-                }
-                ws.close_connection().await?;
-                continue;
-            }
-            ClientType::SENDER => {
-                if let Err(e) =
-                    SenderSide::init(&client.username, &mut ws, &mut front_connection).await
-                {
-                    println!("MAIN EXITED WITH ERROR {:?}", e);
-                    ws.close_connection().await?;
-                }
-            }
-        }
+        // Failures are reported to the front end, the node keeps waiting for sessions
+        let _ = run_session(config, &mut front_connection, &mut ws, command).await;
     }
 
     println!("Main done");
-    unsafe {
-        gstreamer::deinit();
-    }
     Ok(())
 }
+
+/// Connects to the intermediary server.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the node.
+async fn connect(config: &Config) -> Result<WsProtocol, NodeError> {
+    WsProtocol::ws_protocol(&config.signaling.url)
+        .await
+        .map_err(NodeError::Signaling)
+}
+
+/// Runs the session started by `command` and reports how it ended to the front end.
+///
+/// # Arguments
+///
+/// * `config` - The configuration of the node.
+/// * `front_connection` - The connection with the front end, or a headless one.
+/// * `ws` - The connection with the intermediary server, closed once the session ends.
+/// * `command` - A [`FrontCommand::StartOffering`] or a [`FrontCommand::StartGame`].
+///
+/// # Returns
+/// How the session stopped, or the error that ended it.
+async fn run_session(
+    config: &Config,
+    front_connection: &mut FrontConnection,
+    ws: &mut WsProtocol,
+    command: FrontCommand,
+) -> Result<ShutdownReport, NodeError> {
+    let session = Session::new();
+    front_connection.follow_session(&session);
+    let result = match command {
+        FrontCommand::StartGame {
+            username,
+            offerer_username,
+            game_name,
+            minutes,
+            mouse_mode,
+        } => {
+            let mut config = config.clone();
+            if let Some(mode) = mouse_mode {
+                config.input.mouse_mode = mode;
+            }
+            let ctx = SessionContext {
+                ws,
+                front_connection,
+                config: &config,
+                session: &session,
+            };
+            ReceiverSide::init(
+                &username,
+                &offerer_username,
+                &game_name,
+                &minutes.to_string(),
+                ctx,
+            )
+            .await
+        }
+        FrontCommand::StartOffering { username } => {
+            let ctx = SessionContext {
+                ws,
+                front_connection,
+                config,
+                session: &session,
+            };
+            SenderSide::init(&username, ctx).await
+        }
+        // The rest of the commands are handled by the control channel itself
+        _ => {
+            front_connection.release_session();
+            return Ok(ShutdownReport::default());
+        }
+    };
+
+    front_connection.release_session();
+    let events = front_connection.events();
+    match &result {
+        Ok(report) => events.send(FrontEvent::SessionEnded {
+            reason: report.reason(),
+        }),
+        Err(e) => {
+            log::error!("MAIN | Session failed [{}] | {}", e.code(), e);
+            events.send(FrontEvent::Error {
+                code: e.code(),
+                message: e.to_string(),
+            });
+            events.send(FrontEvent::SessionEnded {
+                reason: e.code().to_string(),
+            });
+        }
+    }
+    if let Err(e) = ws.close_connection().await {
+        log::warn!("MAIN | Error closing signaling connection | {}", e);
+    }
+
+    result
+}
@@ -1,21 +1,29 @@
-use super::output_const::*;
-use std::mem;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use webrtc::data_channel::data_channel_message::DataChannelMessage;
 use webrtc::data_channel::RTCDataChannel;
-use winapi::um::winuser::*;
-use winput::Button;
+
+use super::input_injector::{InputInjector, SharedInjector};
+use crate::input::held_keys::HeldKeys;
+use crate::input::input_codec::decode_frame;
+use crate::input::input_event::InputEvent;
 
 /// # ButtonController
 ///
 /// The `ButtonController` struct provides functionality for handling keyboard and mouse events
 /// via a WebRTC data channel.
-pub struct ButtonController {}
+///
+/// It keeps the keys and buttons it pressed, so the ones stuck after a lost release are
+/// released when the client syncs the keys it holds.
+pub struct ButtonController {
+    held: HeldKeys,
+}
 
 impl ButtonController {
     /// Creates a new `ButtonController`.
     pub fn new() -> ButtonController {
-        ButtonController {}
+        ButtonController {
+            held: HeldKeys::default(),
+        }
     }
 
     /// Starts the keyboard controller by registering a callback for incoming messages on the
@@ -24,117 +32,68 @@ impl ButtonController {
     /// # Arguments
     ///
     /// * `ch` - An Arc reference to the RTCDataChannel.
-    pub fn start_keyboard_controller(ch: Arc<RTCDataChannel>) {
+    /// * `injector` - Injects the events received.
+    pub fn start_keyboard_controller(ch: Arc<RTCDataChannel>, injector: SharedInjector) {
+        let controller = Arc::new(Mutex::new(ButtonController::new()));
         ch.on_message(Box::new(move |msg: DataChannelMessage| {
+            let injector = injector.clone();
+            let controller = controller.clone();
             Box::pin(async move {
-                let s = String::from_utf8_lossy(&msg.data);
-                let (action, rest) = s.split_at(1);
-
-                match action {
-                    PRESS_KEYBOARD_ACTION => {
-                        let key = match rest.parse::<u8>() {
-                            Ok(k) => k,
-                            Err(e) => {
-                                log::error!("BUTTON CONTROLLER | Error parsing u8: {}", e);
-                                return;
-                            }
-                        };
-                        send_input_key(key as i32, false);
-                    }
-                    RELEASE_KEYBOARD_ACTION => {
-                        let key = match rest.parse::<u8>() {
-                            Ok(k) => k,
-                            Err(e) => {
-                                log::error!("BUTTON CONTROLLER | Error parsing u8: {}", e);
-                                return;
-                            }
-                        };
-                        send_input_key(key as i32, true);
-                    }
-                    PRESS_MOUSE_ACTION => {
-                        let key = match rest.parse::<u8>() {
-                            Ok(k) => k,
-                            Err(e) => {
-                                log::error!("BUTTON CONTROLLER | Error parsing u8: {}", e);
-                                return;
-                            }
-                        };
-                        if let Some(button) = get_mouse_button(key) {
-                            winput::press(button);
-                        }
-                    }
-                    RELEASE_MOUSE_ACTION => {
-                        let key = match rest.parse::<u8>() {
-                            Ok(k) => k,
-                            Err(e) => {
-                                log::error!("BUTTON CONTROLLER | Error parsing u8: {}", e);
-                                return;
-                            }
-                        };
-                        if let Some(button) = get_mouse_button(key) {
-                            winput::release(button);
-                        }
-                    }
-                    SCROLL_HORIZONTAL_ACTION => {
-                        let delta = match rest.parse::<f32>() {
-                            Ok(k) => k,
-                            Err(e) => {
-                                log::error!("BUTTON CONTROLLER | Error parsing f32: {}", e);
-                                return;
-                            }
-                        };
-                        winput::Mouse::scrollh(delta);
-                    }
-                    SCROLL_VERTICAL_ACTION => {
-                        let delta = match rest.parse::<f32>() {
-                            Ok(k) => k,
-                            Err(e) => {
-                                log::error!("BUTTON CONTROLLER | Error parsing f32: {}", e);
-                                return;
-                            }
-                        };
-                        winput::Mouse::scroll(delta)
-                    }
-                    _ => {}
-                }
+                let mut controller = controller.lock().unwrap_or_else(|e| e.into_inner());
+                let mut injector = injector.lock().unwrap_or_else(|e| e.into_inner());
+                controller.handle_message(injector.as_mut(), &msg.data);
             })
         }));
     }
-}
 
-/// Maps the numeric key value to the corresponding mouse button.
-fn get_mouse_button(key: u8) -> Option<Button> {
-    match key {
-        0 => Some(Button::Left),
-        1 => Some(Button::Right),
-        2 => Some(Button::Middle),
-        3 => Some(Button::X1),
-        4 => Some(Button::X2),
-        _ => None,
+    /// Decodes a frame of the keyboard channel and injects its event.
+    ///
+    /// # Arguments
+    ///
+    /// * `injector` - Injects the event.
+    /// * `data` - The frame received, malformed frames are logged and dropped.
+    pub fn handle_message(&mut self, injector: &mut dyn InputInjector, data: &[u8]) {
+        let frame = match decode_frame(data) {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::warn!("BUTTON CONTROLLER | Rejected frame | {}", e);
+                return;
+            }
+        };
+        log::trace!(
+            "BUTTON CONTROLLER | Event {} at {} ms: {:?}",
+            frame.sequence,
+            frame.timestamp_ms,
+            frame.event
+        );
+        match frame.event {
+            InputEvent::HeldKeys(held) => self.sync(injector, &held),
+            event => self.inject(injector, event),
+        }
     }
-}
 
-/// Sends a keyboard input event.
-///
-/// # Arguments
-///
-/// * `virtual_key` - The virtual key code.
-/// * `up` - Indicates whether the key is being released (true) or pressed (false)
-pub fn send_input_key(virtual_key: i32, up: bool) {
-    unsafe {
-        let mut input = INPUT {
-            type_: INPUT_KEYBOARD,
-            u: std::mem::zeroed(),
-        };
-        *input.u.ki_mut() = KEYBDINPUT {
-            wVk: virtual_key as u16,
-            dwFlags: if up { KEYEVENTF_KEYUP } else { 0 },
-            dwExtraInfo: 1,
-            wScan: KEYEVENTF_SCANCODE as u16,
-            time: 0,
-        };
+    /// Presses and releases the keys and buttons that differ from the ones held on the client.
+    ///
+    /// # Arguments
+    ///
+    /// * `injector` - Injects the missing presses and releases.
+    /// * `held` - The keys and buttons held on the client.
+    fn sync(&mut self, injector: &mut dyn InputInjector, held: &HeldKeys) {
+        for event in self.held.reconcile(held) {
+            log::warn!(
+                "BUTTON CONTROLLER | Out of sync with the client, injecting {:?}",
+                event
+            );
+            self.inject(injector, event);
+        }
+    }
 
-        SendInput(1, &mut input, mem::size_of::<INPUT>() as i32);
+    fn inject(&mut self, injector: &mut dyn InputInjector, event: InputEvent) {
+        // Kept even if the injection fails, so a failing key is not retried on every sync
+        self.held.apply(&event);
+        if let Err(e) = injector.inject(event) {
+            log::error!("BUTTON CONTROLLER | Error injecting {:?}: {}", event, e);
+        }
     }
 }
 
@@ -143,3 +102,119 @@ impl Default for ButtonController {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::input_codec::{encode_frame, InputFrame};
+    use crate::input::input_event::MouseButton;
+    use crate::output::input_injector::RecordingInjector;
+
+    fn frame(sequence: u32, event: InputEvent) -> Vec<u8> {
+        encode_frame(&InputFrame {
+            sequence,
+            timestamp_ms: sequence.wrapping_mul(10),
+            event,
+        })
+    }
+
+    /// Feeds the events to a new controller and returns what it injected.
+    fn replay(controller: &mut ButtonController, events: &[InputEvent]) -> Vec<InputEvent> {
+        let recorder = RecordingInjector::new();
+        let mut injector = recorder.clone();
+        for (sequence, event) in events.iter().enumerate() {
+            controller.handle_message(&mut injector, &frame(sequence as u32, *event));
+        }
+        recorder.events()
+    }
+
+    fn held(keys: &[u8], buttons: &[MouseButton]) -> HeldKeys {
+        let mut held = HeldKeys::default();
+        for vk in keys {
+            held.apply(&InputEvent::KeyPress(*vk));
+        }
+        for button in buttons {
+            held.apply(&InputEvent::MousePress(*button));
+        }
+        held
+    }
+
+    #[test]
+    fn events_are_injected_in_order() {
+        let events = [
+            InputEvent::KeyPress(0x41),
+            InputEvent::MousePress(MouseButton::Left),
+            InputEvent::ScrollVertical(-1.5),
+            InputEvent::ScrollHorizontal(2.0),
+            InputEvent::MouseRelease(MouseButton::Left),
+            InputEvent::KeyRelease(0x41),
+        ];
+
+        assert_eq!(replay(&mut ButtonController::new(), &events), events);
+    }
+
+    #[test]
+    fn malformed_frames_are_dropped() {
+        let recorder = RecordingInjector::new();
+        let mut injector = recorder.clone();
+        let mut controller = ButtonController::new();
+
+        let mut wrong_version = frame(0, InputEvent::KeyPress(0x41));
+        wrong_version[0] = wrong_version[0].wrapping_add(1);
+        controller.handle_message(&mut injector, &[]);
+        controller.handle_message(&mut injector, &wrong_version);
+        controller.handle_message(&mut injector, &frame(1, InputEvent::KeyPress(0x41))[..5]);
+
+        assert!(recorder.events().is_empty());
+    }
+
+    #[test]
+    fn sync_releases_what_the_client_no_longer_holds() {
+        let mut controller = ButtonController::new();
+        let pressed = [
+            InputEvent::KeyPress(0x41),
+            InputEvent::KeyPress(0x57),
+            InputEvent::MousePress(MouseButton::Right),
+        ];
+        replay(&mut controller, &pressed);
+
+        // The release of A and of the right button were lost
+        let sync = InputEvent::HeldKeys(held(&[0x57], &[]));
+        assert_eq!(
+            replay(&mut controller, &[sync]),
+            vec![
+                InputEvent::KeyRelease(0x41),
+                InputEvent::MouseRelease(MouseButton::Right),
+            ]
+        );
+    }
+
+    #[test]
+    fn sync_presses_what_the_client_holds() {
+        let mut controller = ButtonController::new();
+
+        let sync = InputEvent::HeldKeys(held(&[0x20], &[MouseButton::X1]));
+        assert_eq!(
+            replay(&mut controller, &[sync]),
+            vec![
+                InputEvent::KeyPress(0x20),
+                InputEvent::MousePress(MouseButton::X1),
+            ]
+        );
+    }
+
+    #[test]
+    fn sync_in_agreement_injects_nothing() {
+        let mut controller = ButtonController::new();
+        replay(
+            &mut controller,
+            &[
+                InputEvent::KeyPress(0x41),
+                InputEvent::MousePress(MouseButton::Left),
+            ],
+        );
+
+        let sync = InputEvent::HeldKeys(held(&[0x41], &[MouseButton::Left]));
+        assert!(replay(&mut controller, &[sync]).is_empty());
+    }
+}
@@ -0,0 +1,68 @@
+use std::sync::Arc;
+use webrtc::data_channel::data_channel_message::DataChannelMessage;
+use webrtc::data_channel::RTCDataChannel;
+
+use super::input_injector::{InputInjector, SharedInjector};
+use crate::input::input_codec::decode_frame;
+
+/// # GamepadController
+///
+/// The `GamepadController` struct provides functionality for replaying the controllers of the
+/// client via a WebRTC data channel.
+pub struct GamepadController {}
+
+impl GamepadController {
+    /// Creates a new `GamepadController`.
+    pub fn new() -> GamepadController {
+        GamepadController {}
+    }
+
+    /// Starts the gamepad controller by registering a callback for incoming messages on the
+    /// provided WebRTC data channel.
+    ///
+    /// # Arguments
+    ///
+    /// * `ch` - An Arc reference to the RTCDataChannel.
+    /// * `injector` - Injects the events received.
+    pub fn start_gamepad_controller(ch: Arc<RTCDataChannel>, injector: SharedInjector) {
+        ch.on_message(Box::new(move |msg: DataChannelMessage| {
+            let injector = injector.clone();
+            Box::pin(async move {
+                let mut injector = injector.lock().unwrap_or_else(|e| e.into_inner());
+                GamepadController::handle_message(injector.as_mut(), &msg.data);
+            })
+        }));
+    }
+
+    /// Decodes a frame of the gamepad channel and injects its event.
+    ///
+    /// # Arguments
+    ///
+    /// * `injector` - Injects the event.
+    /// * `data` - The frame received, malformed frames are logged and dropped.
+    pub fn handle_message(injector: &mut dyn InputInjector, data: &[u8]) {
+        let frame = match decode_frame(data) {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::warn!("GAMEPAD CONTROLLER | Rejected frame | {}", e);
+                return;
+            }
+        };
+        log::trace!(
+            "GAMEPAD CONTROLLER | Event {} at {} ms: {:?}",
+            frame.sequence,
+            frame.timestamp_ms,
+            frame.event
+        );
+        let event = frame.event;
+        if let Err(e) = injector.inject(event) {
+            log::error!("GAMEPAD CONTROLLER | Error injecting {:?}: {}", event, e);
+        }
+    }
+}
+
+impl Default for GamepadController {
+    fn default() -> Self {
+        Self::new()
+    }
+}
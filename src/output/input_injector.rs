@@ -0,0 +1,93 @@
+use std::io::Error;
+use std::sync::{Arc, Mutex};
+
+use crate::input::input_event::InputEvent;
+use crate::utils::node_error::NodeError;
+
+/// Replays the input events received from the client on the offerer.
+pub trait InputInjector: Send {
+    /// Injects a single event.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event to inject.
+    fn inject(&mut self, event: InputEvent) -> Result<(), Error>;
+}
+
+/// Injector shared by the callbacks of the keyboard, mouse and gamepad data channels.
+pub type SharedInjector = Arc<Mutex<Box<dyn InputInjector>>>;
+
+/// Records the events instead of injecting them, to check what a client sends.
+///
+/// Clones share the recording, so a clone can be kept to read the events after the
+/// injector is handed to the controllers.
+#[derive(Clone, Default)]
+pub struct RecordingInjector {
+    events: Arc<Mutex<Vec<InputEvent>>>,
+}
+
+impl RecordingInjector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the events injected so far, in order.
+    pub fn events(&self) -> Vec<InputEvent> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .clone()
+    }
+}
+
+impl InputInjector for RecordingInjector {
+    fn inject(&mut self, event: InputEvent) -> Result<(), Error> {
+        self.events
+            .lock()
+            .unwrap_or_else(|e| e.into_inner())
+            .push(event);
+        Ok(())
+    }
+}
+
+/// Drops the events, used when there is no game to send them to.
+pub struct NoopInjector;
+
+impl InputInjector for NoopInjector {
+    fn inject(&mut self, _event: InputEvent) -> Result<(), Error> {
+        Ok(())
+    }
+}
+
+/// Creates the injector of the current platform.
+///
+/// # Arguments
+///
+/// * `window` - Handle of the game window, absolute mouse positions are mapped to its client
+///   area. 0 maps them to the whole screen, which is what Linux always does.
+///
+/// # Returns
+/// The injector, or `NodeError::Input` if the platform has none or it cannot be opened.
+pub fn create_injector(window: u64) -> Result<Box<dyn InputInjector>, NodeError> {
+    platform_injector(window).map_err(NodeError::Input)
+}
+
+#[cfg(windows)]
+fn platform_injector(window: u64) -> Result<Box<dyn InputInjector>, Error> {
+    Ok(Box::new(super::windows_injector::WindowsInjector::new(
+        window,
+    )))
+}
+
+#[cfg(target_os = "linux")]
+fn platform_injector(_window: u64) -> Result<Box<dyn InputInjector>, Error> {
+    Ok(Box::new(super::uinput_injector::UinputInjector::new()?))
+}
+
+#[cfg(not(any(windows, target_os = "linux")))]
+fn platform_injector(_window: u64) -> Result<Box<dyn InputInjector>, Error> {
+    Err(Error::new(
+        std::io::ErrorKind::Unsupported,
+        "Input injection is not supported on this platform",
+    ))
+}
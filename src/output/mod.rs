@@ -1,3 +1,14 @@
 pub mod button_controller;
+pub mod gamepad_controller;
+pub mod input_injector;
 pub mod mouse_controller;
 pub mod output_const;
+#[cfg(target_os = "linux")]
+pub mod uinput_gamepad;
+#[cfg(target_os = "linux")]
+pub mod uinput_injector;
+#[cfg(windows)]
+pub mod vigem_gamepad;
+pub mod virtual_gamepad;
+#[cfg(windows)]
+pub mod windows_injector;
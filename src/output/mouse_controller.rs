@@ -1,56 +1,212 @@
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 
 use webrtc::data_channel::data_channel_message::DataChannelMessage;
 use webrtc::data_channel::RTCDataChannel;
-use winput::Mouse;
+
+use super::input_injector::{InputInjector, SharedInjector};
+use super::output_const::{MOUSE_DELAY, MOUSE_SMOOTHING_STEPS, MOUSE_SMOOTHING_THRESHOLD};
+use crate::input::input_codec::{decode_frame, InputFrame};
+use crate::input::input_event::InputEvent;
 
 /// # MouseController
 ///
 /// The `MouseController` struct provides functionality for handling keyboard and mouse events
 /// via a WebRTC data channel
-pub struct MouseController {}
+///
+/// The mouse channel is unordered, so it drops the positions older than the last one.
+pub struct MouseController {
+    /// Number of the last position injected.
+    last_position: Option<u32>,
+}
 
 impl MouseController {
     /// Creates a new `MouseController`.
     pub fn new() -> MouseController {
-        MouseController {}
+        MouseController {
+            last_position: None,
+        }
     }
 
     /// Starts the mouse controller by registering a callback for incoming messages on the
     /// provided WebRTC data channel.
     ///
+    /// The callback only decodes the messages, the events are injected by a separate task so
+    /// the steps of a long movement do not hold the reading of the channel.
+    ///
     /// # Arguments
     ///
     /// * `ch` - An Arc reference to the RTCDataChannel.
-    pub fn start_mouse_controller(ch: Arc<RTCDataChannel>) {
+    /// * `injector` - Injects the movements received.
+    pub fn start_mouse_controller(ch: Arc<RTCDataChannel>, injector: SharedInjector) {
+        let (tx, rx) = unbounded_channel();
+        // Ends once the callback, and the sender it holds, is dropped with the channel
+        tokio::spawn(inject_events(rx, injector));
+
+        let controller = Arc::new(Mutex::new(MouseController::new()));
         ch.on_message(Box::new(move |msg: DataChannelMessage| {
-            Box::pin(async move {
-                let s = String::from_utf8_lossy(&msg.data);
-
-                // Split the string into two parts
-                let parts: Vec<&str> = s.split_whitespace().collect();
-
-                // Parse the parts into integers
-                let x = match parts[0].parse::<i32>() {
-                    Ok(x) => x,
-                    Err(e) => {
-                        log::error!("MOUSE CONTROLLER | Error parsing i32: {}", e);
-                        return;
-                    }
-                };
-                let y = match parts[1].parse::<i32>() {
-                    Ok(y) => y,
-                    Err(e) => {
-                        log::error!("MOUSE CONTROLLER | Error parsing i32: {}", e);
-                        return;
+            let event = controller
+                .lock()
+                .unwrap_or_else(|e| e.into_inner())
+                .decode_message(&msg.data);
+            if let Some(event) = event {
+                let _ = tx.send(event);
+            }
+            Box::pin(async {})
+        }));
+    }
+
+    /// Decodes a frame of the mouse channel and injects its movement at once.
+    ///
+    /// # Arguments
+    ///
+    /// * `injector` - Injects the movement.
+    /// * `data` - The frame received, malformed frames are logged and dropped.
+    pub fn handle_message(&mut self, injector: &mut dyn InputInjector, data: &[u8]) {
+        if let Some(event) = self.decode_message(data) {
+            MouseController::inject(injector, event);
+        }
+    }
+
+    /// Decodes a frame of the mouse channel, `None` if it is malformed or outdated.
+    fn decode_message(&mut self, data: &[u8]) -> Option<InputEvent> {
+        let frame = match decode_frame(data) {
+            Ok(frame) => frame,
+            Err(e) => {
+                log::warn!("MOUSE CONTROLLER | Rejected frame | {}", e);
+                return None;
+            }
+        };
+        log::trace!(
+            "MOUSE CONTROLLER | Event {} at {} ms: {:?}",
+            frame.sequence,
+            frame.timestamp_ms,
+            frame.event
+        );
+        if self.is_outdated(&frame) {
+            log::debug!(
+                "MOUSE CONTROLLER | Dropped position {}, received after a newer one",
+                frame.sequence
+            );
+            return None;
+        }
+        Some(frame.event)
+    }
+
+    /// Whether the frame is a position older than the last one, movements are added up so
+    /// their order does not matter.
+    fn is_outdated(&mut self, frame: &InputFrame) -> bool {
+        if !matches!(frame.event, InputEvent::MousePosition { .. }) {
+            return false;
+        }
+        if let Some(last) = self.last_position {
+            // Numbers wrap, the ones up to half the range behind are older
+            if (frame.sequence.wrapping_sub(last) as i32) <= 0 {
+                return true;
+            }
+        }
+        self.last_position = Some(frame.sequence);
+        false
+    }
+
+    fn inject(injector: &mut dyn InputInjector, event: InputEvent) {
+        if let Err(e) = injector.inject(event) {
+            log::error!("MOUSE CONTROLLER | Error injecting {:?}: {}", event, e);
+        }
+    }
+}
+
+/// Injects the events received, splitting long movements in steps `MOUSE_DELAY`
+/// milliseconds apart.
+///
+/// A movement whose steps are still being injected when the next event arrives is outdated:
+/// the rest of it is injected at once, so the cursor never lags behind the client.
+///
+/// # Arguments
+///
+/// * `rx` - The events decoded from the mouse channel, in the order they were received.
+/// * `injector` - Injects the events.
+async fn inject_events(mut rx: UnboundedReceiver<InputEvent>, injector: SharedInjector) {
+    let inject = |event| {
+        let mut injector = injector.lock().unwrap_or_else(|e| e.into_inner());
+        MouseController::inject(injector.as_mut(), event);
+    };
+    let mut next = None;
+
+    loop {
+        let event = match next.take() {
+            Some(event) => event,
+            None => match rx.recv().await {
+                Some(event) => event,
+                None => return,
+            },
+        };
+
+        let mut steps = smooth(event).into_iter();
+        while let Some(step) = steps.next() {
+            inject(step);
+            if steps.as_slice().is_empty() {
+                break;
+            }
+            tokio::select! {
+                _ = tokio::time::sleep(Duration::from_millis(MOUSE_DELAY)) => {}
+                received = rx.recv() => {
+                    let (dx, dy) = steps.fold((0, 0), |(x, y), step| match step {
+                        InputEvent::MouseMove { dx, dy } => (x + dx, y + dy),
+                        _ => (x, y),
+                    });
+                    inject(InputEvent::MouseMove { dx, dy });
+                    match received {
+                        Some(event) => next = Some(event),
+                        None => return,
                     }
-                };
+                    break;
+                }
+            }
+        }
+    }
+}
 
-                //thread::sleep(std::time::Duration::from_micros(MOUSE_DELAY));
-                Mouse::move_relative(x, y);
-            })
-        }));
+/// Splits a long movement into steps, injected `MOUSE_DELAY` milliseconds apart.
+///
+/// Movements are added up by the client between two messages, so a fast movement arrives
+/// as a jump of many pixels that games would show as a skip of the cursor.
+///
+/// # Arguments
+///
+/// * `event` - The event received.
+///
+/// # Returns
+/// The steps, or the event itself if it is short or not a relative movement.
+fn smooth(event: InputEvent) -> Vec<InputEvent> {
+    let (dx, dy) = match event {
+        InputEvent::MouseMove { dx, dy } => (dx, dy),
+        _ => return vec![event],
+    };
+    let length = dx.unsigned_abs().max(dy.unsigned_abs());
+    if length <= MOUSE_SMOOTHING_THRESHOLD {
+        return vec![event];
     }
+
+    // Every step moves to the rounded point of the line, so the steps add up to the movement
+    let steps = MOUSE_SMOOTHING_STEPS as i64;
+    let point = |i: i64| {
+        (
+            (dx as i64 * i / steps) as i32,
+            (dy as i64 * i / steps) as i32,
+        )
+    };
+    (1..=steps)
+        .map(|i| {
+            let (x, y) = point(i);
+            let (last_x, last_y) = point(i - 1);
+            InputEvent::MouseMove {
+                dx: x - last_x,
+                dy: y - last_y,
+            }
+        })
+        .collect()
 }
 
 impl Default for MouseController {
@@ -58,3 +214,160 @@ impl Default for MouseController {
         Self::new()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::input::input_codec::encode_frame;
+    use crate::output::input_injector::RecordingInjector;
+    use tokio::sync::mpsc::UnboundedSender;
+    use tokio::task::JoinHandle;
+
+    /// Starts the injection task with an injector that records the events.
+    fn start() -> (
+        UnboundedSender<InputEvent>,
+        RecordingInjector,
+        JoinHandle<()>,
+    ) {
+        let (tx, rx) = unbounded_channel();
+        let recorder = RecordingInjector::new();
+        let injector: SharedInjector = Arc::new(Mutex::new(Box::new(recorder.clone())));
+        (tx, recorder, tokio::spawn(inject_events(rx, injector)))
+    }
+
+    fn moved(dx: i32, dy: i32) -> InputEvent {
+        InputEvent::MouseMove { dx, dy }
+    }
+
+    #[tokio::test]
+    async fn long_movement_is_injected_in_steps() {
+        let (tx, recorder, task) = start();
+
+        tx.send(moved(40, -20)).unwrap();
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        drop(tx);
+        task.await.unwrap();
+
+        assert_eq!(recorder.events(), vec![moved(10, -5); 4]);
+    }
+
+    #[tokio::test]
+    async fn newer_event_injects_the_rest_of_the_movement_at_once() {
+        let (tx, recorder, task) = start();
+        let position = InputEvent::MousePosition { x: 0.5, y: 0.25 };
+
+        // Both are queued before the task runs, so the second arrives during the steps
+        tx.send(moved(40, 0)).unwrap();
+        tx.send(position).unwrap();
+        drop(tx);
+        task.await.unwrap();
+
+        assert_eq!(
+            recorder.events(),
+            vec![moved(10, 0), moved(30, 0), position]
+        );
+    }
+
+    fn frame(sequence: u32, event: InputEvent) -> Vec<u8> {
+        encode_frame(&InputFrame {
+            sequence,
+            timestamp_ms: sequence.wrapping_mul(10),
+            event,
+        })
+    }
+
+    /// Feeds the frames to a new controller and returns what it injected.
+    fn replay(frames: &[(u32, InputEvent)]) -> Vec<InputEvent> {
+        let recorder = RecordingInjector::new();
+        let mut injector = recorder.clone();
+        let mut controller = MouseController::new();
+        for (sequence, event) in frames {
+            controller.handle_message(&mut injector, &frame(*sequence, *event));
+        }
+        recorder.events()
+    }
+
+    fn position(x: f32, y: f32) -> InputEvent {
+        InputEvent::MousePosition { x, y }
+    }
+
+    #[test]
+    fn movements_and_positions_are_injected() {
+        let events = [moved(3, -4), position(0.5, 0.5), moved(-1, 0)];
+        let frames: Vec<_> = events
+            .iter()
+            .copied()
+            .enumerate()
+            .map(|(i, e)| (i as u32, e))
+            .collect();
+
+        assert_eq!(replay(&frames), events);
+    }
+
+    #[test]
+    fn positions_older_than_the_last_one_are_dropped() {
+        let injected = replay(&[
+            (1, position(0.1, 0.1)),
+            (3, position(0.3, 0.3)),
+            (2, position(0.2, 0.2)),
+            (3, position(0.3, 0.3)),
+            (4, position(0.4, 0.4)),
+        ]);
+
+        assert_eq!(
+            injected,
+            vec![position(0.1, 0.1), position(0.3, 0.3), position(0.4, 0.4)]
+        );
+    }
+
+    #[test]
+    fn movements_are_injected_in_any_order() {
+        let injected = replay(&[
+            (5, moved(1, 1)),
+            (4, moved(2, 2)),
+            (6, position(0.5, 0.5)),
+            (3, moved(3, 3)),
+        ]);
+
+        assert_eq!(
+            injected,
+            vec![moved(1, 1), moved(2, 2), position(0.5, 0.5), moved(3, 3)]
+        );
+    }
+
+    #[test]
+    fn position_numbers_wrap() {
+        let injected = replay(&[(u32::MAX, position(0.1, 0.1)), (0, position(0.2, 0.2))]);
+
+        assert_eq!(injected, vec![position(0.1, 0.1), position(0.2, 0.2)]);
+    }
+
+    #[test]
+    fn malformed_frames_are_dropped() {
+        let recorder = RecordingInjector::new();
+        let mut injector = recorder.clone();
+        let mut controller = MouseController::new();
+
+        controller.handle_message(&mut injector, &[]);
+        controller.handle_message(&mut injector, &frame(0, moved(1, 1))[..11]);
+
+        assert!(recorder.events().is_empty());
+    }
+
+    #[test]
+    fn short_movement_is_not_split() {
+        assert_eq!(smooth(moved(16, -16)), vec![moved(16, -16)]);
+    }
+
+    #[test]
+    fn steps_add_up_to_the_movement() {
+        let steps = smooth(moved(-37, 101));
+        assert_eq!(steps.len(), MOUSE_SMOOTHING_STEPS as usize);
+
+        let total = steps.iter().fold((0, 0), |(x, y), step| match step {
+            InputEvent::MouseMove { dx, dy } => (x + dx, y + dy),
+            _ => panic!("unexpected step {:?}", step),
+        });
+        assert_eq!(total, (-37, 101));
+    }
+}
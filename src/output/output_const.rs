@@ -1,8 +1,20 @@
-// Delay between movement of the mouse in microseconds
-pub const MOUSE_DELAY: u64 = 250;
-pub const PRESS_KEYBOARD_ACTION: &str = "p";
-pub const RELEASE_KEYBOARD_ACTION: &str = "r";
-pub const PRESS_MOUSE_ACTION: &str = "m";
-pub const RELEASE_MOUSE_ACTION: &str = "t";
-pub const SCROLL_VERTICAL_ACTION: &str = "v";
-pub const SCROLL_HORIZONTAL_ACTION: &str = "h";
+// Delay between the steps of a long mouse movement in milliseconds, the resolution of the
+// tokio timer
+pub const MOUSE_DELAY: u64 = 1;
+// Movements longer than this, in pixels, are injected in steps
+pub const MOUSE_SMOOTHING_THRESHOLD: u32 = 16;
+// Steps of a long movement, the ones left when the next message arrives are injected at once
+pub const MOUSE_SMOOTHING_STEPS: u32 = 4;
+// Start of the names of every virtual device created on Linux
+pub const UINPUT_NAME_PREFIX: &str = "cgrs virtual";
+// Name of the virtual device that injects the input on Linux
+pub const UINPUT_DEVICE_NAME: &str = "cgrs virtual input";
+// Name of the virtual pointer of the absolute mouse mode on Linux
+pub const UINPUT_POINTER_NAME: &str = "cgrs virtual pointer";
+// Range of the positions of the virtual pointer
+pub const POINTER_ABS_MAX: i32 = 32767;
+// Name of the virtual controllers on Linux, followed by their index
+pub const UINPUT_GAMEPAD_NAME: &str = "cgrs virtual gamepad";
+// USB ids of the Xbox 360 controller, so games apply its mapping to the virtual controllers
+pub const XBOX360_VENDOR_ID: u16 = 0x045e;
+pub const XBOX360_PRODUCT_ID: u16 = 0x028e;
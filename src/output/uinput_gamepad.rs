@@ -0,0 +1,105 @@
+use std::io::Error;
+
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{
+    AbsInfo, AbsoluteAxisType, AttributeSet, BusType, EventType, InputEvent as DeviceEvent,
+    InputId, Key, UinputAbsSetup,
+};
+
+use super::output_const::{UINPUT_GAMEPAD_NAME, XBOX360_PRODUCT_ID, XBOX360_VENDOR_ID};
+use super::virtual_gamepad::VirtualGamepad;
+use crate::input::gamepad_state::GamepadState;
+use crate::input::input_const::*;
+
+/// Buttons of the state and their Linux button, with the codes of the xpad driver.
+const BUTTONS: [(u16, Key); 11] = [
+    (GAMEPAD_A, Key::BTN_SOUTH),
+    (GAMEPAD_B, Key::BTN_EAST),
+    // xpad reports X and Y as BTN_X and BTN_Y, the codes of north and west
+    (GAMEPAD_X, Key::BTN_NORTH),
+    (GAMEPAD_Y, Key::BTN_WEST),
+    (GAMEPAD_LEFT_SHOULDER, Key::BTN_TL),
+    (GAMEPAD_RIGHT_SHOULDER, Key::BTN_TR),
+    (GAMEPAD_BACK, Key::BTN_SELECT),
+    (GAMEPAD_START, Key::BTN_START),
+    (GAMEPAD_GUIDE, Key::BTN_MODE),
+    (GAMEPAD_LEFT_THUMB, Key::BTN_THUMBL),
+    (GAMEPAD_RIGHT_THUMB, Key::BTN_THUMBR),
+];
+
+/// A virtual controller created with uinput, seen by games as an Xbox 360 controller.
+///
+/// Requires write access to `/dev/uinput`.
+pub struct UinputGamepad {
+    device: VirtualDevice,
+}
+
+impl UinputGamepad {
+    /// Creates the virtual device.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Index of the controller on the client.
+    pub fn new(pad: u8) -> Result<UinputGamepad, Error> {
+        let keys: AttributeSet<Key> = BUTTONS.iter().map(|(_, key)| *key).collect();
+        let stick = |axis| UinputAbsSetup::new(axis, AbsInfo::new(0, -32768, 32767, 16, 128, 0));
+        let trigger = |axis| UinputAbsSetup::new(axis, AbsInfo::new(0, 0, 255, 0, 0, 0));
+        let hat = |axis| UinputAbsSetup::new(axis, AbsInfo::new(0, -1, 1, 0, 0, 0));
+
+        let device = VirtualDeviceBuilder::new()?
+            .name(&format!("{} {}", UINPUT_GAMEPAD_NAME, pad))
+            .input_id(InputId::new(
+                BusType::BUS_USB,
+                XBOX360_VENDOR_ID,
+                XBOX360_PRODUCT_ID,
+                1,
+            ))
+            .with_keys(&keys)?
+            .with_absolute_axis(&stick(AbsoluteAxisType::ABS_X))?
+            .with_absolute_axis(&stick(AbsoluteAxisType::ABS_Y))?
+            .with_absolute_axis(&stick(AbsoluteAxisType::ABS_RX))?
+            .with_absolute_axis(&stick(AbsoluteAxisType::ABS_RY))?
+            .with_absolute_axis(&trigger(AbsoluteAxisType::ABS_Z))?
+            .with_absolute_axis(&trigger(AbsoluteAxisType::ABS_RZ))?
+            .with_absolute_axis(&hat(AbsoluteAxisType::ABS_HAT0X))?
+            .with_absolute_axis(&hat(AbsoluteAxisType::ABS_HAT0Y))?
+            .build()?;
+
+        Ok(UinputGamepad { device })
+    }
+}
+
+impl VirtualGamepad for UinputGamepad {
+    fn update(&mut self, state: &GamepadState) -> Result<(), Error> {
+        let pressed = |button: u16| (state.buttons & button != 0) as i32;
+        // Linux sticks are positive down, XInput ones up
+        let invert = |value: i16| (-(value as i32)).min(i16::MAX as i32);
+
+        let mut events: Vec<DeviceEvent> = BUTTONS
+            .iter()
+            .map(|(button, key)| DeviceEvent::new(EventType::KEY, key.code(), pressed(*button)))
+            .collect();
+        // The kernel drops the values that did not change
+        events.extend(
+            [
+                (AbsoluteAxisType::ABS_X, state.thumb_lx as i32),
+                (AbsoluteAxisType::ABS_Y, invert(state.thumb_ly)),
+                (AbsoluteAxisType::ABS_RX, state.thumb_rx as i32),
+                (AbsoluteAxisType::ABS_RY, invert(state.thumb_ry)),
+                (AbsoluteAxisType::ABS_Z, state.left_trigger as i32),
+                (AbsoluteAxisType::ABS_RZ, state.right_trigger as i32),
+                (
+                    AbsoluteAxisType::ABS_HAT0X,
+                    pressed(GAMEPAD_DPAD_RIGHT) - pressed(GAMEPAD_DPAD_LEFT),
+                ),
+                (
+                    AbsoluteAxisType::ABS_HAT0Y,
+                    pressed(GAMEPAD_DPAD_DOWN) - pressed(GAMEPAD_DPAD_UP),
+                ),
+            ]
+            .into_iter()
+            .map(|(axis, value)| DeviceEvent::new(EventType::ABSOLUTE, axis.0, value)),
+        );
+        self.device.emit(&events)
+    }
+}
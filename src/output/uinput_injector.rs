@@ -0,0 +1,152 @@
+use std::io::Error;
+
+use evdev::uinput::{VirtualDevice, VirtualDeviceBuilder};
+use evdev::{
+    AbsInfo, AbsoluteAxisType, AttributeSet, EventType, InputEvent as DeviceEvent, Key,
+    RelativeAxisType, UinputAbsSetup,
+};
+
+use super::input_injector::InputInjector;
+use super::output_const::{POINTER_ABS_MAX, UINPUT_DEVICE_NAME, UINPUT_POINTER_NAME};
+use super::uinput_gamepad::UinputGamepad;
+use super::virtual_gamepad::VirtualGamepads;
+use crate::input::input_event::{InputEvent, MouseButton};
+use crate::input::linux_keys::{linux_button, linux_key};
+
+/// Injects the events through a virtual keyboard and mouse created with uinput, and a
+/// virtual controller for every controller of the client.
+///
+/// Requires write access to `/dev/uinput`.
+pub struct UinputInjector {
+    device: VirtualDevice,
+    /// Fractions of a wheel notch not sent yet, vertical and horizontal.
+    scroll: (f32, f32),
+    /// Absolute pointer, created when the first position arrives.
+    pointer: Option<VirtualDevice>,
+    gamepads: VirtualGamepads<UinputGamepad>,
+}
+
+impl UinputInjector {
+    /// Creates the virtual device.
+    pub fn new() -> Result<UinputInjector, Error> {
+        let mut keys: AttributeSet<Key> = (0..=u8::MAX).filter_map(linux_key).collect();
+        for button in [
+            MouseButton::Left,
+            MouseButton::Right,
+            MouseButton::Middle,
+            MouseButton::X1,
+            MouseButton::X2,
+        ] {
+            keys.insert(linux_button(button));
+        }
+        let axes: AttributeSet<RelativeAxisType> = [
+            RelativeAxisType::REL_X,
+            RelativeAxisType::REL_Y,
+            RelativeAxisType::REL_WHEEL,
+            RelativeAxisType::REL_HWHEEL,
+        ]
+        .into_iter()
+        .collect();
+
+        let device = VirtualDeviceBuilder::new()?
+            .name(UINPUT_DEVICE_NAME)
+            .with_keys(&keys)?
+            .with_relative_axes(&axes)?
+            .build()?;
+
+        Ok(UinputInjector {
+            device,
+            scroll: (0.0, 0.0),
+            pointer: None,
+            gamepads: VirtualGamepads::new(UinputGamepad::new),
+        })
+    }
+
+    fn key(&mut self, key: Key, pressed: bool) -> Result<(), Error> {
+        self.device
+            .emit(&[DeviceEvent::new(EventType::KEY, key.code(), pressed as i32)])
+    }
+
+    /// Moves the absolute pointer, whose range is mapped by the display server to the screen.
+    fn position(&mut self, x: f32, y: f32) -> Result<(), Error> {
+        if self.pointer.is_none() {
+            self.pointer = Some(create_pointer()?);
+        }
+        let pointer = self.pointer.as_mut().expect("created above");
+        let axis = |axis: AbsoluteAxisType, value: f32| {
+            DeviceEvent::new(
+                EventType::ABSOLUTE,
+                axis.0,
+                (value * POINTER_ABS_MAX as f32).round() as i32,
+            )
+        };
+        pointer.emit(&[
+            axis(AbsoluteAxisType::ABS_X, x),
+            axis(AbsoluteAxisType::ABS_Y, y),
+        ])
+    }
+
+    fn relative(&mut self, events: &[(RelativeAxisType, i32)]) -> Result<(), Error> {
+        let events: Vec<DeviceEvent> = events
+            .iter()
+            .filter(|(_, value)| *value != 0)
+            .map(|(axis, value)| DeviceEvent::new(EventType::RELATIVE, axis.0, *value))
+            .collect();
+        if events.is_empty() {
+            return Ok(());
+        }
+        self.device.emit(&events)
+    }
+}
+
+impl InputInjector for UinputInjector {
+    fn inject(&mut self, event: InputEvent) -> Result<(), Error> {
+        match event {
+            InputEvent::KeyPress(vk) | InputEvent::KeyRelease(vk) => match linux_key(vk) {
+                Some(key) => self.key(key, matches!(event, InputEvent::KeyPress(_))),
+                None => {
+                    log::debug!("UINPUT | Virtual key {} has no Linux key", vk);
+                    Ok(())
+                }
+            },
+            InputEvent::MousePress(button) => self.key(linux_button(button), true),
+            InputEvent::MouseRelease(button) => self.key(linux_button(button), false),
+            InputEvent::MouseMove { dx, dy } => {
+                self.relative(&[(RelativeAxisType::REL_X, dx), (RelativeAxisType::REL_Y, dy)])
+            }
+            InputEvent::ScrollVertical(delta) => {
+                let notches = take_notches(&mut self.scroll.0, delta);
+                self.relative(&[(RelativeAxisType::REL_WHEEL, notches)])
+            }
+            InputEvent::ScrollHorizontal(delta) => {
+                let notches = take_notches(&mut self.scroll.1, delta);
+                self.relative(&[(RelativeAxisType::REL_HWHEEL, notches)])
+            }
+            InputEvent::MousePosition { x, y } => self.position(x, y),
+            InputEvent::Gamepad(update) => self.gamepads.apply(&update),
+            // Reconciled by the button controller
+            InputEvent::HeldKeys(_) => Ok(()),
+        }
+    }
+}
+
+/// Creates a pointer that reports absolute positions, like the tablets of virtual machines.
+fn create_pointer() -> Result<VirtualDevice, Error> {
+    // Without a button it would not be taken as a pointer, clicks use the main device
+    let keys: AttributeSet<Key> = [Key::BTN_LEFT].into_iter().collect();
+    let axis = |axis| UinputAbsSetup::new(axis, AbsInfo::new(0, 0, POINTER_ABS_MAX, 0, 0, 0));
+    VirtualDeviceBuilder::new()?
+        .name(UINPUT_POINTER_NAME)
+        .with_keys(&keys)?
+        .with_absolute_axis(&axis(AbsoluteAxisType::ABS_X))?
+        .with_absolute_axis(&axis(AbsoluteAxisType::ABS_Y))?
+        .build()
+}
+
+/// Adds `delta` to the pending fraction of a notch and takes the whole notches.
+fn take_notches(pending: &mut f32, delta: f32) -> i32 {
+    *pending += delta;
+    let notches = pending.trunc();
+    *pending -= notches;
+    notches as i32
+}
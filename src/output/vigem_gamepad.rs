@@ -0,0 +1,49 @@
+use std::io::{Error, ErrorKind};
+
+use vigem_client::{Client, TargetId, XButtons, XGamepad, Xbox360Wired};
+
+use super::virtual_gamepad::VirtualGamepad;
+use crate::input::gamepad_state::GamepadState;
+
+/// A virtual Xbox 360 controller created through the ViGEmBus driver.
+///
+/// Requires ViGEmBus to be installed on the offerer. The controller is unplugged when dropped.
+pub struct VigemGamepad {
+    target: Xbox360Wired<Client>,
+}
+
+impl VigemGamepad {
+    /// Plugs in a new controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `pad` - Index of the controller on the client.
+    pub fn new(pad: u8) -> Result<VigemGamepad, Error> {
+        let client = Client::connect().map_err(vigem_error)?;
+        let mut target = Xbox360Wired::new(client, TargetId::XBOX360_WIRED);
+        target.plugin().map_err(vigem_error)?;
+        target.wait_ready().map_err(vigem_error)?;
+        log::debug!("VIGEM | Controller {} plugged in", pad);
+        Ok(VigemGamepad { target })
+    }
+}
+
+impl VirtualGamepad for VigemGamepad {
+    fn update(&mut self, state: &GamepadState) -> Result<(), Error> {
+        // The layout of the state is the one of XInput, so it maps field by field
+        let gamepad = XGamepad {
+            buttons: XButtons { raw: state.buttons },
+            left_trigger: state.left_trigger,
+            right_trigger: state.right_trigger,
+            thumb_lx: state.thumb_lx,
+            thumb_ly: state.thumb_ly,
+            thumb_rx: state.thumb_rx,
+            thumb_ry: state.thumb_ry,
+        };
+        self.target.update(&gamepad).map_err(vigem_error)
+    }
+}
+
+fn vigem_error(e: vigem_client::Error) -> Error {
+    Error::new(ErrorKind::Other, format!("ViGEm error: {}", e))
+}
@@ -0,0 +1,67 @@
+use std::collections::HashMap;
+use std::io::Error;
+
+use crate::input::gamepad_state::{GamepadState, GamepadUpdate};
+
+/// A controller created on the offerer to replay one of the client.
+pub trait VirtualGamepad: Send {
+    /// Sets the buttons and axes of the controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `state` - The whole state of the controller.
+    fn update(&mut self, state: &GamepadState) -> Result<(), Error>;
+}
+
+/// The virtual controllers of a client, each one created when its first update arrives.
+pub struct VirtualGamepads<P: VirtualGamepad> {
+    pads: HashMap<u8, (GamepadState, P)>,
+    create: fn(u8) -> Result<P, Error>,
+    /// Set once a controller cannot be created, so the error is reported only once.
+    disabled: bool,
+}
+
+impl<P: VirtualGamepad> VirtualGamepads<P> {
+    /// Creates an empty set of controllers.
+    ///
+    /// # Arguments
+    ///
+    /// * `create` - Creates the controller of the given index.
+    pub fn new(create: fn(u8) -> Result<P, Error>) -> Self {
+        Self {
+            pads: HashMap::new(),
+            create,
+            disabled: false,
+        }
+    }
+
+    /// Applies an update to its controller.
+    ///
+    /// # Arguments
+    ///
+    /// * `update` - The changes of the controller.
+    ///
+    /// # Returns
+    /// An error if the controller cannot be created or updated.
+    pub fn apply(&mut self, update: &GamepadUpdate) -> Result<(), Error> {
+        if self.disabled {
+            return Ok(());
+        }
+        if !self.pads.contains_key(&update.pad) {
+            match (self.create)(update.pad) {
+                Ok(pad) => {
+                    log::info!("GAMEPAD | Virtual controller {} created", update.pad);
+                    self.pads.insert(update.pad, (GamepadState::default(), pad));
+                }
+                Err(e) => {
+                    self.disabled = true;
+                    return Err(e);
+                }
+            }
+        }
+
+        let (state, pad) = self.pads.get_mut(&update.pad).expect("inserted above");
+        state.apply(update);
+        pad.update(state)
+    }
+}
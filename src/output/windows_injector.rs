@@ -0,0 +1,126 @@
+use std::io::Error;
+use std::mem;
+
+use winapi::shared::windef::{HWND, POINT, RECT};
+use winapi::um::winuser::*;
+use winput::{Button, Mouse};
+
+use super::input_injector::InputInjector;
+use super::vigem_gamepad::VigemGamepad;
+use super::virtual_gamepad::VirtualGamepads;
+use crate::input::input_event::{InputEvent, MouseButton};
+
+/// Injects the events with `SendInput`, and the controllers through ViGEm.
+pub struct WindowsInjector {
+    /// Handle of the game window, absolute positions are mapped to its client area.
+    window: u64,
+    gamepads: VirtualGamepads<VigemGamepad>,
+}
+
+impl WindowsInjector {
+    /// Creates the injector.
+    ///
+    /// # Arguments
+    ///
+    /// * `window` - Handle of the game window, 0 to map absolute positions to the screen.
+    pub fn new(window: u64) -> WindowsInjector {
+        WindowsInjector {
+            window,
+            gamepads: VirtualGamepads::new(VigemGamepad::new),
+        }
+    }
+}
+
+impl InputInjector for WindowsInjector {
+    fn inject(&mut self, event: InputEvent) -> Result<(), Error> {
+        match event {
+            InputEvent::KeyPress(key) => send_input_key(key as i32, false),
+            InputEvent::KeyRelease(key) => send_input_key(key as i32, true),
+            InputEvent::MousePress(button) => winput::press(winput_button(button)),
+            InputEvent::MouseRelease(button) => winput::release(winput_button(button)),
+            InputEvent::MouseMove { dx, dy } => Mouse::move_relative(dx, dy),
+            InputEvent::MousePosition { x, y } => {
+                let area = client_area(self.window);
+                let x = area.left + (x * (area.right - area.left - 1) as f32).round() as i32;
+                let y = area.top + (y * (area.bottom - area.top - 1) as f32).round() as i32;
+                unsafe {
+                    SetCursorPos(x, y);
+                }
+            }
+            InputEvent::ScrollVertical(delta) => Mouse::scroll(delta),
+            InputEvent::ScrollHorizontal(delta) => Mouse::scrollh(delta),
+            InputEvent::Gamepad(update) => return self.gamepads.apply(&update),
+            // Reconciled by the button controller
+            InputEvent::HeldKeys(_) => {}
+        }
+        Ok(())
+    }
+}
+
+/// Returns the client area of the window in screen coordinates, or the primary screen if
+/// there is no window or it was closed.
+///
+/// # Arguments
+///
+/// * `window` - Handle of the window, or 0.
+fn client_area(window: u64) -> RECT {
+    unsafe {
+        let hwnd = window as HWND;
+        let mut client: RECT = mem::zeroed();
+        let mut origin = POINT { x: 0, y: 0 };
+        if window != 0
+            && GetClientRect(hwnd, &mut client) != 0
+            && ClientToScreen(hwnd, &mut origin) != 0
+            && client.right > 0
+            && client.bottom > 0
+        {
+            return RECT {
+                left: origin.x,
+                top: origin.y,
+                right: origin.x + client.right,
+                bottom: origin.y + client.bottom,
+            };
+        }
+        RECT {
+            left: 0,
+            top: 0,
+            right: GetSystemMetrics(SM_CXSCREEN),
+            bottom: GetSystemMetrics(SM_CYSCREEN),
+        }
+    }
+}
+
+/// Maps the mouse button to the one of `winput`.
+fn winput_button(button: MouseButton) -> Button {
+    match button {
+        MouseButton::Left => Button::Left,
+        MouseButton::Right => Button::Right,
+        MouseButton::Middle => Button::Middle,
+        MouseButton::X1 => Button::X1,
+        MouseButton::X2 => Button::X2,
+    }
+}
+
+/// Sends a keyboard input event.
+///
+/// # Arguments
+///
+/// * `virtual_key` - The virtual key code.
+/// * `up` - Indicates whether the key is being released (true) or pressed (false)
+pub fn send_input_key(virtual_key: i32, up: bool) {
+    unsafe {
+        let mut input = INPUT {
+            type_: INPUT_KEYBOARD,
+            u: std::mem::zeroed(),
+        };
+        *input.u.ki_mut() = KEYBDINPUT {
+            wVk: virtual_key as u16,
+            dwFlags: if up { KEYEVENTF_KEYUP } else { 0 },
+            dwExtraInfo: 1,
+            wScan: KEYEVENTF_SCANCODE as u16,
+            time: 0,
+        };
+
+        SendInput(1, &mut input, mem::size_of::<INPUT>() as i32);
+    }
+}
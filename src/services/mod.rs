@@ -1,3 +1,5 @@
 pub mod receiver;
 pub mod sender;
 pub mod sender_utils;
+pub mod session_context;
+pub mod session_state;
@@ -1,14 +1,18 @@
 use std::io::{Error, ErrorKind};
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::{mpsc, Arc};
+use std::time::Duration;
 
-use crate::front_connection::front_protocol::FrontConnection;
+use crate::config::node_config::ErrorTrackerConfig;
 use crate::gstreamer_pipeline::av_player::start_player;
+use crate::gstreamer_pipeline::player_backend::create_backend;
 use crate::input::input_capture::InputCapture;
+use crate::input::input_source::{create_gamepad_source, create_source};
+use crate::services::session_context::SessionContext;
+use crate::services::session_state::{Session, SessionEvent};
 
 use crate::utils::error_tracker::ErrorTracker;
-use crate::utils::shutdown;
-use crate::utils::webrtc_const::{READ_TRACK_LIMIT, READ_TRACK_THRESHOLD};
-use tokio::sync::Barrier;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 use webrtc::api::media_engine::MIME_TYPE_H264;
 use webrtc::data_channel::RTCDataChannel;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
@@ -19,11 +23,15 @@ use webrtc::{
 };
 
 use crate::utils::latency_const::LATENCY_CHANNEL_LABEL;
-use crate::utils::shutdown::Shutdown;
-use crate::utils::webrtc_const::STUN_ADRESS;
+use crate::utils::node_error::NodeError;
+use crate::utils::supervisor::{ShutdownReport, Supervisor, Task};
+use crate::utils::supervisor_const::SHUTDOWN_DEADLINE;
 use crate::webrtcommunication::communication::{encode, Communication};
 use crate::webrtcommunication::latency::Latency;
-use crate::websocketprotocol::socket_protocol::WsProtocol;
+use crate::webrtcommunication::reconnect::{answer_offer, ConnectionEvent, Reconnect};
+use crate::webrtcommunication::stats::report_stream_stats;
+use crate::webrtcommunication::trickle::exchange_candidates;
+use crate::websocketprotocol::signaling_transport::SessionSignal;
 
 pub struct ReceiverSide {}
 
@@ -33,46 +41,127 @@ impl ReceiverSide {
         offerer_name: &str,
         game_name: &str,
         minutes: &str,
-        front_connection: &mut FrontConnection,
-    ) -> Result<(), Error> {
-        // Initialize Log:
-        let mut ws: WsProtocol = WsProtocol::ws_protocol().await?;
-        ws.init_client(client_name, offerer_name, game_name, minutes)
-            .await?;
+        ctx: SessionContext<'_>,
+    ) -> Result<ShutdownReport, NodeError> {
+        ctx.ws
+            .init_client(
+                client_name,
+                offerer_name,
+                game_name,
+                minutes,
+                ctx.config.webrtc.trickle_ice,
+            )
+            .await
+            .map_err(NodeError::Signaling)?;
+        let session = ctx.session;
+        let _ = session.handle(SessionEvent::Start);
+
+        let supervisor = Supervisor::new();
+        let mut peer_connection = None;
+
+        let outcome = Self::run(
+            client_name,
+            offerer_name,
+            ctx,
+            &supervisor,
+            &mut peer_connection,
+        )
+        .await;
+
+        // Errors that were not reported yet still stop every task
+        if let Err(e) = &outcome {
+            if supervisor.failure().is_none() {
+                supervisor.fail("Receiver", &e.to_string());
+            }
+        }
+
+        let _ = session.handle(SessionEvent::Stop);
+        let report = supervisor
+            .shutdown(Duration::from_secs(SHUTDOWN_DEADLINE))
+            .await;
+        log::info!("RECEIVER | Session {}", report);
+        let _ = session.handle(SessionEvent::Drained);
+
+        let closed = match peer_connection {
+            Some(pc) => pc.close().await,
+            None => Ok(()),
+        };
+        outcome?;
+        if let Err(e) = closed {
+            return Err(NodeError::WebRtc("Error closing peer connection", e));
+        }
 
-        let shutdown = Shutdown::new();
+        Ok(report)
+    }
 
-        let comunication = Communication::new(STUN_ADRESS.to_owned()).await?;
+    /// Runs a session until it ends or fails. Cleaning up is left to `init`, so returning
+    /// early at any point does not leave tasks or the peer connection behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Handles of the node the session runs with.
+    /// * `supervisor` - Owns the tasks of the session.
+    /// * `pc` - Set to the peer connection of the session once it is created.
+    async fn run(
+        client_name: &str,
+        offerer_name: &str,
+        ctx: SessionContext<'_>,
+        supervisor: &Supervisor,
+        pc: &mut Option<Arc<RTCPeerConnection>>,
+    ) -> Result<(), NodeError> {
+        let SessionContext {
+            ws,
+            front_connection,
+            config,
+            session,
+        } = ctx;
+        let comunication = Communication::new(&config.webrtc).await?;
 
         let peer_connection = comunication.get_peer();
+        *pc = Some(peer_connection.clone());
 
-        let barrier = Arc::new(Barrier::new(4));
-        let barrier_clone = barrier.clone();
         // Start mosue and keyboard capture
         let pc_cpy = peer_connection.clone();
+        let mut session_input = session.subscribe();
+        let input_config = config.input.clone();
 
-        let mut shutdown_cpya = shutdown.clone();
-        let shutdown_cpy1 = shutdown.clone();
-
-        tokio::spawn(async move {
-            match InputCapture::new(pc_cpy, &mut shutdown_cpya).await {
+        supervisor.spawn("Input capture", move |task| async move {
+            match InputCapture::new(pc_cpy, task.clone()).await {
                 Ok(mut input_capture) => {
-                    barrier_clone.wait().await;
-                    match input_capture.start().await {
+                    tokio::select! {
+                        _ = task.cancelled() => return,
+                        streaming = session_input.wait_streaming() => {
+                            if !streaming {
+                                return;
+                            }
+                        }
+                    }
+                    let mut sources = match create_source(&input_config) {
+                        Ok(source) => vec![source],
+                        Err(e) => {
+                            log::error!("Failed to open the input source: {}", e);
+                            task.fail("Open input source");
+                            return;
+                        }
+                    };
+                    // Controllers are optional, the game can still be played without them
+                    if input_config.gamepad {
+                        match create_gamepad_source() {
+                            Ok(source) => sources.push(source),
+                            Err(e) => log::warn!("Gamepads disabled: {}", e),
+                        }
+                    }
+                    match input_capture.start(sources, &input_config).await {
                         Ok(_) => {}
                         Err(e) => {
                             log::error!("Failed to start InputCapture: {}", e);
-                            shutdown_cpy1
-                                .notify_error(false, "Start input capture")
-                                .await;
+                            task.fail("Start input capture");
                         }
                     }
                 }
                 Err(e) => {
                     log::error!("Failed to create InputCapture: {}", e);
-                    shutdown_cpy1
-                        .notify_error(false, "Create Input Capture")
-                        .await;
+                    task.fail("Create Input Capture");
                 }
             }
         });
@@ -82,16 +171,10 @@ impl ReceiverSide {
 
         let (tx_audio, rx_audio): (FrameChannel, mpsc::Receiver<(bool, Vec<u8>)>) = mpsc::channel();
 
-        let mut shutdown_audio = shutdown.clone();
-        let barrier_clone_player = barrier.clone();
-        tokio::spawn(async move {
-            start_player(
-                rx_video,
-                rx_audio,
-                &mut shutdown_audio,
-                barrier_clone_player,
-            )
-            .await;
+        let session_player = session.subscribe();
+        let backend = create_backend(&config.player);
+        supervisor.spawn("Player", move |task| {
+            start_player(rx_video, rx_audio, task, session_player, backend)
         });
 
         // Set a handler for when a new remote track starts, this handler saves buffers to disk as
@@ -101,97 +184,152 @@ impl ReceiverSide {
             &peer_connection,
             tx_audio,
             tx_video,
-            shutdown.clone(),
-            barrier.clone(),
+            supervisor.clone(),
+            session.clone(),
+            config.error_tracker.clone(),
         );
 
-        channel_handler(&peer_connection, shutdown.clone());
+        channel_handler(&peer_connection, supervisor.clone());
 
         // Allow us to receive 1 audio track
-        if peer_connection
+        if let Err(e) = peer_connection
             .add_transceiver_from_kind(RTPCodecType::Audio, None)
             .await
-            .is_err()
         {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error adding audio transceiver",
-            ));
+            return Err(NodeError::WebRtc("Error adding audio transceiver", e));
         }
 
-        add_peer_connection_handler(&peer_connection, shutdown.clone());
+        let (connection_tx, mut connection_rx) = unbounded_channel();
+        add_peer_connection_handler(
+            &peer_connection,
+            supervisor.clone(),
+            session.clone(),
+            connection_tx,
+        );
+
+        let pc_stats = peer_connection.clone();
+        let session_stats = session.subscribe();
+        let events = front_connection.events();
+        supervisor.spawn("Stream stats", move |task| {
+            report_stream_stats(pc_stats, session_stats, events, task)
+        });
 
         // Set the remote SessionDescription: ACA METER USER INPUT Y PEGAR EL SDP
         // Wait for the offer to be pasted
 
-        let sdp = ws.wait_for_offerer_sdp().await?;
+        let (sdp, offerer_trickle) = ws
+            .wait_for_offerer_sdp()
+            .await
+            .map_err(NodeError::Signaling)?;
         comunication.set_sdp(sdp).await?;
         let peer_connection = comunication.get_peer();
 
+        // Trickle candidates only if the offerer is trickling its own
+        let trickle = config.webrtc.trickle_ice && offerer_trickle;
+        let mut local_candidates = if trickle {
+            Some(comunication.local_candidates())
+        } else {
+            None
+        };
+
         // Create an answer
         let answer = match peer_connection.create_answer(None).await {
             Ok(answer) => answer,
-            Err(_) => return Err(Error::new(ErrorKind::Other, "Error creating answer")),
+            Err(e) => return Err(NodeError::WebRtc("Error creating answer", e)),
         };
 
         // Create channel that is blocked until ICE Gathering is complete
         let mut gather_complete = peer_connection.gathering_complete_promise().await;
 
         // Sets the LocalDescription, and starts our UDP listeners
-        if peer_connection.set_local_description(answer).await.is_err() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error setting local description",
-            ));
+        if let Err(e) = peer_connection.set_local_description(answer).await {
+            return Err(NodeError::WebRtc("Error setting local description", e));
         }
 
-        // Block until ICE Gathering is complete, disabling trickle ICE
-        // we do this because we only can exchange one signaling message
-        // in a production application you should exchange ICE Candidates via OnICECandidate
-        let _ = gather_complete.recv().await;
+        // Without trickle ICE, block until ICE Gathering is complete
+        // so every candidate is sent in the answer
+        if !trickle {
+            let _ = gather_complete.recv().await;
+        }
 
         // Output the answer in base64 so we can paste it in browser
         if let Some(local_desc) = peer_connection.local_description().await {
             // IMPRIMIR SDP EN BASE64
             let json_str = serde_json::to_string(&local_desc)?;
             let b64 = encode(&json_str);
-            ws.send_sdp_to_offerer(offerer_name, &b64).await?;
+            // The peers may connect as soon as the offerer gets the answer, so the session
+            // must already be connecting by then
+            let _ = session.handle(SessionEvent::DescriptionsExchanged);
+            ws.send_sdp_to_offerer(offerer_name, &b64)
+                .await
+                .map_err(NodeError::Signaling)?;
             println!("{b64}");
         } else {
             log::error!("RECEIVER | Generate local_description failed!");
         }
 
-        let mut wait_shutdown: bool = false;
-        tokio::select! {
-            _ = shutdown.wait_for_shutdown() => {
-                log::info!("SENDER | Shutdown signal received");
-                ws.force_stop_session(client_name).await?;
-                wait_shutdown = true;
-            }
-            _ = front_connection.waiting_to_disconnect() => {
-                log::info!("SENDER | Disconnect signal received");
-                ws.force_stop_session(client_name).await?;
-                shutdown.notify_error(true, "Disconnect signal received").await;
-            }
-            _ = ws.wait_for_stop_session() => {
-                log::info!("SENDER | Stop session signal received");
-                shutdown.notify_error(true, "Stop session signal received").await;
-            }
-        }
-
-        if !wait_shutdown {
-            let _ = shutdown.wait_for_shutdown().await;
+        if let Some(candidates) = local_candidates.as_mut() {
+            let mut session_connection = session.subscribe();
+            let wait_connection = async {
+                tokio::select! {
+                    _ = session_connection.wait_streaming() => {}
+                    _ = supervisor.cancelled() => {}
+                }
+            };
+            exchange_candidates(&comunication, ws, offerer_name, candidates, wait_connection)
+                .await?;
         }
 
-        if peer_connection.close().await.is_err() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error closing peer connection",
-            ));
+        // The offerer sends the ICE restart, the client only answers it and gives up
+        // if the connection is not restored in time
+        let mut reconnect = Reconnect::new(&config.reconnect);
+        loop {
+            tokio::select! {
+                _ = supervisor.cancelled() => {
+                    log::info!("SENDER | Shutdown signal received");
+                    ws.force_stop_session(client_name)
+                        .await
+                        .map_err(NodeError::Signaling)?;
+                    break;
+                }
+                _ = front_connection.waiting_to_disconnect() => {
+                    log::info!("SENDER | Disconnect signal received");
+                    ws.force_stop_session(client_name)
+                        .await
+                        .map_err(NodeError::Signaling)?;
+                    supervisor.stop();
+                    break;
+                }
+                signal = ws.wait_for_session_event() => match signal {
+                    Ok(SessionSignal::Offer(sdp)) => {
+                        log::info!("RECEIVER | Offer received during session");
+                        if let Err(e) = answer_offer(&comunication, ws, offerer_name, sdp).await {
+                            log::error!("RECEIVER | Error answering offer | {}", e);
+                        }
+                    }
+                    Ok(SessionSignal::Candidate(candidate)) => {
+                        if let Err(e) = comunication.add_remote_candidate(&candidate).await {
+                            log::warn!("RECEIVER | Error adding remote candidate | {}", e);
+                        }
+                    }
+                    Ok(SessionSignal::Answer(_)) => {
+                        log::warn!("RECEIVER | Unexpected answer received during session");
+                    }
+                    Ok(SessionSignal::EndSession) | Err(_) => {
+                        log::info!("SENDER | Stop session signal received");
+                        supervisor.stop();
+                        break;
+                    }
+                },
+                Some(event) = connection_rx.recv() => reconnect.on_event(event),
+                _ = reconnect.expired() => {
+                    log::error!("RECEIVER | Connection could not be recovered");
+                    reconnect.give_up();
+                    supervisor.fail("Reconnect", "Reconnect timeout");
+                }
+            }
         }
 
-        shutdown.shutdown();
-
         Ok(())
     }
 }
@@ -203,42 +341,57 @@ impl ReceiverSide {
 /// * `peer_connection` - A RTCPeerConnection.
 /// * `tx_audio` - A channel to configure in case it is an audio track.
 /// * `tx_audio` - A channel to configure in case it is a video track.
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `supervisor` -  Owns the tasks that read the tracks.
+/// * `session` - Used to wait until the session is streaming before reading the tracks.
+/// * `tracker_config` - Thresholds of the error trackers used when reading the tracks.
 fn set_on_track_handler(
     peer_connection: &Arc<RTCPeerConnection>,
     tx_audio: mpsc::Sender<(bool, Vec<u8>)>,
     tx_video: mpsc::Sender<(bool, Vec<u8>)>,
-    shutdown: shutdown::Shutdown,
-    barrier: Arc<Barrier>,
+    supervisor: Supervisor,
+    session: Session,
+    tracker_config: ErrorTrackerConfig,
 ) {
     peer_connection.on_track(Box::new(move |track, _, _| {
         let codec = track.codec();
         let mime_type = codec.capability.mime_type.to_lowercase();
-        let barrier_audio = barrier.clone();
+        let mut session_audio = session.subscribe();
         // Check if is a audio track
         if mime_type == MIME_TYPE_OPUS.to_lowercase() {
             let tx_audio_cpy = tx_audio.clone();
-            let mut shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                tokio::spawn(async move {
-                    barrier_audio.wait().await;
-                    println!("RECEIVER | Got OPUS Track");
-                    let _ = read_audio_track(track, tx_audio_cpy, &mut shutdown_cpy).await;
-                });
+            let tracker_config_cpy = tracker_config.clone();
+            supervisor.spawn("Read audio track", move |task| async move {
+                tokio::select! {
+                    _ = task.cancelled() => return,
+                    streaming = session_audio.wait_streaming() => {
+                        if !streaming {
+                            return;
+                        }
+                    }
+                }
+                println!("RECEIVER | Got OPUS Track");
+                let _ = read_audio_track(track, tx_audio_cpy, task, tracker_config_cpy).await;
             });
+            return Box::pin(async {});
         };
-        let barrier_video = barrier.clone();
-        // Check if is a audio track
+        let mut session_video = session.subscribe();
+        // Check if is a video track
         if mime_type == MIME_TYPE_H264.to_lowercase() {
             let tx_video_cpy = tx_video.clone();
-            let mut shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                tokio::spawn(async move {
-                    barrier_video.wait().await;
-                    println!("RECEIVER | Got H264 Track");
-                    let _ = read_video_track(track, tx_video_cpy, &mut shutdown_cpy).await;
-                });
+            let tracker_config_cpy = tracker_config.clone();
+            supervisor.spawn("Read video track", move |task| async move {
+                tokio::select! {
+                    _ = task.cancelled() => return,
+                    streaming = session_video.wait_streaming() => {
+                        if !streaming {
+                            return;
+                        }
+                    }
+                }
+                println!("RECEIVER | Got H264 Track");
+                let _ = read_video_track(track, tx_video_cpy, task, tracker_config_cpy).await;
             });
+            return Box::pin(async {});
         };
 
         Box::pin(async {})
@@ -251,28 +404,32 @@ fn set_on_track_handler(
 ///
 /// * `track` - Audio track from which to read rtp packets
 /// * `tx` - A channel to send the packets read
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `task` -  Handle of the supervised task, used for graceful shutdown.
+/// * `tracker_config` - Thresholds of the error tracker used when reading packets.
 ///
 /// # Return
 /// Result containing `Ok(())` on success. Error on error.
 async fn read_audio_track(
     track: Arc<TrackRemote>,
     tx: mpsc::Sender<(bool, Vec<u8>)>,
-    shutdown: &mut shutdown::Shutdown,
+    task: Task,
+    tracker_config: ErrorTrackerConfig,
 ) -> Result<(), Error> {
-    let mut error_tracker = ErrorTracker::new(READ_TRACK_THRESHOLD, READ_TRACK_LIMIT);
-    shutdown.add_task("Read audio track").await;
+    let mut error_tracker = ErrorTracker::new(
+        tracker_config.read_track_threshold,
+        tracker_config.read_track_limit,
+    );
 
     loop {
         tokio::select! {
             result = track.read_rtp() => {
                 if let Ok((rtp_packet, _)) = result {
                     let value = rtp_packet.payload.to_vec();
-                    send_packet_in_channel(&tx, value, shutdown.clone()).await?;
+                    send_packet_in_channel(&tx, value, &task)?;
                 }else if error_tracker.increment_with_error(){
                         log::error!("RECEIVER | Max Attemps | Error reading RTP packet");
                         tx.send((true, vec![])).unwrap();
-                        shutdown.notify_error(false,"Error sending rtp packet").await;
+                        task.fail("Error reading audio rtp packet");
                         drop(tx);
                         return Err(Error::new(ErrorKind::Other, "Error reading RTP packet"));
                 }else{
@@ -283,7 +440,7 @@ async fn read_audio_track(
             _ = tokio::signal::ctrl_c() => {
                 return Ok(());
             }
-            _= shutdown.wait_for_error() => {
+            _= task.cancelled() => {
                 log::info!("READ AUDIO TRACK | Shutdown received");
                 tx.send((true, vec![])).unwrap();
                 drop(tx);
@@ -293,17 +450,17 @@ async fn read_audio_track(
     }
 }
 
-async fn send_packet_in_channel(
+fn send_packet_in_channel(
     tx: &mpsc::Sender<(bool, Vec<u8>)>,
     value: Vec<u8>,
-    shutdown: shutdown::Shutdown,
+    task: &Task,
 ) -> Result<(), Error> {
     match tx.send((false, value)) {
         Ok(_) => Ok(()),
         Err(e) => {
             log::error!("RECEIVER | Error sending audio packet to channel: {e}");
             tx.send((true, vec![])).unwrap();
-            shutdown.notify_error(false, "Sending audio packet").await;
+            task.fail("Error sending packet to player");
             //drop(tx);
             Err(Error::new(
                 ErrorKind::Other,
@@ -319,27 +476,31 @@ async fn send_packet_in_channel(
 ///
 /// * `track` - Video track from which to read data
 /// * `tx` - A channel to send the data read
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `task` -  Handle of the supervised task, used for graceful shutdown.
+/// * `tracker_config` - Thresholds of the error tracker used when reading packets.
 ///
 /// # Return
 /// Result containing `Ok(())` on success. Error on error.
 async fn read_video_track(
     track: Arc<TrackRemote>,
     tx: mpsc::Sender<(bool, Vec<u8>)>,
-    shutdown: &mut shutdown::Shutdown,
+    task: Task,
+    tracker_config: ErrorTrackerConfig,
 ) -> Result<(), Error> {
-    let mut error_tracker = ErrorTracker::new(READ_TRACK_THRESHOLD, READ_TRACK_LIMIT);
-    shutdown.add_task("Read video track").await;
+    let mut error_tracker = ErrorTracker::new(
+        tracker_config.read_track_threshold,
+        tracker_config.read_track_limit,
+    );
 
     loop {
         let mut buff: [u8; 1400] = [0; 1400];
         tokio::select! {
             result = track.read(&mut buff) => {
                 if let Ok((_rtp_packet, _)) = result {
-                    send_packet_in_channel(&tx, buff.to_vec(), shutdown.clone()).await?;
+                    send_packet_in_channel(&tx, buff.to_vec(), &task)?;
                 }else if error_tracker.increment_with_error(){
                         log::error!("RECEIVER | Max Attemps | Error reading RTP packet");
-                        shutdown.notify_error(false, "read video track max attemps").await;
+                        task.fail("Error reading video rtp packet");
                         tx.send((true, vec![])).unwrap();
                         drop(tx);
                         return Err(Error::new(ErrorKind::Other, "Error reading RTP packet"));
@@ -351,7 +512,7 @@ async fn read_video_track(
             _ = tokio::signal::ctrl_c() => {
                 return Ok(());
             }
-            _= shutdown.wait_for_error() => {
+            _= task.cancelled() => {
                 log::info!("READ VIDEO TRACK | Shutdown received");
                 tx.send((true, vec![])).unwrap();
                 drop(tx);
@@ -366,21 +527,19 @@ async fn read_video_track(
 /// # Arguments
 ///
 /// * `peer_conection` - A RTCPeerConnection
-/// * `shutdown` -  Used for graceful shutdown.
-fn channel_handler(peer_connection: &Arc<RTCPeerConnection>, shutdown: shutdown::Shutdown) {
+/// * `supervisor` -  Notified if the latency measurement cannot be started.
+fn channel_handler(peer_connection: &Arc<RTCPeerConnection>, supervisor: Supervisor) {
     // Register data channel creation handling
     peer_connection.on_data_channel(Box::new(move |d: Arc<RTCDataChannel>| {
         let d_label = d.label().to_owned();
 
         if d_label == LATENCY_CHANNEL_LABEL {
-            let shutdown_cpy = shutdown.clone();
+            let supervisor_cpy = supervisor.clone();
             Box::pin(async move {
                 // Start the latency measurement
                 if let Err(e) = Latency::start_latency_receiver(d).await {
                     log::error!("RECEIVER | Error starting latency receiver: {e}");
-                    shutdown_cpy
-                        .notify_error(false, "Error sending latency")
-                        .await;
+                    supervisor_cpy.fail("Latency", "Error starting latency receiver");
                 }
             })
         } else {
@@ -390,55 +549,63 @@ fn channel_handler(peer_connection: &Arc<RTCPeerConnection>, shutdown: shutdown:
         }
     }));
 }
+/// Sets the event handler for peer connection state changes
+///
+/// # Arguments
+///
+/// * `peer_connection` - A RTCPeerConnection.
+/// * `supervisor` -  Notified if the connection cannot be established.
+/// * `session` - Moved to streaming when the peers get connected.
+/// * `connection_tx` - A channel to report the connection being lost and restored once connected.
 fn add_peer_connection_handler(
     peer_connection: &Arc<RTCPeerConnection>,
-    shutdown: shutdown::Shutdown,
+    supervisor: Supervisor,
+    session: Session,
+    connection_tx: UnboundedSender<ConnectionEvent>,
 ) {
+    let connected_once = Arc::new(AtomicBool::new(false));
     peer_connection.on_peer_connection_state_change(Box::new(move |s: RTCPeerConnectionState| {
         log::info!("Peer Connection State has changed {s}");
 
         if s == RTCPeerConnectionState::Connected {
             log::info!("Peer Connection state: Connected");
-            return Box::pin(async move {
-                println!("RECEIVER | Barrier waiting");
-                println!("RECEIVER | Barrier released");
-            });
+            if connected_once.swap(true, Ordering::SeqCst) {
+                let _ = connection_tx.send(ConnectionEvent::Restored);
+                return Box::pin(async {});
+            }
+            let _ = session.handle(SessionEvent::PeerConnected);
+            return Box::pin(async {});
         }
 
         if s == RTCPeerConnectionState::Closed {
             log::error!("RECEIVER | Peer connection state: Closed");
-            let shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                shutdown_cpy
-                    .notify_error(true, "Peer connection closed")
-                    .await;
-                log::error!("RECEIVER | Notify error sended");
-            });
+            // The connection is also closed when the session ends normally
+            if !supervisor.is_cancelled() {
+                supervisor.fail("Peer connection", "Peer connection closed");
+            }
+            return Box::pin(async {});
+        }
+
+        let lost = s == RTCPeerConnectionState::Failed || s == RTCPeerConnectionState::Disconnected;
+        if lost && connected_once.load(Ordering::SeqCst) {
+            log::warn!("RECEIVER | Peer connection state: {s} | Waiting for an ICE restart");
+            let _ = connection_tx.send(ConnectionEvent::Lost);
+            return Box::pin(async {});
         }
 
         if s == RTCPeerConnectionState::Failed {
             // Wait until PeerConnection has had no network activity for 30 seconds or another failure. It may be reconnected using an ICE Restart.
             // Use webrtc.PeerConnectionStateDisconnected if you are interested in detecting faster timeout.
             // Note that the PeerConnection may come back from PeerConnectionStateDisconnected.
-            log::error!("SENDER | Peer connection state: Failed");
-            let shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                shutdown_cpy
-                    .notify_error(true, "Peer connection failed")
-                    .await;
-                log::error!("RECEIVER | Notify error sended");
-            });
+            log::error!("RECEIVER | Peer connection state: Failed");
+            supervisor.fail("Peer connection", "Peer connection failed");
+            return Box::pin(async {});
         }
 
         if s == RTCPeerConnectionState::Disconnected {
             log::error!("RECEIVER | Peer connection state: Disconnected");
-            let shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                shutdown_cpy
-                    .notify_error(true, "Peer connection disconnected")
-                    .await;
-                log::error!("RECEIVER | Notify error sended");
-            });
+            supervisor.fail("Peer connection", "Peer connection disconnected");
+            return Box::pin(async {});
         }
 
         Box::pin(async {})
@@ -1,22 +1,32 @@
+use std::fmt;
 use std::io::{Error, ErrorKind};
-use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
 use tokio::sync::mpsc::channel;
 use tokio::sync::mpsc::Receiver;
-use tokio::sync::Barrier;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
 
-use crate::front_connection::front_protocol::FrontConnection;
+use crate::config::node_config::ErrorTrackerConfig;
 use crate::gstreamer_pipeline::av_capture::start_capture;
-use crate::services::sender_utils::{get_handler, initialize_game};
-use crate::utils::shutdown::Shutdown;
+use crate::gstreamer_pipeline::capture_backend::{create_backend, BackendKind};
+use crate::services::sender_utils::{get_handler, initialize_game, kill_process};
+use crate::services::session_context::SessionContext;
+use crate::services::session_state::{Session, SessionEvent, SessionWatcher};
+use crate::utils::node_error::NodeError;
+use crate::utils::supervisor::{ShutdownReport, Supervisor, Task};
+use crate::utils::supervisor_const::SHUTDOWN_DEADLINE;
 use crate::webrtcommunication::communication::{encode, Communication};
 
-use crate::input::input_const::{KEYBOARD_CHANNEL_LABEL, MOUSE_CHANNEL_LABEL};
+use crate::input::input_const::{
+    GAMEPAD_CHANNEL_LABEL, KEYBOARD_CHANNEL_LABEL, MOUSE_CHANNEL_LABEL,
+};
 use crate::output::button_controller::ButtonController;
+use crate::output::gamepad_controller::GamepadController;
+use crate::output::input_injector::{create_injector, InputInjector, NoopInjector, SharedInjector};
 use crate::output::mouse_controller::MouseController;
 use webrtc::data_channel::RTCDataChannel;
 
-use crate::utils::shutdown;
 use webrtc::api::media_engine::{MIME_TYPE_H264, MIME_TYPE_OPUS};
 use webrtc::media::Sample;
 use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
@@ -27,36 +37,99 @@ use webrtc::track::track_local::track_local_static_rtp::TrackLocalStaticRTP;
 use webrtc::track::track_local::track_local_static_sample::TrackLocalStaticSample;
 use webrtc::track::track_local::{TrackLocal, TrackLocalWriter};
 
-//use std::process::Command;
-use winapi::um::processthreadsapi::OpenProcess;
-use winapi::um::processthreadsapi::TerminateProcess;
-use winapi::um::winnt::PROCESS_TERMINATE;
-
 use crate::utils::webrtc_const::{
-    AUDIO_CHANNELS, AUDIO_SAMPLE_RATE, AUDIO_TRACK_ID, SEND_TRACK_LIMIT, SEND_TRACK_THRESHOLD,
-    STREAM_TRACK_ID, STUN_ADRESS, VIDEO_TRACK_ID,
+    AUDIO_CHANNELS, AUDIO_SAMPLE_RATE, AUDIO_TRACK_ID, STREAM_TRACK_ID, VIDEO_TRACK_ID,
 };
 use crate::webrtcommunication::latency::Latency;
-use crate::websocketprotocol::socket_protocol::{ClientInfo, WsProtocol};
+use crate::webrtcommunication::reconnect::{restart_ice, ConnectionEvent, Reconnect};
+use crate::webrtcommunication::stats::report_stream_stats;
+use crate::webrtcommunication::trickle::exchange_candidates;
+use crate::websocketprotocol::signaling_transport::{ClientInfo, SessionSignal};
 
 pub struct SenderSide {}
+
+/// What a session leaves behind besides its tasks, released however the session ended.
+#[derive(Default)]
+struct SessionResources {
+    /// Process of the game launched for the session.
+    pid: Option<u32>,
+    pc: Option<Arc<RTCPeerConnection>>,
+}
+
 impl SenderSide {
     pub async fn init(
         offerer_name: &str,
-        ws: &mut WsProtocol,
-        front_connection: &mut FrontConnection,
-    ) -> Result<(), Error> {
-        let shutdown = Shutdown::new();
+        ctx: SessionContext<'_>,
+    ) -> Result<ShutdownReport, NodeError> {
+        let session = ctx.session;
+        let supervisor = Supervisor::new();
+        let mut resources = SessionResources::default();
+
+        let outcome = Self::run(offerer_name, ctx, &supervisor, &mut resources).await;
+
+        // Errors that were not reported yet still stop every task
+        if let Err(e) = &outcome {
+            if supervisor.failure().is_none() {
+                supervisor.fail("Sender", &e.to_string());
+            }
+        }
+
+        let _ = session.handle(SessionEvent::Stop);
+        let report = supervisor
+            .shutdown(Duration::from_secs(SHUTDOWN_DEADLINE))
+            .await;
+        log::info!("SENDER | Session {}", report);
+        let _ = session.handle(SessionEvent::Drained);
+
+        if let Some(pid) = resources.pid {
+            kill_process(pid);
+        }
+
+        let closed = match resources.pc {
+            Some(pc) => pc.close().await,
+            None => Ok(()),
+        };
+        outcome?;
+        if let Err(e) = closed {
+            return Err(NodeError::WebRtc("Error closing peer connection", e));
+        }
+
+        Ok(report)
+    }
 
+    /// Runs a session until it ends or fails. Cleaning up is left to `init`, so returning
+    /// early at any point does not leave tasks, the game or the peer connection behind.
+    ///
+    /// # Arguments
+    ///
+    /// * `ctx` - Handles of the node the session runs with.
+    /// * `supervisor` - Owns the tasks of the session.
+    /// * `resources` - Filled with what must be released once the session ends.
+    async fn run(
+        offerer_name: &str,
+        ctx: SessionContext<'_>,
+        supervisor: &Supervisor,
+        resources: &mut SessionResources,
+    ) -> Result<(), NodeError> {
+        let SessionContext {
+            ws,
+            front_connection,
+            config,
+            session,
+        } = ctx;
         // Wait for client to request a connection
-        ws.init_offer(offerer_name).await?;
+        ws.init_offer(offerer_name)
+            .await
+            .map_err(NodeError::Signaling)?;
+        let _ = session.handle(SessionEvent::Start);
         let client_info: Option<ClientInfo>;
 
         tokio::select! {
             cf = ws.wait_for_game_solicitude() => {
-                client_info = Some(cf?);
+                client_info = Some(cf.map_err(NodeError::Signaling)?);
             }
             _ = front_connection.waiting_to_disconnect() => {
+                supervisor.stop();
                 return Ok(());
             }
         }
@@ -66,15 +139,19 @@ impl SenderSide {
         let new_client = match client_info {
             Some(c) => c,
             None => {
-                return Err(Error::new(ErrorKind::Other, "Error receiving client info"));
+                return Err(NodeError::Signaling(Error::new(
+                    ErrorKind::InvalidData,
+                    "Error receiving client info",
+                )));
             }
         };
-        // Start game
+        // Start game, the test backend streams a generated pattern instead
         let game_path = &new_client.game_path;
+        let launch_game = config.capture.backend != BackendKind::Test;
 
-        initialize_game(game_path)?;
-
-        let barrier = Arc::new(Barrier::new(5));
+        if launch_game {
+            initialize_game(game_path)?;
+        }
 
         //Create audio frames channels
         let (tx_audio, rx_audio) = channel(100);
@@ -82,165 +159,246 @@ impl SenderSide {
         // Create video frame channels
         let (tx_video, rx_video) = channel(100);
 
-        let comunication =
-            check_error(Communication::new(STUN_ADRESS.to_owned()).await, &shutdown).await?;
+        let comunication = check_error(Communication::new(&config.webrtc).await, supervisor)?;
+        resources.pc = Some(comunication.get_peer());
 
-        let (hwnd, pid) = match get_handler(game_path) {
-            Ok((hwnd, pid)) => (hwnd, pid),
-            Err(_) => {
-                shutdown.notify_error(true, "get_handler").await;
-                return Err(Error::new(ErrorKind::Other, "Error getting handler"));
+        let (hwnd, pid) = if launch_game {
+            match get_handler(game_path) {
+                Ok((hwnd, pid)) => (hwnd, Some(pid)),
+                Err(e) => {
+                    supervisor.fail("Sender", &e.to_string());
+                    return Err(e);
+                }
             }
+        } else {
+            (0, None)
         };
+        resources.pid = pid;
 
         // Start the video capture
-        let mut shutdown_capture = shutdown.clone();
+        let session_capture = session.subscribe();
+        let backend = create_backend(&config.capture);
+        let video_config = config.video.clone();
 
-        let barrier_video = barrier.clone();
+        // Frames are dropped while paused, the encoder is asked for a keyframe on resume
+        let (keyframe_tx, keyframe_rx) = unbounded_channel();
 
-        tokio::spawn(async move {
+        supervisor.spawn("Capture", move |task| {
             start_capture(
                 tx_video,
                 tx_audio,
-                &mut shutdown_capture,
-                barrier_video,
+                task,
+                session_capture,
+                backend,
                 hwnd,
+                video_config,
+                keyframe_rx,
             )
-            .await;
         });
 
         let pc = comunication.get_peer();
 
         let (_rtp_sender, audio_track) =
-            create_track_sample(pc.clone(), shutdown.clone(), MIME_TYPE_OPUS, AUDIO_TRACK_ID)
-                .await?;
+            create_track_sample(pc.clone(), supervisor, MIME_TYPE_OPUS, AUDIO_TRACK_ID).await?;
         let (rtp_video_sender, video_track) =
-            create_track_rtp(pc.clone(), shutdown.clone(), MIME_TYPE_H264, VIDEO_TRACK_ID).await?;
+            create_track_rtp(pc.clone(), supervisor, MIME_TYPE_H264, VIDEO_TRACK_ID).await?;
 
-        check_error(Latency::start_latency_sender(pc.clone()).await, &shutdown).await?;
+        check_error(Latency::start_latency_sender(pc.clone()).await, supervisor)?;
 
-        channel_handler(&pc, shutdown.clone());
+        // The session goes on without remote input if it cannot be injected. The test
+        // backend streams no game, so the input must not reach the desktop of the host
+        let injector = if launch_game {
+            create_injector(hwnd)
+        } else {
+            log::info!("SENDER | Test capture backend, remote input is dropped");
+            Ok(Box::new(NoopInjector) as Box<dyn InputInjector>)
+        };
+        match injector {
+            Ok(injector) => channel_handler(&pc, Arc::new(Mutex::new(injector))),
+            Err(e) => log::warn!("SENDER | Remote input disabled | {}", e),
+        }
 
-        let shutdown_cpy_3 = shutdown.clone();
-        tokio::spawn(async move {
-            read_rtcp(&mut shutdown_cpy_3.clone(), rtp_video_sender).await;
-        });
+        supervisor.spawn("Read rtcp", move |task| read_rtcp(task, rtp_video_sender));
 
-        let barrier_audio_send = barrier.clone();
-        let mut shutdown_cpy_2 = shutdown.clone();
-        tokio::spawn(async move {
+        let session_audio_send = session.subscribe();
+        let audio_tracker_config = config.error_tracker.clone();
+        supervisor.spawn("Audio sending", move |task| {
             start_audio_sending(
-                barrier_audio_send,
+                session_audio_send,
                 rx_audio,
                 audio_track,
-                &mut shutdown_cpy_2,
+                task,
+                audio_tracker_config,
             )
-            .await;
         });
 
-        let barrier_video_send = barrier.clone();
-        let mut shutdown_cpy_4 = shutdown.clone();
-        tokio::spawn(async move {
+        let session_video_send = session.subscribe();
+        let video_tracker_config = config.error_tracker.clone();
+        supervisor.spawn("Video sending", move |task| {
             start_video_sending(
-                barrier_video_send,
+                session_video_send,
                 rx_video,
                 video_track,
-                &mut shutdown_cpy_4,
+                task,
+                video_tracker_config,
+                keyframe_tx,
             )
-            .await;
         });
 
-        set_peer_events(&pc, barrier.clone(), shutdown.clone());
+        let (connection_tx, mut connection_rx) = unbounded_channel();
+        set_peer_events(&pc, session.clone(), supervisor.clone(), connection_tx);
+
+        let pc_stats = pc.clone();
+        let session_stats = session.subscribe();
+        let events = front_connection.events();
+        supervisor.spawn("Stream stats", move |task| {
+            report_stream_stats(pc_stats, session_stats, events, task)
+        });
+
+        // Trickle candidates only if the client advertised support for it
+        let trickle = config.webrtc.trickle_ice && new_client.trickle;
+        let mut local_candidates = if trickle {
+            Some(comunication.local_candidates())
+        } else {
+            None
+        };
 
         // Create an answer to send to the other process
         let offer = match pc.create_offer(None).await {
             Ok(offer) => offer,
-            Err(_) => {
-                shutdown.notify_error(true, "Create offer").await;
-                return Err(Error::new(ErrorKind::Other, "Error creating offer"));
+            Err(e) => {
+                supervisor.fail("Sender", "Create offer");
+                return Err(NodeError::WebRtc("Error creating offer", e));
             }
         };
         // Create channel that is blocked until ICE Gathering is complete
         let mut gather_complete = pc.gathering_complete_promise().await;
 
         // Sets the LocalDescription, and starts our UDP listeners
-        if let Err(_e) = pc.set_local_description(offer).await {
-            shutdown.notify_error(true, "Set local description").await;
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error setting local description",
-            ));
+        if let Err(e) = pc.set_local_description(offer).await {
+            supervisor.fail("Sender", "Set local description");
+            return Err(NodeError::WebRtc("Error setting local description", e));
         }
 
-        let _ = gather_complete.recv().await;
+        // Without trickle ICE every candidate must be in the sdp sent
+        if !trickle {
+            let _ = gather_complete.recv().await;
+        }
 
         if let Some(local_desc) = pc.local_description().await {
             let json_str = serde_json::to_string(&local_desc)?;
             let b64 = encode(&json_str);
-            ws.send_sdp_to_client(&new_client.client_name, &b64).await?;
+            ws.send_sdp_to_client(&new_client.client_name, &b64, trickle)
+                .await
+                .map_err(NodeError::Signaling)?;
             println!("{b64}");
         } else {
             log::error!("SENDER | Generate local_description failed");
-            shutdown.notify_error(true, "Local description").await;
-            return Err(Error::new(
-                ErrorKind::Other,
+            supervisor.fail("Sender", "Local description");
+            return Err(NodeError::WebRtc(
                 "Generate local_description failed",
+                webrtc::Error::new("missing local description".to_owned()),
             ));
         }
 
-        let client_sdp = ws.wait_for_client_sdp().await?;
-        check_error(comunication.set_sdp(client_sdp).await, &shutdown).await?;
-
-        let mut barrier_passed: bool = false;
-        tokio::select! {
-            _ = barrier.wait() => {
-                barrier_passed = true
+        let client_sdp = check_error(
+            ws.wait_for_client_sdp().await.map_err(NodeError::Signaling),
+            supervisor,
+        )?;
+        // The peers may connect as soon as the answer is set, so the session must already be
+        // connecting by then
+        let _ = session.handle(SessionEvent::DescriptionsExchanged);
+        check_error(comunication.set_sdp(client_sdp).await, supervisor)?;
+
+        let mut session_connection = session.subscribe();
+        let wait_connection = async {
+            tokio::select! {
+                streaming = session_connection.wait_streaming() => streaming,
+                _ = supervisor.cancelled() => {
+                    log::error!("SENDER | Error notifier signal");
+                    false
+                }
             }
-            _ = shutdown.wait_for_shutdown() => {
-                log::error!("RECEIVER | Error notifier signal");
+        };
+
+        let streaming = match local_candidates.as_mut() {
+            Some(candidates) => {
+                let exchange = exchange_candidates(
+                    &comunication,
+                    ws,
+                    &new_client.client_name,
+                    candidates,
+                    wait_connection,
+                )
+                .await;
+                check_error(exchange, supervisor)?
             }
+            None => wait_connection.await,
         };
 
-        if barrier_passed {
+        if streaming {
             let session_minutes = new_client.minutes;
             ws.start_session(
                 offerer_name,
                 new_client.client_name.as_str(),
                 &session_minutes,
             )
-            .await?;
+            .await
+            .map_err(NodeError::Signaling)?;
             println!("SENDER | Start session msg sended");
 
-            let mut wait_shutdown: bool = false;
-            tokio::select! {
-                _ = shutdown.wait_for_shutdown() => {
-                    log::info!("SENDER | Shutdown signal received");
-                    ws.force_stop_session(offerer_name).await?;
-                    wait_shutdown = true;
-                }
-                _  = front_connection.waiting_to_disconnect() => {
-                    log::info!("SENDER | Disconnect signal received");
-                    ws.force_stop_session(offerer_name).await?;
-                    shutdown.notify_error(true, "Stop session signal received").await;
-                }
-                _ = ws.wait_for_stop_session() => {
-                    log::info!("SENDER | Stop session signal received");
-                    shutdown.notify_error(true, "Stop session signal received").await;
+            let mut reconnect = Reconnect::new(&config.reconnect);
+            loop {
+                tokio::select! {
+                    _ = supervisor.cancelled() => {
+                        log::info!("SENDER | Shutdown signal received");
+                        ws.force_stop_session(offerer_name)
+                            .await
+                            .map_err(NodeError::Signaling)?;
+                        break;
+                    }
+                    _  = front_connection.waiting_to_disconnect() => {
+                        log::info!("SENDER | Disconnect signal received");
+                        ws.force_stop_session(offerer_name)
+                            .await
+                            .map_err(NodeError::Signaling)?;
+                        supervisor.stop();
+                        break;
+                    }
+                    signal = ws.wait_for_session_event() => match signal {
+                        Ok(SessionSignal::Answer(sdp)) => {
+                            if let Err(e) = comunication.set_sdp(sdp).await {
+                                log::warn!("SENDER | Error setting restart answer | {}", e);
+                            }
+                        }
+                        Ok(SessionSignal::Candidate(candidate)) => {
+                            if let Err(e) = comunication.add_remote_candidate(&candidate).await {
+                                log::warn!("SENDER | Error adding remote candidate | {}", e);
+                            }
+                        }
+                        Ok(SessionSignal::Offer(_)) => {
+                            log::warn!("SENDER | Unexpected offer received during session");
+                        }
+                        Ok(SessionSignal::EndSession) | Err(_) => {
+                            log::info!("SENDER | Stop session signal received");
+                            supervisor.stop();
+                            break;
+                        }
+                    },
+                    Some(event) = connection_rx.recv() => reconnect.on_event(event),
+                    _ = reconnect.restart_due() => {
+                        reconnect.restart_sent();
+                        if let Err(e) = restart_ice(&comunication, ws, &new_client.client_name).await {
+                            log::error!("SENDER | Error sending ICE restart | {}", e);
+                        }
+                    }
+                    _ = reconnect.expired() => {
+                        log::error!("SENDER | Connection could not be recovered");
+                        reconnect.give_up();
+                        supervisor.fail("Reconnect", "Reconnect timeout");
+                    }
                 }
             }
-
-            if !wait_shutdown {
-                let _ = shutdown.wait_for_shutdown().await;
-            }
-        }
-
-        kill_process(pid)?;
-
-        if pc.close().await.is_err() {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error closing peer connection",
-            ));
         }
 
         Ok(())
@@ -252,18 +410,18 @@ impl SenderSide {
 /// # Arguments
 ///
 /// * `pc` - A RTCPeerConnection to add the track.
-/// * `shutdown` - Used for graceful shutdown.
+/// * `supervisor` - Notified if the track cannot be added.
 /// * `mime_type` - The mime type for the configuration of the track.
 /// * `track_id` - The id provided for the configuration of the track.
 ///
 /// # Return
-/// Result containing `Ok((Arc<RTCRtpSender>, Arc<TrackLocalStaticSample>))` on success. Error on error.
+/// Result containing `Ok((Arc<RTCRtpSender>, Arc<TrackLocalStaticSample>))` on success. `NodeError::WebRtc` if the track could not be added.
 async fn create_track_sample(
     pc: Arc<RTCPeerConnection>,
-    shutdown: shutdown::Shutdown,
+    supervisor: &Supervisor,
     mime_type: &str,
     track_id: &str,
-) -> Result<(Arc<RTCRtpSender>, Arc<TrackLocalStaticSample>), Error> {
+) -> Result<(Arc<RTCRtpSender>, Arc<TrackLocalStaticSample>), NodeError> {
     let track = Arc::new(TrackLocalStaticSample::new(
         RTCRtpCodecCapability {
             mime_type: mime_type.to_owned(),
@@ -277,12 +435,9 @@ async fn create_track_sample(
         .await
     {
         Ok(rtp_sender) => Ok((rtp_sender, track)),
-        Err(_) => {
-            shutdown.notify_error(true, "Add track sample").await;
-            Err(Error::new(
-                ErrorKind::Other,
-                "Error setting local description",
-            ))
+        Err(e) => {
+            supervisor.fail("Sender", "Add track sample");
+            Err(NodeError::WebRtc("Error adding track", e))
         }
     }
 }
@@ -292,18 +447,18 @@ async fn create_track_sample(
 /// # Arguments
 ///
 /// * `pc` - A RTCPeerConnection to add the track.
-/// * `shutdown` - Used for graceful shutdown.
+/// * `supervisor` - Notified if the track cannot be added.
 /// * `mime_type` - The mime type for the configuration of the track.
 /// * `track_id` - The id provided for the configuration of the track.
 ///
 /// # Return
-/// Result containing `Ok((Arc<RTCRtpSender>, Arc<TrackLocalStaticRTP>))` on success. Error on error.
+/// Result containing `Ok((Arc<RTCRtpSender>, Arc<TrackLocalStaticRTP>))` on success. `NodeError::WebRtc` if the track could not be added.
 async fn create_track_rtp(
     pc: Arc<RTCPeerConnection>,
-    shutdown: shutdown::Shutdown,
+    supervisor: &Supervisor,
     mime_type: &str,
     track_id: &str,
-) -> Result<(Arc<RTCRtpSender>, Arc<TrackLocalStaticRTP>), Error> {
+) -> Result<(Arc<RTCRtpSender>, Arc<TrackLocalStaticRTP>), NodeError> {
     let track = Arc::new(TrackLocalStaticRTP::new(
         RTCRtpCodecCapability {
             mime_type: mime_type.to_owned(),
@@ -317,28 +472,32 @@ async fn create_track_rtp(
         .await
     {
         Ok(rtp_sender) => Ok((rtp_sender, track)),
-        Err(_) => {
-            shutdown.notify_error(true, "Add track rtp").await;
-            Err(Error::new(
-                ErrorKind::Other,
-                "Error setting local description",
-            ))
+        Err(e) => {
+            supervisor.fail("Sender", "Add track rtp");
+            Err(NodeError::WebRtc("Error adding track", e))
         }
     }
 }
 
 /// Sets the event handlers for ice connection/peer connection state change on the provided connection
 ///
+/// Until the first connection a failure ends the session. Once connected, losing the
+/// connection is reported through `connection_tx` so it can be recovered.
+///
 /// # Arguments
 ///
 /// * `pc` - A RTCPeerConnection.
-/// * `done_tx` - A channel to send the message if the peer connection fails.
-/// * `barrier` - Used for synchronization.
+/// * `session` - Moved to streaming when the peers get connected.
+/// * `supervisor` - Notified if the connection cannot be established.
+/// * `connection_tx` - A channel to report the connection being lost and restored.
 fn set_peer_events(
     pc: &Arc<RTCPeerConnection>,
-    barrier: Arc<Barrier>,
-    shutdown: shutdown::Shutdown,
+    session: Session,
+    supervisor: Supervisor,
+    connection_tx: UnboundedSender<ConnectionEvent>,
 ) {
+    let connected_once = Arc::new(AtomicBool::new(false));
+
     // Set the handler for ICE connection state
     // This will notify you when the peer has connected/disconnected
     // pc.on_ice_connection_state_change(Box::new(move |connection_state: RTCIceConnectionState| {
@@ -355,64 +514,58 @@ fn set_peer_events(
 
         if s == RTCPeerConnectionState::Connected {
             log::info!("Peer Connection state: Connected");
-            let barrier_cpy = barrier.clone();
-            return Box::pin(async move {
-                println!("SENDER | Barrier waiting");
-                barrier_cpy.wait().await;
-                println!("SENDER | Barrier released");
-            });
+            if connected_once.swap(true, Ordering::SeqCst) {
+                let _ = connection_tx.send(ConnectionEvent::Restored);
+                return Box::pin(async {});
+            }
+            let _ = session.handle(SessionEvent::PeerConnected);
+            return Box::pin(async {});
         }
 
         if s == RTCPeerConnectionState::Closed {
             log::error!("SENDER | Peer connection state: Closed");
-            let shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                shutdown_cpy
-                    .notify_error(true, "Peer connection closed")
-                    .await;
-                log::error!("SENDER | Notify error sended");
-            });
+            // The connection is also closed when the session ends normally
+            if !supervisor.is_cancelled() {
+                supervisor.fail("Peer connection", "Peer connection closed");
+            }
+            return Box::pin(async {});
+        }
+
+        let lost = s == RTCPeerConnectionState::Failed || s == RTCPeerConnectionState::Disconnected;
+        if lost && connected_once.load(Ordering::SeqCst) {
+            log::warn!("SENDER | Peer connection state: {s} | Trying to recover");
+            let _ = connection_tx.send(ConnectionEvent::Lost);
+            return Box::pin(async {});
         }
 
         if s == RTCPeerConnectionState::Failed {
             log::error!("SENDER | Peer connection state: Failed");
-            let shutdown_cpy = shutdown.clone();
-            return Box::pin(async move {
-                shutdown_cpy
-                    .notify_error(true, "Peer connection Failed")
-                    .await;
-                log::error!("SENDER | Notify error sended");
-            });
+            supervisor.fail("Peer connection", "Peer connection failed");
+            return Box::pin(async {});
         }
 
         if s == RTCPeerConnectionState::Disconnected {
             log::error!("SENDER | Peer connection state: Disconnected");
-            let shutdown_cpy = shutdown.clone();
-            //let _ = done_tx.try_send(());
-            return Box::pin(async move {
-                shutdown_cpy
-                    .notify_error(true, "Peer connection disconnected")
-                    .await;
-                log::error!("SENDER | Notify error sended");
-            });
+            supervisor.fail("Peer connection", "Peer connection disconnected");
+            return Box::pin(async {});
         }
 
         Box::pin(async {})
     }));
 }
 
-/// Checks the result provided and notifies the shutdown in case of error
+/// Checks the result provided and notifies the supervisor in case of error
 ///
 /// # Arguments
 ///
 /// * `result` - The result to check.
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `supervisor` -  Notified of the error, which stops every task.
 ///
 /// # Return
 /// The result provided as argument
-async fn check_error<T, E>(result: Result<T, E>, shutdown: &Shutdown) -> Result<T, E> {
-    if result.is_err() {
-        shutdown.notify_error(true, "Check error").await;
+fn check_error<T, E: fmt::Display>(result: Result<T, E>, supervisor: &Supervisor) -> Result<T, E> {
+    if let Err(e) = &result {
+        supervisor.fail("Sender", &e.to_string());
     }
     result
 }
@@ -421,17 +574,16 @@ async fn check_error<T, E>(result: Result<T, E>, shutdown: &Shutdown) -> Result<
 ///
 /// # Arguments
 ///
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `task` -  Handle of the supervised task, used for graceful shutdown.
 /// * `rtp_sender` -  RTCRtpSender from which to read messages.
-async fn read_rtcp(shutdown: &mut shutdown::Shutdown, rtp_sender: Arc<RTCRtpSender>) {
-    shutdown.add_task("Read rtcp").await;
+async fn read_rtcp(task: Task, rtp_sender: Arc<RTCRtpSender>) {
     let mut rtcp_buf = vec![0u8; 1500];
     loop {
         tokio::select! {
             _ = rtp_sender.read(&mut rtcp_buf) => {
 
             }
-            _ = shutdown.wait_for_error() => {
+            _ = task.cancelled() => {
                 log::error!("SENDER | read_rtcp | Shutdown signal received");
                 break;
             }
@@ -443,30 +595,35 @@ async fn read_rtcp(shutdown: &mut shutdown::Shutdown, rtp_sender: Arc<RTCRtpSend
 ///
 /// # Arguments
 ///
-/// * `barrier_audio_send` - Used for synchronization.
+/// * `session` - Used to wait until the session is streaming.
 /// * `rx` - A channel to receive samples.
 /// * `audio_track` - Track to write the samples to.
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `task` -  Handle of the supervised task, used for graceful shutdown.
+/// * `tracker_config` - Thresholds of the error tracker used when writing samples.
 async fn start_audio_sending(
-    barrier_audio_send: Arc<Barrier>,
+    mut session: SessionWatcher,
     mut rx: Receiver<Vec<u8>>,
     audio_track: Arc<TrackLocalStaticSample>,
-    shutdown: &mut shutdown::Shutdown,
+    task: Task,
+    tracker_config: ErrorTrackerConfig,
 ) {
-    shutdown.add_task("Audio sending").await;
-
     tokio::select! {
-        _ = shutdown.wait_for_error() => {
+        _ = task.cancelled() => {
             log::error!("SENDER | START AUDIO SENDING | Shutdown signal received");
             return;
         },
-        _ = barrier_audio_send.wait() => {
-            log::info!("SENDER | START AUDIO SENDING | Barrier passed");
+        streaming = session.wait_streaming() => {
+            if !streaming {
+                return;
+            }
+            log::info!("SENDER | START AUDIO SENDING | Session streaming");
         }
     }
 
-    let mut error_tracker_write =
-        crate::utils::error_tracker::ErrorTracker::new(SEND_TRACK_THRESHOLD, SEND_TRACK_LIMIT);
+    let mut error_tracker_write = crate::utils::error_tracker::ErrorTracker::new(
+        tracker_config.send_track_threshold,
+        tracker_config.send_track_limit,
+    );
 
     let sample_duration =
         Duration::from_millis((AUDIO_CHANNELS as u64 * 10000000) / AUDIO_SAMPLE_RATE as u64);
@@ -474,13 +631,15 @@ async fn start_audio_sending(
     let mut data = match rx.recv().await {
         Some(d) => d,
         None => {
-            shutdown.notify_error(false, "No audio data received").await;
+            task.fail("No audio data received");
             return;
         }
     };
 
     loop {
-        if let Err(err) = audio_track
+        if session.is_paused() {
+            // Keep draining the capture while paused so no stale samples are sent on resume
+        } else if let Err(err) = audio_track
             .write_sample(&Sample {
                 data: data.clone().into(),
                 duration: sample_duration,
@@ -491,7 +650,7 @@ async fn start_audio_sending(
             log::warn!("SENDER | Error writing sample | {}", err);
             if error_tracker_write.increment_with_error() {
                 log::error!("SENDER | Max attemps | Error writing sample | {}", err);
-                shutdown.notify_error(false, "Error writing sample").await;
+                task.fail("Error writing sample");
                 return;
             } else {
                 log::warn!("SENDER | Error writing sample | {}", err);
@@ -509,14 +668,12 @@ async fn start_audio_sending(
                     }
                     None => {
                         log::error!("SENDER | Error receiving audio data |");
-                        shutdown
-                            .notify_error(false, "Error receiving audio data")
-                            .await;
+                        task.fail("Error receiving audio data");
                         return;
                     }
                 }
             },
-            _ = shutdown.wait_for_error() => {
+            _ = task.cancelled() => {
                 log::error!("SENDER | START AUDIO SENDING | Shutdown signal received");
                 break;
             }
@@ -528,45 +685,63 @@ async fn start_audio_sending(
 ///
 /// # Arguments
 ///
-/// * `barrier_video_send` - Used for synchronization.
+/// * `session` - Used to wait until the session is streaming.
 /// * `rx` - A channel to receive samples.
 /// * `video_track` - Track to write the samples to.
-/// * `shutdown` -  Used for graceful shutdown.
+/// * `task` -  Handle of the supervised task, used for graceful shutdown.
+/// * `tracker_config` - Thresholds of the error tracker used when writing samples.
+/// * `keyframe_tx` - Asks the capture for a keyframe when the session is resumed.
 async fn start_video_sending(
-    barrier_video_send: Arc<Barrier>,
+    mut session: SessionWatcher,
     mut rx: Receiver<Vec<u8>>,
     video_track: Arc<TrackLocalStaticRTP>,
-    shutdown: &mut shutdown::Shutdown,
+    task: Task,
+    tracker_config: ErrorTrackerConfig,
+    keyframe_tx: UnboundedSender<()>,
 ) {
-    shutdown.add_task("Video sending").await;
-
     tokio::select! {
-        _ = shutdown.wait_for_error() => {
+        _ = task.cancelled() => {
             log::error!("SENDER | START VIDEO SENDING | Shutdown signal received");
             return;
         },
-        _ =  barrier_video_send.wait() => {
-            log::info!("SENDER | START VIDEO SENDING | Barrier passed");
+        streaming = session.wait_streaming() => {
+            if !streaming {
+                return;
+            }
+            log::info!("SENDER | START VIDEO SENDING | Session streaming");
         }
     }
 
-    let mut error_tracker_write =
-        crate::utils::error_tracker::ErrorTracker::new(SEND_TRACK_THRESHOLD, SEND_TRACK_LIMIT);
+    let mut error_tracker_write = crate::utils::error_tracker::ErrorTracker::new(
+        tracker_config.send_track_threshold,
+        tracker_config.send_track_limit,
+    );
 
     let mut data = match rx.recv().await {
         Some(d) => d,
         None => {
-            shutdown.notify_error(false, "No video data received").await;
+            task.fail("No video data received");
             return;
         }
     };
 
+    let mut was_paused = false;
     loop {
-        if let Err(err) = video_track.write(&data).await {
+        let paused = session.is_paused();
+        if was_paused && !paused {
+            // The frames dropped while paused are referenced by the next ones, the receiver
+            // cannot decode the stream again until a keyframe arrives
+            let _ = keyframe_tx.send(());
+        }
+        was_paused = paused;
+
+        if paused {
+            // Keep draining the capture while paused so no stale frames are sent on resume
+        } else if let Err(err) = video_track.write(&data).await {
             log::warn!("SENDER | Error writing sample | {}", err);
             if error_tracker_write.increment_with_error() {
                 log::error!("SENDER | Max attemps | Error writing sample | {}", err);
-                shutdown.notify_error(false, "Error writing sample").await;
+                task.fail("Error writing sample");
                 return;
             } else {
                 log::warn!("SENDER | Error writing sample | {}", err);
@@ -584,14 +759,12 @@ async fn start_video_sending(
                     }
                     None => {
                         log::error!("SENDER | Error receiving video data |");
-                        shutdown
-                            .notify_error(false, "Error receiving video data")
-                            .await;
+                        task.fail("Error receiving video data");
                         return;
                     }
                 }
             },
-            _ = shutdown.wait_for_error() => {
+            _ = task.cancelled() => {
                 log::error!("SENDER | START VIDEO SENDING | Shutdown signal received");
                 break;
             }
@@ -604,19 +777,24 @@ async fn start_video_sending(
 /// # Arguments
 ///
 /// * `peer_conection` - A RTCPeerConnection
-/// * `shutdown` -  Used for graceful shutdown.
-fn channel_handler(peer_connection: &Arc<RTCPeerConnection>, _shutdown: shutdown::Shutdown) {
+/// * `injector` - Injects the input received through the keyboard and mouse channels
+fn channel_handler(peer_connection: &Arc<RTCPeerConnection>, injector: SharedInjector) {
     // Register data channel creation handling
     peer_connection.on_data_channel(Box::new(move |d: Arc<RTCDataChannel>| {
         let d_label = d.label().to_owned();
+        let injector = injector.clone();
 
         if d_label == MOUSE_CHANNEL_LABEL {
             Box::pin(async {
-                MouseController::start_mouse_controller(d);
+                MouseController::start_mouse_controller(d, injector);
             })
         } else if d_label == KEYBOARD_CHANNEL_LABEL {
             Box::pin(async {
-                ButtonController::start_keyboard_controller(d);
+                ButtonController::start_keyboard_controller(d, injector);
+            })
+        } else if d_label == GAMEPAD_CHANNEL_LABEL {
+            Box::pin(async {
+                GamepadController::start_gamepad_controller(d, injector);
             })
         } else {
             Box::pin(async move {
@@ -625,17 +803,3 @@ fn channel_handler(peer_connection: &Arc<RTCPeerConnection>, _shutdown: shutdown
         }
     }));
 }
-
-fn kill_process(pid: u32) -> std::io::Result<()> {
-    unsafe {
-        let h_process = OpenProcess(PROCESS_TERMINATE, 0, pid);
-        if h_process.is_null() {
-            println!("Failed to open the process.");
-            return Ok(());
-        }
-
-        TerminateProcess(h_process, 1);
-    };
-
-    Ok(())
-}
@@ -7,7 +7,8 @@
 /// # Note
 ///
 /// This module relies on the `sysinfo` and `winapi` crates for retrieving process information
-/// and interacting with the Windows API, respectively.
+/// and interacting with the Windows API, respectively. The Windows API is only used on Windows,
+/// on other platforms the whole screen is captured instead of the game window.
 ///
 /// The `initialize_game` function can be used to start a game process given its path. It supports
 /// both `.exe` files and shortcuts (`.lnk` or `.url` files).
@@ -30,6 +31,7 @@ use std::process::Command;
 
 use std::thread::sleep;
 use std::time::Duration;
+#[cfg(windows)]
 use winapi::{
     shared::{
         minwindef::{BOOL, DWORD, FALSE, LPARAM, TRUE},
@@ -41,7 +43,9 @@ use winapi::{
     },
 };
 
-use sysinfo::System;
+use sysinfo::{Pid, System};
+
+use crate::utils::node_error::NodeError;
 
 #[derive(Debug)]
 struct ProcessInfo {
@@ -49,6 +53,7 @@ struct ProcessInfo {
     path: String,
 }
 
+#[cfg(windows)]
 struct EnumData {
     target_pid: DWORD,
     hwnd: Option<HWND>,
@@ -57,21 +62,22 @@ struct EnumData {
 const HANDLER_RETRIES: usize = 8;
 const HANDLER_SLEEP: usize = 10000;
 
-pub fn initialize_game(game_path: &str) -> Result<(), Error> {
-    if game_path.ends_with(".exe") {
+pub fn initialize_game(game_path: &str) -> Result<(), NodeError> {
+    // Executables have no extension outside Windows
+    if game_path.ends_with(".exe") || cfg!(not(windows)) {
         match Command::new(game_path).spawn() {
             Ok(_child) => Ok(()),
-            Err(_) => Err(Error::new(ErrorKind::Other, "Error initializing game")),
+            Err(e) => Err(NodeError::GameLaunch(e)),
         }
     } else {
-        Err(Error::new(
-            ErrorKind::Other,
+        Err(NodeError::GameLaunch(Error::new(
+            ErrorKind::Unsupported,
             "Error type of file not supported",
-        ))
+        )))
     }
 }
 
-pub fn get_handler(target_path: &str) -> Result<(u64, u32), Error> {
+pub fn get_handler(target_path: &str) -> Result<(u64, u32), NodeError> {
     let mut found_process: Option<ProcessInfo> = None;
     let game_path: String = target_path.replace("\\\\", "\\");
 
@@ -94,25 +100,63 @@ pub fn get_handler(target_path: &str) -> Result<(u64, u32), Error> {
 
     if found_process.is_none() {
         log::error!("SENDER UTILS | Process not found PID");
-        return Err(Error::new(
-            ErrorKind::Other,
+        return Err(NodeError::GameLaunch(Error::new(
+            ErrorKind::NotFound,
             "Process PID not found after retries",
-        ));
+        )));
     }
     for i in 0..HANDLER_RETRIES {
         if let Some(process) = &found_process {
-            if let Some(hwnd) = get_hwnd_by_pid(process.pid) {
-                return Ok((hwnd as u64, process.pid));
+            if let Some(hwnd) = find_window(process.pid) {
+                return Ok((hwnd, process.pid));
             }
         }
         sleep(Duration::from_millis(HANDLER_SLEEP as u64));
         println!("Retrying get hwnd... {}/{}\r", i + 1, HANDLER_RETRIES);
     }
     log::error!("SENDER UTILS | Process not found HWND");
-    Err(Error::new(
-        ErrorKind::Other,
+    Err(NodeError::GameLaunch(Error::new(
+        ErrorKind::NotFound,
         "Process HWND not found after retries",
-    ))
+    )))
+}
+
+/// Ends the game process started for the session.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+pub fn kill_process(pid: u32) {
+    let system = System::new_all();
+    match system.process(Pid::from_u32(pid)) {
+        Some(process) => {
+            if !process.kill() {
+                log::warn!("SENDER UTILS | Could not kill process {}", pid);
+            }
+        }
+        None => log::warn!("SENDER UTILS | Process {} already finished", pid),
+    }
+}
+
+/// Function that retrieves the handle of the window to capture for a process.
+///
+/// # Arguments
+///
+/// * `pid` - The process ID.
+///
+/// # Returns
+///
+/// * `Option<u64>` - The handle of the window, or None if the process has no visible window yet.
+#[cfg(windows)]
+fn find_window(pid: u32) -> Option<u64> {
+    get_hwnd_by_pid(pid).map(|hwnd| hwnd as u64)
+}
+
+/// Outside Windows the window is not looked up, 0 makes the capture backend record the
+/// whole screen.
+#[cfg(not(windows))]
+fn find_window(_pid: u32) -> Option<u64> {
+    Some(0)
 }
 
 /// Function that retrieves ProcessInfo with PID
@@ -149,6 +193,7 @@ fn get_processes_info() -> std::io::Result<Vec<ProcessInfo>> {
 /// # Returns
 ///
 /// * `BOOL` - To continue enumeration, the callback function must return TRUE; to stop enumeration, it must return FALSE.
+#[cfg(windows)]
 unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL {
     let data = &mut *(lparam as *mut EnumData);
     let mut pid: DWORD = 0;
@@ -173,6 +218,7 @@ unsafe extern "system" fn enum_windows_proc(hwnd: HWND, lparam: LPARAM) -> BOOL
 /// # Returns
 ///
 /// * `Option<HWND>` - If the function succeeds, the return value is a handle to the window. If no window is associated with the process, the return value is None.
+#[cfg(windows)]
 fn get_hwnd_by_pid(pid: DWORD) -> Option<HWND> {
     let mut data = EnumData {
         target_pid: pid,
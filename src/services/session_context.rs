@@ -0,0 +1,16 @@
+use crate::config::node_config::Config;
+use crate::front_connection::front_protocol::FrontConnection;
+use crate::services::session_state::Session;
+use crate::websocketprotocol::signaling_transport::SignalingTransport;
+
+/// Handles of the node a session runs with, shared by the sender and the receiver.
+pub struct SessionContext<'a> {
+    /// Connection with the intermediary server.
+    pub ws: &'a mut dyn SignalingTransport,
+    /// Connection with the front end, or a headless one.
+    pub front_connection: &'a mut FrontConnection,
+    /// The configuration of the node.
+    pub config: &'a Config,
+    /// State of the session, followed by the front end.
+    pub session: &'a Session,
+}
@@ -0,0 +1,314 @@
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::watch;
+
+use serde::Serialize;
+
+/// Stages a streaming session goes through, on both the sender and the receiver.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub enum SessionState {
+    /// Nothing has started yet.
+    Idle,
+    /// Waiting for the peer and exchanging the session descriptions.
+    Negotiating,
+    /// Descriptions exchanged, waiting for the peer connection to be established.
+    Connecting,
+    /// The peer connection is established and media is flowing.
+    Streaming,
+    /// The session was stopped and its tasks are shutting down.
+    Draining,
+    /// Every task stopped.
+    Ended,
+}
+
+/// Events that move a session from one state to the next.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SessionEvent {
+    /// The session was requested, by the front end or by a client.
+    Start,
+    /// The local and remote descriptions were set.
+    DescriptionsExchanged,
+    /// The peer connection was established for the first time.
+    PeerConnected,
+    /// The session must stop, either normally or because of an error.
+    Stop,
+    /// Every task of the session stopped.
+    Drained,
+}
+
+/// An event that is not valid in the current state.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct InvalidTransition {
+    pub from: SessionState,
+    pub event: SessionEvent,
+}
+
+impl fmt::Display for SessionState {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        fmt::Debug::fmt(self, f)
+    }
+}
+
+impl fmt::Display for InvalidTransition {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "invalid event {:?} in state {}", self.event, self.from)
+    }
+}
+
+impl std::error::Error for InvalidTransition {}
+
+impl SessionState {
+    /// Returns the state reached when `event` happens in this state.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event that happened.
+    ///
+    /// # Returns
+    /// The next state, or an error if the event is not valid in this state.
+    pub fn next(self, event: SessionEvent) -> Result<SessionState, InvalidTransition> {
+        use SessionEvent::*;
+        use SessionState::*;
+
+        match (self, event) {
+            (Idle, Start) => Ok(Negotiating),
+            (Negotiating, DescriptionsExchanged) => Ok(Connecting),
+            (Connecting, PeerConnected) => Ok(Streaming),
+            (Idle | Negotiating | Connecting | Streaming, Stop) => Ok(Draining),
+            (Draining, Drained) => Ok(Ended),
+            (from, event) => Err(InvalidTransition { from, event }),
+        }
+    }
+
+    /// Returns whether the session is stopping or already stopped.
+    pub fn is_finishing(self) -> bool {
+        matches!(self, SessionState::Draining | SessionState::Ended)
+    }
+}
+
+/// Shared state machine of a session.
+///
+/// Transitions are published through a watch channel, so the tasks of the session and the
+/// front end can follow them with [`Session::subscribe`].
+///
+/// A streaming session can also be paused, which stops the media being sent without
+/// ending the session.
+#[derive(Clone)]
+pub struct Session {
+    tx: Arc<watch::Sender<SessionState>>,
+    paused: Arc<AtomicBool>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Session {
+    pub fn new() -> Session {
+        let (tx, _) = watch::channel(SessionState::Idle);
+        Session {
+            tx: Arc::new(tx),
+            paused: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /// Returns the current state.
+    pub fn state(&self) -> SessionState {
+        *self.tx.borrow()
+    }
+
+    /// Applies an event to the session and notifies every subscriber.
+    ///
+    /// # Arguments
+    ///
+    /// * `event` - The event that happened.
+    ///
+    /// # Returns
+    /// The new state, or an error if the event is not valid in the current state. In that
+    /// case the state is left unchanged.
+    pub fn handle(&self, event: SessionEvent) -> Result<SessionState, InvalidTransition> {
+        let mut result = Err(InvalidTransition {
+            from: SessionState::Idle,
+            event,
+        });
+        self.tx.send_if_modified(|state| {
+            result = state.next(event);
+            match result {
+                Ok(next) => {
+                    log::info!("SESSION | {} -> {}", state, next);
+                    *state = next;
+                    true
+                }
+                Err(e) => {
+                    log::warn!("SESSION | {}", e);
+                    false
+                }
+            }
+        });
+        result
+    }
+
+    /// Returns whether the session is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Pauses or resumes the session.
+    ///
+    /// # Arguments
+    ///
+    /// * `paused` - Whether the media must stop being sent.
+    pub fn set_paused(&self, paused: bool) {
+        if self.paused.swap(paused, Ordering::SeqCst) != paused {
+            log::info!("SESSION | Paused: {}", paused);
+        }
+    }
+
+    /// Returns a receiver that follows the state of the session.
+    pub fn subscribe(&self) -> SessionWatcher {
+        SessionWatcher {
+            rx: self.tx.subscribe(),
+            paused: self.paused.clone(),
+        }
+    }
+}
+
+/// Follows the state of a [`Session`].
+#[derive(Clone)]
+pub struct SessionWatcher {
+    rx: watch::Receiver<SessionState>,
+    paused: Arc<AtomicBool>,
+}
+
+impl SessionWatcher {
+    /// Returns the current state.
+    pub fn state(&self) -> SessionState {
+        *self.rx.borrow()
+    }
+
+    /// Returns whether the session is paused.
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Waits for the next state change.
+    ///
+    /// # Returns
+    /// The new state, or `None` if the session was dropped.
+    pub async fn changed(&mut self) -> Option<SessionState> {
+        self.rx.changed().await.ok()?;
+        Some(*self.rx.borrow_and_update())
+    }
+
+    /// Waits until the session starts streaming.
+    ///
+    /// # Returns
+    /// `true` once the session is streaming, `false` if it stops before.
+    pub async fn wait_streaming(&mut self) -> bool {
+        match self
+            .rx
+            .wait_for(|s| *s == SessionState::Streaming || s.is_finishing())
+            .await
+        {
+            Ok(state) => *state == SessionState::Streaming,
+            Err(_) => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use SessionEvent::*;
+    use SessionState::*;
+
+    const STATES: [SessionState; 6] = [Idle, Negotiating, Connecting, Streaming, Draining, Ended];
+    const EVENTS: [SessionEvent; 5] = [Start, DescriptionsExchanged, PeerConnected, Stop, Drained];
+
+    /// Transitions allowed by the state machine, every other pair is rejected.
+    const VALID: [(SessionState, SessionEvent, SessionState); 8] = [
+        (Idle, Start, Negotiating),
+        (Negotiating, DescriptionsExchanged, Connecting),
+        (Connecting, PeerConnected, Streaming),
+        (Idle, Stop, Draining),
+        (Negotiating, Stop, Draining),
+        (Connecting, Stop, Draining),
+        (Streaming, Stop, Draining),
+        (Draining, Drained, Ended),
+    ];
+
+    #[test]
+    fn transition_table() {
+        for from in STATES {
+            for event in EVENTS {
+                let expected = VALID
+                    .iter()
+                    .find(|(f, e, _)| *f == from && *e == event)
+                    .map(|(_, _, to)| *to)
+                    .ok_or(InvalidTransition { from, event });
+                assert_eq!(from.next(event), expected, "{:?} in {}", event, from);
+            }
+        }
+    }
+
+    #[test]
+    fn only_draining_and_ended_are_finishing() {
+        for state in STATES {
+            assert_eq!(state.is_finishing(), matches!(state, Draining | Ended));
+        }
+    }
+
+    #[test]
+    fn invalid_event_leaves_the_state_unchanged() {
+        let session = Session::new();
+        session.handle(Start).unwrap();
+
+        assert_eq!(
+            session.handle(PeerConnected),
+            Err(InvalidTransition {
+                from: Negotiating,
+                event: PeerConnected,
+            })
+        );
+        assert_eq!(session.state(), Negotiating);
+    }
+
+    #[tokio::test]
+    async fn connected_session_is_streaming() {
+        let session = Session::new();
+        let mut watcher = session.subscribe();
+
+        let waiting = tokio::spawn(async move { watcher.wait_streaming().await });
+        session.handle(Start).unwrap();
+        session.handle(DescriptionsExchanged).unwrap();
+        session.handle(PeerConnected).unwrap();
+
+        assert!(waiting.await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn stopped_session_never_streams() {
+        let session = Session::new();
+        let mut watcher = session.subscribe();
+
+        session.handle(Start).unwrap();
+        session.handle(Stop).unwrap();
+
+        assert!(!watcher.wait_streaming().await);
+    }
+
+    #[test]
+    fn pausing_is_seen_by_watchers() {
+        let session = Session::new();
+        let watcher = session.subscribe();
+
+        session.set_paused(true);
+        assert!(watcher.is_paused());
+        session.set_paused(false);
+        assert!(!watcher.is_paused());
+    }
+}
@@ -0,0 +1,537 @@
+use futures_util::{SinkExt, StreamExt};
+use std::collections::HashMap;
+use std::io::{Error, ErrorKind};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::net::{TcpListener, TcpStream};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedSender};
+use tokio::sync::Mutex;
+use tokio::task::JoinHandle;
+use tokio_tungstenite::tungstenite::Message;
+
+use super::server_const::SECONDS_PER_MINUTE;
+use crate::websocketprotocol::signaling_message::SignalingMessage;
+
+/// Local stand-in for the intermediary server.
+///
+/// Routes offerers and clients to each other, relays their SDPs and keeps track of the
+/// sessions started so `notifEndSession` is sent when the minutes run out or when any of
+/// the peers forces the session to stop.
+#[derive(Clone)]
+pub struct SignalingServer {
+    state: Arc<Mutex<ServerState>>,
+    games: Arc<HashMap<String, String>>,
+}
+
+#[derive(Default)]
+struct ServerState {
+    peers: HashMap<String, UnboundedSender<String>>,
+    sessions: Vec<Session>,
+}
+
+struct Session {
+    offerer: String,
+    client: String,
+    timer: JoinHandle<()>,
+}
+
+impl SignalingServer {
+    /// Creates a new server.
+    ///
+    /// # Arguments
+    ///
+    /// * `games` - Maps the game names requested by clients to the path sent to the offerer.
+    ///   Games not present in the map are sent with their name as path.
+    pub fn new(games: HashMap<String, String>) -> SignalingServer {
+        SignalingServer {
+            state: Arc::new(Mutex::new(ServerState::default())),
+            games: Arc::new(games),
+        }
+    }
+
+    /// Accepts websocket connections on the given address until an error occurs.
+    pub async fn run(&self, addr: &str) -> Result<(), Error> {
+        let listener = TcpListener::bind(addr).await?;
+        log::info!("SIGNALING SERVER | Listening on {}", addr);
+        self.serve(listener).await
+    }
+
+    /// Accepts websocket connections on a bound listener until an error occurs.
+    pub async fn serve(&self, listener: TcpListener) -> Result<(), Error> {
+        loop {
+            let (stream, peer_addr) = listener.accept().await?;
+            let server = self.clone();
+            tokio::spawn(async move {
+                if let Err(e) = server.handle_connection(stream).await {
+                    log::error!("SIGNALING SERVER | Connection {} ended: {}", peer_addr, e);
+                }
+            });
+        }
+    }
+
+    /// Reads the messages of a single node and routes them.
+    async fn handle_connection(&self, stream: TcpStream) -> Result<(), Error> {
+        let ws = match tokio_tungstenite::accept_async(stream).await {
+            Ok(ws) => ws,
+            Err(e) => return Err(Error::new(ErrorKind::Other, e)),
+        };
+        let (mut writer, mut reader) = ws.split();
+
+        let (tx, mut rx) = unbounded_channel::<String>();
+        let writer_task = tokio::spawn(async move {
+            while let Some(text) = rx.recv().await {
+                if writer.send(Message::Text(text)).await.is_err() {
+                    break;
+                }
+            }
+        });
+
+        let mut usernames: Vec<String> = Vec::new();
+        while let Some(frame) = reader.next().await {
+            let text = match frame {
+                Ok(Message::Text(text)) => text,
+                Ok(Message::Close(_)) => break,
+                Ok(_) => continue,
+                Err(e) => {
+                    log::warn!("SIGNALING SERVER | Error reading frame: {}", e);
+                    break;
+                }
+            };
+
+            match SignalingMessage::decode(&text) {
+                Ok((_version, msg)) => self.route(msg, &tx, &mut usernames).await,
+                Err(e) => log::warn!("SIGNALING SERVER | Invalid message {:?}: {}", text, e),
+            }
+        }
+
+        writer_task.abort();
+        self.disconnect(&tx, &usernames).await;
+        Ok(())
+    }
+
+    /// Forgets the names registered by a closed connection and ends their sessions.
+    ///
+    /// A name registered again by a newer connection is kept, along with its sessions.
+    ///
+    /// # Arguments
+    ///
+    /// * `tx` - Channel of the closed connection.
+    /// * `usernames` - Names registered by the closed connection.
+    async fn disconnect(&self, tx: &UnboundedSender<String>, usernames: &[String]) {
+        let mut gone = Vec::new();
+        {
+            let mut state = self.state.lock().await;
+            for username in usernames {
+                if state
+                    .peers
+                    .get(username)
+                    .is_some_and(|t| t.same_channel(tx))
+                {
+                    state.peers.remove(username);
+                    gone.push(username);
+                }
+            }
+        }
+
+        for username in gone {
+            while let Some(session) = self.take_session(username).await {
+                log::info!(
+                    "SIGNALING SERVER | {} disconnected, ending its session",
+                    username
+                );
+                self.notify_end(session).await;
+            }
+        }
+    }
+
+    /// Handles a message received from a node.
+    ///
+    /// # Arguments
+    ///
+    /// * `msg` - The message received.
+    /// * `tx` - Channel to write messages back to the node that sent it.
+    /// * `usernames` - Names registered by this connection, removed when it closes.
+    async fn route(
+        &self,
+        msg: SignalingMessage,
+        tx: &UnboundedSender<String>,
+        usernames: &mut Vec<String>,
+    ) {
+        match msg {
+            SignalingMessage::InitOfferer { username } => {
+                log::info!("SIGNALING SERVER | Offerer {} registered", username);
+                self.register(username, tx, usernames).await;
+            }
+            SignalingMessage::InitClient {
+                username,
+                offerer_username,
+                game_name,
+                minutes,
+                trickle,
+            } => {
+                log::info!(
+                    "SIGNALING SERVER | Client {} requests {} from {}",
+                    username,
+                    game_name,
+                    offerer_username
+                );
+                self.register(username.clone(), tx, usernames).await;
+                let game_path = self
+                    .games
+                    .get(&game_name)
+                    .cloned()
+                    .unwrap_or_else(|| game_name.clone());
+                self.send_to(
+                    &offerer_username,
+                    SignalingMessage::SdpRequestFrom {
+                        client_name: username,
+                        game_name,
+                        game_path,
+                        minutes,
+                        trickle,
+                    },
+                )
+                .await;
+            }
+            SignalingMessage::OffererSdp {
+                client_name,
+                sdp,
+                trickle,
+            } => {
+                self.send_to(&client_name, SignalingMessage::SdpOfferer { sdp, trickle })
+                    .await;
+            }
+            SignalingMessage::IceCandidate { peer, candidate } => match usernames.last() {
+                Some(origin) => {
+                    let msg = SignalingMessage::IceCandidate {
+                        peer: origin.to_owned(),
+                        candidate,
+                    };
+                    self.send_to(&peer, msg).await;
+                }
+                None => log::warn!("SIGNALING SERVER | Candidate from unregistered node"),
+            },
+            SignalingMessage::ClientSdp {
+                offerer_username,
+                sdp,
+            } => {
+                self.send_to(&offerer_username, SignalingMessage::SdpClient { sdp })
+                    .await;
+            }
+            SignalingMessage::StartSession {
+                offerer,
+                client,
+                minutes,
+            } => {
+                self.start_session(offerer, client, &minutes).await;
+            }
+            SignalingMessage::ForceStopSession { username } => {
+                log::info!(
+                    "SIGNALING SERVER | {} forced the end of the session",
+                    username
+                );
+                self.end_session(&username).await;
+            }
+            other => {
+                log::warn!(
+                    "SIGNALING SERVER | Unexpected message from node: {}",
+                    other.msg_type()
+                );
+            }
+        }
+    }
+
+    async fn register(
+        &self,
+        username: String,
+        tx: &UnboundedSender<String>,
+        usernames: &mut Vec<String>,
+    ) {
+        let mut state = self.state.lock().await;
+        state.peers.insert(username.clone(), tx.clone());
+        usernames.push(username);
+    }
+
+    /// Sends a message to the node registered with the given name.
+    async fn send_to(&self, username: &str, msg: SignalingMessage) {
+        let state = self.state.lock().await;
+        match state.peers.get(username) {
+            Some(tx) => {
+                if tx.send(msg.encode()).is_err() {
+                    log::warn!("SIGNALING SERVER | {} is no longer connected", username);
+                }
+            }
+            None => log::warn!(
+                "SIGNALING SERVER | Dropping {} message, {} is not connected",
+                msg.msg_type(),
+                username
+            ),
+        }
+    }
+
+    /// Starts the timer of a session. When it expires both peers are notified.
+    async fn start_session(&self, offerer: String, client: String, minutes: &str) {
+        let duration = match minutes
+            .parse::<u64>()
+            .ok()
+            .and_then(|m| m.checked_mul(SECONDS_PER_MINUTE))
+        {
+            Some(seconds) => Duration::from_secs(seconds),
+            None => {
+                log::error!("SIGNALING SERVER | Invalid session minutes: {}", minutes);
+                return;
+            }
+        };
+        log::info!(
+            "SIGNALING SERVER | Session {} -> {} started for {} minutes",
+            offerer,
+            client,
+            minutes
+        );
+
+        let server = self.clone();
+        let offerer_cpy = offerer.clone();
+        let timer = tokio::spawn(async move {
+            tokio::time::sleep(duration).await;
+            log::info!(
+                "SIGNALING SERVER | Session of {} ran out of time",
+                offerer_cpy
+            );
+            server.end_session(&offerer_cpy).await;
+        });
+
+        let mut state = self.state.lock().await;
+        state.sessions.push(Session {
+            offerer,
+            client,
+            timer,
+        });
+    }
+
+    /// Ends the session in which `username` takes part, notifying both peers.
+    async fn end_session(&self, username: &str) {
+        match self.take_session(username).await {
+            Some(session) => self.notify_end(session).await,
+            None => log::warn!("SIGNALING SERVER | No session found for {}", username),
+        }
+    }
+
+    /// Removes the first session in which `username` takes part.
+    async fn take_session(&self, username: &str) -> Option<Session> {
+        let mut state = self.state.lock().await;
+        let i = state
+            .sessions
+            .iter()
+            .position(|s| s.offerer == username || s.client == username)?;
+        Some(state.sessions.remove(i))
+    }
+
+    /// Sends `notifEndSession` to both peers of a session and stops its timer.
+    async fn notify_end(&self, session: Session) {
+        self.send_to(&session.offerer, SignalingMessage::NotifEndSession)
+            .await;
+        self.send_to(&session.client, SignalingMessage::NotifEndSession)
+            .await;
+        session.timer.abort();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tokio::net::TcpStream;
+    use tokio::time::timeout;
+    use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+    type Client = WebSocketStream<MaybeTlsStream<TcpStream>>;
+
+    const DEADLINE: Duration = Duration::from_secs(5);
+
+    /// Starts a server on a free port.
+    async fn start_server() -> (SignalingServer, String) {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let url = format!("ws://{}", listener.local_addr().unwrap());
+        let server = SignalingServer::new(HashMap::from([(
+            "game".to_owned(),
+            "C:\\games\\game.exe".to_owned(),
+        )]));
+        let server_cpy = server.clone();
+        tokio::spawn(async move { server_cpy.serve(listener).await });
+        (server, url)
+    }
+
+    async fn send(client: &mut Client, msg: SignalingMessage) {
+        client.send(Message::Text(msg.encode())).await.unwrap();
+    }
+
+    async fn receive(client: &mut Client) -> SignalingMessage {
+        loop {
+            let frame = timeout(DEADLINE, client.next())
+                .await
+                .expect("no message received")
+                .unwrap()
+                .unwrap();
+            if let Message::Text(text) = frame {
+                return SignalingMessage::decode(&text).unwrap().1;
+            }
+        }
+    }
+
+    /// Connects a node and waits until the server knows its name.
+    async fn register(server: &SignalingServer, url: &str, msg: SignalingMessage) -> Client {
+        let username = match &msg {
+            SignalingMessage::InitOfferer { username } => username.clone(),
+            SignalingMessage::InitClient { username, .. } => username.clone(),
+            other => panic!("{} does not register a node", other.msg_type()),
+        };
+        let (mut client, _) = connect_async(url).await.unwrap();
+        send(&mut client, msg).await;
+        wait_until(server, |state| state.peers.contains_key(&username)).await;
+        client
+    }
+
+    async fn wait_until(server: &SignalingServer, done: impl Fn(&ServerState) -> bool) {
+        timeout(DEADLINE, async {
+            while !done(&*server.state.lock().await) {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("the server did not reach the expected state");
+    }
+
+    fn init_offerer() -> SignalingMessage {
+        SignalingMessage::InitOfferer {
+            username: "offerer".to_owned(),
+        }
+    }
+
+    fn init_client() -> SignalingMessage {
+        SignalingMessage::InitClient {
+            username: "client".to_owned(),
+            offerer_username: "offerer".to_owned(),
+            game_name: "game".to_owned(),
+            minutes: "30".to_owned(),
+            trickle: true,
+        }
+    }
+
+    /// Registers both peers, exchanges their descriptions and starts a session.
+    async fn start_session(server: &SignalingServer, url: &str) -> (Client, Client) {
+        let mut offerer = register(server, url, init_offerer()).await;
+        let mut client = register(server, url, init_client()).await;
+
+        assert_eq!(
+            receive(&mut offerer).await,
+            SignalingMessage::SdpRequestFrom {
+                client_name: "client".to_owned(),
+                game_name: "game".to_owned(),
+                game_path: "C:\\games\\game.exe".to_owned(),
+                minutes: "30".to_owned(),
+                trickle: true,
+            }
+        );
+        let offer = SignalingMessage::OffererSdp {
+            client_name: "client".to_owned(),
+            sdp: "offer sdp".to_owned(),
+            trickle: true,
+        };
+        send(&mut offerer, offer).await;
+        assert_eq!(
+            receive(&mut client).await,
+            SignalingMessage::SdpOfferer {
+                sdp: "offer sdp".to_owned(),
+                trickle: true,
+            }
+        );
+        let answer = SignalingMessage::ClientSdp {
+            offerer_username: "offerer".to_owned(),
+            sdp: "answer sdp".to_owned(),
+        };
+        send(&mut client, answer).await;
+        assert_eq!(
+            receive(&mut offerer).await,
+            SignalingMessage::SdpClient {
+                sdp: "answer sdp".to_owned(),
+            }
+        );
+
+        let start = SignalingMessage::StartSession {
+            offerer: "offerer".to_owned(),
+            client: "client".to_owned(),
+            minutes: "30".to_owned(),
+        };
+        send(&mut offerer, start).await;
+        wait_until(server, |state| state.sessions.len() == 1).await;
+        (offerer, client)
+    }
+
+    #[tokio::test]
+    async fn forced_stop_notifies_both_peers() {
+        let (server, url) = start_server().await;
+        let (mut offerer, mut client) = start_session(&server, &url).await;
+
+        let stop = SignalingMessage::ForceStopSession {
+            username: "client".to_owned(),
+        };
+        send(&mut client, stop).await;
+        assert_eq!(
+            receive(&mut offerer).await,
+            SignalingMessage::NotifEndSession
+        );
+        assert_eq!(
+            receive(&mut client).await,
+            SignalingMessage::NotifEndSession
+        );
+        wait_until(&server, |state| state.sessions.is_empty()).await;
+    }
+
+    #[tokio::test]
+    async fn closed_connection_ends_its_session() {
+        let (server, url) = start_server().await;
+        let (mut offerer, mut client) = start_session(&server, &url).await;
+
+        client.close(None).await.unwrap();
+        assert_eq!(
+            receive(&mut offerer).await,
+            SignalingMessage::NotifEndSession
+        );
+        wait_until(&server, |state| {
+            state.sessions.is_empty() && !state.peers.contains_key("client")
+        })
+        .await;
+    }
+
+    #[tokio::test]
+    async fn closed_connection_keeps_a_newer_registration() {
+        let (server, url) = start_server().await;
+        let mut old = register(&server, &url, init_offerer()).await;
+        let other = SignalingMessage::InitOfferer {
+            username: "other".to_owned(),
+        };
+        send(&mut old, other).await;
+        wait_until(&server, |state| state.peers.contains_key("other")).await;
+        let mut new = register(&server, &url, init_offerer()).await;
+
+        // Only the name that was not registered again is forgotten
+        old.close(None).await.unwrap();
+        wait_until(&server, |state| !state.peers.contains_key("other")).await;
+        assert!(server.state.lock().await.peers.contains_key("offerer"));
+
+        let _client = register(&server, &url, init_client()).await;
+        assert!(matches!(
+            receive(&mut new).await,
+            SignalingMessage::SdpRequestFrom { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn minutes_that_overflow_are_rejected() {
+        let (server, _) = start_server().await;
+        let minutes = u64::MAX.to_string();
+        server
+            .start_session("offerer".to_owned(), "client".to_owned(), &minutes)
+            .await;
+        assert!(server.state.lock().await.sessions.is_empty());
+    }
+}
@@ -0,0 +1,2 @@
+pub const DEFAULT_SERVER_ADDR: &str = "127.0.0.1:8080";
+pub const SECONDS_PER_MINUTE: u64 = 60;
@@ -2,22 +2,22 @@ use std::collections::HashMap;
 
 use gstreamer::{glib, Element};
 
+use super::audio_const::{PIPEWIRE_MONITOR_PROPERTIES, PULSE_MONITOR_DEVICE};
+
 /// Creates GStreamer elements required for audio capture pipeline.
 ///
+/// # Arguments
+///
+/// * `source` - The element that produces the raw audio, see the `*_source` functions.
+///
 /// # Returns
 /// A Result containing:
 /// * A `HashMap` of Gstreamer elements in case of success.
 /// * A `glib::BoolError` in case of error
-pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+pub fn create_elements(source: Element) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
     let mut elements = HashMap::new();
 
     // Create the elements
-    let wasapi2src = gstreamer::ElementFactory::make("wasapi2src")
-        .name("wasapi2src")
-        .property("loopback", true)
-        .property("low-latency", true)
-        .build()?;
-
     let queue = gstreamer::ElementFactory::make("queue")
         .name("audio_capture_queue")
         .build()?;
@@ -38,7 +38,7 @@ pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolErr
         .name("rtpopuspay")
         .build()?;
 
-    elements.insert("src", wasapi2src);
+    elements.insert("src", source);
     elements.insert("queue", queue);
     elements.insert("convert", audioconvert);
     elements.insert("sample", audioresample);
@@ -47,3 +47,37 @@ pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolErr
 
     Ok(elements)
 }
+
+/// Creates a WASAPI source that captures the audio played by the system, on Windows.
+pub fn wasapi_source() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("wasapi2src")
+        .name("wasapi2src")
+        .property("loopback", true)
+        .property("low-latency", true)
+        .build()
+}
+
+/// Creates a PulseAudio source that captures the audio played by the system.
+pub fn pulse_source() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("pulsesrc")
+        .name("pulsesrc")
+        .property("device", PULSE_MONITOR_DEVICE)
+        .build()
+}
+
+/// Creates a PipeWire source that captures the audio played by the system.
+pub fn pipewire_source() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("pipewiresrc")
+        .name("audio_pipewiresrc")
+        .property("do-timestamp", true)
+        .property_from_str("stream-properties", PIPEWIRE_MONITOR_PROPERTIES)
+        .build()
+}
+
+/// Creates a live test tone source, used to test the pipeline without a sound card.
+pub fn test_source() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("audiotestsrc")
+        .name("audiotestsrc")
+        .property("is-live", true)
+        .build()
+}
@@ -1,6 +1,10 @@
 // AUDIO CAPTURE CONSTANTS
 pub const AUDIO_CAPTURE_PIPELINE_NAME: &str = "AUDIO CAPTURE";
 pub const CAPS_CHANNELS_AMOUNT: u8 = 2;
+// Monitor of the default PulseAudio sink, which carries the audio played by the system
+pub const PULSE_MONITOR_DEVICE: &str = "@DEFAULT_MONITOR@";
+// Makes pipewiresrc record the output of the default sink instead of a microphone
+pub const PIPEWIRE_MONITOR_PROPERTIES: &str = "props,stream.capture.sink=true";
 
 //AUDIO PLAYBACK CONSTANTS
 pub const AUDIO_PLAYER_PIPELINE_NAME: &str = "AUDIO PLAYER";
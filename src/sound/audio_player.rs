@@ -4,10 +4,14 @@ use gstreamer::{glib, Element};
 
 /// Creates the elements for the audio player pipeline.
 ///
+/// # Arguments
+///
+/// * `sink` - The element that plays the audio, see the `*_sink` functions.
+///
 /// # Returns
 ///
 /// A Result containing a HashMap with the elements if the operation was successful, otherwise an Error is returned.
-pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+pub fn create_elements(sink: Element) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
     let mut elements = HashMap::new();
 
     let queue = gstreamer::ElementFactory::make("queue")
@@ -34,17 +38,27 @@ pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolErr
         .name("audioresample")
         .build()?;
 
-    let autoaudiosink = gstreamer::ElementFactory::make("autoaudiosink")
-        .name("autoaudiosink")
-        .build()?;
-
     elements.insert("queue", queue);
     elements.insert("depay", rtpopusdepay);
     elements.insert("parse", opusparse);
     elements.insert("dec", opusdec);
     elements.insert("convert", audioconvert);
     elements.insert("sample", audioresample);
-    elements.insert("sink", autoaudiosink);
+    elements.insert("sink", sink);
 
     Ok(elements)
 }
+
+/// Creates a sink that picks the audio output of the platform.
+pub fn auto_sink() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("autoaudiosink")
+        .name("autoaudiosink")
+        .build()
+}
+
+/// Creates a sink that drops the audio, used to run the receiver without a sound card.
+pub fn fake_sink() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("fakesink")
+        .name("audio_fakesink")
+        .build()
+}
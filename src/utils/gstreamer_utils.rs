@@ -1,4 +1,4 @@
-use crate::utils::shutdown::{self};
+use crate::utils::supervisor::Task;
 use gstreamer::{prelude::*, Pipeline};
 use gstreamer_app::{AppSink, AppSrc};
 use std::{
@@ -12,22 +12,19 @@ use tokio::{runtime::Runtime, sync::mpsc::Sender};
 /// # Arguments
 ///
 /// * `pipeline` - A gstreamer pipeline
-/// * `shutdown` - A shutdown handle used for graceful shutdown
-pub async fn read_bus(pipeline: Pipeline, shutdown: &mut shutdown::Shutdown) {
+/// * `task` - Handle of the supervised task, used for graceful shutdown
+pub async fn read_bus(pipeline: Pipeline, task: Task) {
     // Wait until error or EOS
     let pipeline_name = pipeline.name();
 
     let bus = match pipeline.bus() {
         Some(b) => b,
         None => {
-            shutdown
-                .notify_error(false, "read bus gstreamer utils")
-                .await;
+            task.fail("Pipeline bus not found");
             log::error!("{pipeline_name} | Pipeline bus not found");
             return;
         }
     };
-    shutdown.add_task("Read bus").await;
 
     for msg in bus.iter_timed(gstreamer::ClockTime::NONE) {
         use gstreamer::MessageView;
@@ -38,9 +35,7 @@ pub async fn read_bus(pipeline: Pipeline, shutdown: &mut shutdown::Shutdown) {
                     err.src().map(|s| s.path_string()),
                     err.error()
                 );
-                shutdown
-                    .notify_error(false, "error from element gutils")
-                    .await;
+                task.fail("Error received from pipeline element");
                 break;
             }
             MessageView::StateChanged(state_changed) => {
@@ -50,8 +45,8 @@ pub async fn read_bus(pipeline: Pipeline, shutdown: &mut shutdown::Shutdown) {
                         state_changed.old(),
                         state_changed.current()
                     );
-                    if shutdown.check_for_error().await {
-                        println!("Enter in check for error");
+                    if task.is_cancelled() {
+                        log::info!("{pipeline_name} | Read bus cancelled");
                         break;
                     }
                 }
@@ -0,0 +1,81 @@
+/// Positional fields of a pipe-delimited message, the ones after its type.
+///
+/// Shared by the front end commands and the signaling messages, so both report missing
+/// fields the same way.
+pub(crate) struct Fields<'a> {
+    kind: &'static str,
+    parts: &'a [&'a str],
+}
+
+/// A field the message requires is not present.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct MissingField {
+    /// Type of the message, `unknown` if it is not part of the protocol.
+    pub kind: &'static str,
+    /// Name of the missing field.
+    pub field: &'static str,
+}
+
+impl<'a> Fields<'a> {
+    /// # Arguments
+    ///
+    /// * `kind` - Type of the message, the first part of the line.
+    /// * `known` - Every type of the protocol, so the type can be kept as `'static`.
+    /// * `parts` - The parts of the line after the type.
+    pub(crate) fn new(kind: &str, known: &[&'static str], parts: &'a [&'a str]) -> Self {
+        Fields {
+            kind: known_kind(kind, known),
+            parts,
+        }
+    }
+
+    /// Returns the type of the message, `unknown` if it is not part of the protocol.
+    pub(crate) fn kind(&self) -> &'static str {
+        self.kind
+    }
+
+    /// Returns the field at `index`, which may be empty.
+    pub(crate) fn get(&self, index: usize, field: &'static str) -> Result<String, MissingField> {
+        match self.parts.get(index) {
+            Some(value) => Ok(value.to_string()),
+            None => Err(MissingField {
+                kind: self.kind,
+                field,
+            }),
+        }
+    }
+
+    /// Returns a field that may be left out, `None` if it is missing or empty.
+    pub(crate) fn optional(&self, index: usize) -> Option<&'a str> {
+        self.parts
+            .get(index)
+            .copied()
+            .filter(|value| !value.trim().is_empty())
+    }
+
+    /// Returns the field at `index` joined with every field after it. Used for payloads
+    /// that may contain the separator.
+    pub(crate) fn rest(
+        &self,
+        index: usize,
+        field: &'static str,
+        separator: char,
+    ) -> Result<String, MissingField> {
+        self.get(index, field)?;
+        Ok(self.parts[index..].join(&separator.to_string()))
+    }
+
+    /// Returns whether the optional field at `index` is the given flag.
+    pub(crate) fn flag(&self, index: usize, flag: &str) -> bool {
+        self.parts.get(index) == Some(&flag)
+    }
+}
+
+/// Maps a message type to its `'static` constant so it can be stored in an error.
+fn known_kind(kind: &str, known: &[&'static str]) -> &'static str {
+    known
+        .iter()
+        .copied()
+        .find(|k| *k == kind)
+        .unwrap_or("unknown")
+}
@@ -1,6 +1,9 @@
-pub mod common_utils;
 pub mod error_tracker;
 pub mod gstreamer_utils;
 pub mod latency_const;
-pub mod shutdown;
+pub mod message_fields;
+pub mod name_parse;
+pub mod node_error;
+pub mod supervisor;
+pub mod supervisor_const;
 pub mod webrtc_const;
@@ -0,0 +1,14 @@
+use serde::de::{value, IntoDeserializer};
+use serde::Deserialize;
+
+/// Parses the names used in the configuration file, so overrides accept the same values.
+///
+/// # Arguments
+///
+/// * `name` - The name of a unit variant, as written in the configuration file.
+///
+/// # Returns
+/// The variant, or an error listing the accepted names.
+pub(crate) fn from_name<T: for<'de> Deserialize<'de>>(name: &str) -> Result<T, value::Error> {
+    T::deserialize(name.into_deserializer())
+}
@@ -0,0 +1,113 @@
+use std::fmt;
+use std::io;
+
+use crate::config::node_config::ConfigError;
+
+/// Boxed error returned by GStreamer, which uses several error types.
+pub type GstError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Every failure that can end a session, grouped by the part of the node it comes from.
+///
+/// Each variant keeps the error that caused it. Errors from libraries that do not say
+/// which step failed also carry a short context.
+#[derive(Debug)]
+pub enum NodeError {
+    /// The intermediary server could not be reached or sent an unexpected message.
+    Signaling(io::Error),
+    /// The WebRTC peer connection failed.
+    WebRtc(&'static str, webrtc::Error),
+    /// A GStreamer pipeline could not be built or run.
+    Gstreamer(&'static str, GstError),
+    /// The game could not be started or its window was not found.
+    GameLaunch(io::Error),
+    /// The connection with the front end failed.
+    FrontProtocol(io::Error),
+    /// Input could not be captured or injected.
+    Input(io::Error),
+    /// The configuration is not valid.
+    Config(ConfigError),
+    /// Any other I/O failure.
+    Io(io::Error),
+}
+
+impl NodeError {
+    /// Returns a stable identifier of the kind of failure, meant to be read by the front end.
+    pub fn code(&self) -> &'static str {
+        match self {
+            NodeError::Signaling(_) => "signaling",
+            NodeError::WebRtc(..) => "webrtc",
+            NodeError::Gstreamer(..) => "gstreamer",
+            NodeError::GameLaunch(_) => "game_launch",
+            NodeError::FrontProtocol(_) => "front_protocol",
+            NodeError::Input(_) => "input",
+            NodeError::Config(_) => "config",
+            NodeError::Io(_) => "io",
+        }
+    }
+
+    /// Builds a GStreamer error from any of the GStreamer error types.
+    pub fn gstreamer(context: &'static str, e: impl Into<GstError>) -> NodeError {
+        NodeError::Gstreamer(context, e.into())
+    }
+}
+
+impl fmt::Display for NodeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NodeError::Signaling(e) => write!(f, "signaling error: {e}"),
+            NodeError::WebRtc(context, e) => write!(f, "webrtc error: {context}: {e}"),
+            NodeError::Gstreamer(context, e) => write!(f, "gstreamer error: {context}: {e}"),
+            NodeError::GameLaunch(e) => write!(f, "game launch error: {e}"),
+            NodeError::FrontProtocol(e) => write!(f, "front protocol error: {e}"),
+            NodeError::Input(e) => write!(f, "input error: {e}"),
+            NodeError::Config(e) => write!(f, "configuration error: {e}"),
+            NodeError::Io(e) => write!(f, "io error: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for NodeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            NodeError::Signaling(e)
+            | NodeError::GameLaunch(e)
+            | NodeError::FrontProtocol(e)
+            | NodeError::Input(e)
+            | NodeError::Io(e) => Some(e),
+            NodeError::WebRtc(_, e) => Some(e),
+            NodeError::Gstreamer(_, e) => Some(e.as_ref()),
+            NodeError::Config(e) => Some(e),
+        }
+    }
+}
+
+impl From<io::Error> for NodeError {
+    fn from(e: io::Error) -> Self {
+        NodeError::Io(e)
+    }
+}
+
+impl From<serde_json::Error> for NodeError {
+    fn from(e: serde_json::Error) -> Self {
+        NodeError::Io(e.into())
+    }
+}
+
+impl From<ConfigError> for NodeError {
+    fn from(e: ConfigError) -> Self {
+        NodeError::Config(e)
+    }
+}
+
+impl From<NodeError> for io::Error {
+    fn from(e: NodeError) -> Self {
+        match e {
+            NodeError::Signaling(e)
+            | NodeError::GameLaunch(e)
+            | NodeError::FrontProtocol(e)
+            | NodeError::Input(e)
+            | NodeError::Io(e) => e,
+            other => io::Error::new(io::ErrorKind::Other, other),
+        }
+    }
+}
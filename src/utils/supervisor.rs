@@ -0,0 +1,392 @@
+use futures_util::FutureExt;
+use std::any::Any;
+use std::fmt;
+use std::future::Future;
+use std::panic::AssertUnwindSafe;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::task::JoinHandle;
+use tokio::time::{timeout_at, Instant};
+use tokio_util::sync::CancellationToken;
+
+/// A failure reported to the supervisor.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Failure {
+    /// Name of the task, or component, that failed.
+    pub task: String,
+    /// Why it failed.
+    pub reason: String,
+}
+
+/// Outcome of shutting down the supervised tasks.
+#[derive(Debug, Default)]
+pub struct ShutdownReport {
+    /// First failure reported, `None` if the session was stopped normally.
+    pub failure: Option<Failure>,
+    /// Tasks that did not stop before the deadline and were aborted.
+    pub unfinished: Vec<String>,
+}
+
+impl ShutdownReport {
+    /// Returns why the session stopped: `stopped` if it ended normally, otherwise the
+    /// task that failed and its reason.
+    pub fn reason(&self) -> String {
+        match &self.failure {
+            Some(failure) => format!("{}: {}", failure.task, failure.reason),
+            None => "stopped".to_string(),
+        }
+    }
+}
+
+impl fmt::Display for ShutdownReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match &self.failure {
+            Some(failure) => write!(f, "stopped by {}: {}", failure.task, failure.reason)?,
+            None => write!(f, "stopped normally")?,
+        }
+        if !self.unfinished.is_empty() {
+            write!(
+                f,
+                " | tasks not stopped in time: {}",
+                self.unfinished.join(", ")
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct State {
+    tasks: Vec<(String, JoinHandle<()>)>,
+    failure: Option<Failure>,
+}
+
+/// Owns the tasks of a session and stops all of them when one fails.
+///
+/// Every task receives a [`Task`] handle that shares a cancellation token. Tasks can be
+/// spawned at any moment: one spawned after the session was cancelled sees the token
+/// already cancelled and stops right away.
+#[derive(Clone)]
+pub struct Supervisor {
+    token: CancellationToken,
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for Supervisor {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self {
+            token: CancellationToken::new(),
+            state: Arc::new(Mutex::new(State {
+                tasks: Vec::new(),
+                failure: None,
+            })),
+        }
+    }
+
+    /// Spawns a named task owned by the supervisor.
+    ///
+    /// A task that panics is reported as failed, which cancels every other task.
+    ///
+    /// # Arguments
+    ///
+    /// * `name` - Name used to report failures and tasks that do not stop.
+    /// * `task` - Builds the future to run from its [`Task`] handle.
+    pub fn spawn<F, Fut>(&self, name: &str, task: F)
+    where
+        F: FnOnce(Task) -> Fut,
+        Fut: Future<Output = ()> + Send + 'static,
+    {
+        let future = AssertUnwindSafe(task(self.task(name))).catch_unwind();
+        let supervisor = self.clone();
+        let name_cpy = name.to_owned();
+        let handle = tokio::spawn(async move {
+            if let Err(payload) = future.await {
+                let reason = format!("task panicked: {}", panic_message(payload.as_ref()));
+                supervisor.fail(&name_cpy, &reason);
+            }
+        });
+        self.lock().tasks.push((name.to_owned(), handle));
+    }
+
+    /// Returns a handle for work that is not spawned by the supervisor, such as callbacks.
+    pub fn task(&self, name: &str) -> Task {
+        Task {
+            name: name.to_owned(),
+            supervisor: self.clone(),
+        }
+    }
+
+    /// Records a failure and cancels every task.
+    ///
+    /// Only the first failure is kept as the cause of the shutdown, later ones are logged.
+    pub fn fail(&self, task: &str, reason: &str) {
+        {
+            let mut state = self.lock();
+            match &state.failure {
+                None => {
+                    log::error!("SUPERVISOR | {} failed | {}", task, reason);
+                    state.failure = Some(Failure {
+                        task: task.to_owned(),
+                        reason: reason.to_owned(),
+                    });
+                }
+                Some(first) => {
+                    log::warn!(
+                        "SUPERVISOR | {} failed after {} | {}",
+                        task,
+                        first.task,
+                        reason
+                    );
+                }
+            }
+        }
+        self.token.cancel();
+    }
+
+    /// Cancels every task without reporting a failure.
+    pub fn stop(&self) {
+        log::info!("SUPERVISOR | Stop requested");
+        self.token.cancel();
+    }
+
+    /// Completes when the session is cancelled.
+    pub async fn cancelled(&self) {
+        self.token.cancelled().await
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.token.is_cancelled()
+    }
+
+    /// Returns the first failure reported, if any.
+    pub fn failure(&self) -> Option<Failure> {
+        self.lock().failure.clone()
+    }
+
+    /// Cancels every task and waits for them to finish.
+    ///
+    /// # Arguments
+    ///
+    /// * `deadline` - Time the tasks have to stop. Tasks still running after it are aborted.
+    ///
+    /// # Returns
+    /// A report with the cause of the shutdown and the tasks that did not stop in time.
+    pub async fn shutdown(&self, deadline: Duration) -> ShutdownReport {
+        self.token.cancel();
+        let deadline = Instant::now() + deadline;
+        let mut unfinished = Vec::new();
+
+        // Tasks may spawn other tasks while stopping, keep going until none is left
+        loop {
+            let tasks = std::mem::take(&mut self.lock().tasks);
+            if tasks.is_empty() {
+                break;
+            }
+            for (name, mut handle) in tasks {
+                match timeout_at(deadline, &mut handle).await {
+                    Ok(_) => {}
+                    Err(_) => {
+                        log::error!("SUPERVISOR | {} did not stop in time", name);
+                        handle.abort();
+                        unfinished.push(name);
+                    }
+                }
+            }
+        }
+
+        ShutdownReport {
+            failure: self.failure(),
+            unfinished,
+        }
+    }
+
+    fn lock(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+/// Returns the message a panic was raised with.
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "unknown panic"
+    }
+}
+
+/// Handle given to a supervised task.
+#[derive(Clone)]
+pub struct Task {
+    name: String,
+    supervisor: Supervisor,
+}
+
+impl Task {
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Completes when the session is cancelled.
+    pub async fn cancelled(&self) {
+        self.supervisor.cancelled().await
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.supervisor.is_cancelled()
+    }
+
+    /// Reports that this task failed, which cancels every other task.
+    pub fn fail(&self, reason: &str) {
+        self.supervisor.fail(&self.name, reason)
+    }
+
+    /// Returns the supervisor that owns this task, used to spawn sibling tasks.
+    pub fn supervisor(&self) -> &Supervisor {
+        &self.supervisor
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    const DEADLINE: Duration = Duration::from_millis(200);
+
+    #[tokio::test]
+    async fn task_spawned_after_cancelling_stops_right_away() {
+        let supervisor = Supervisor::new();
+        supervisor.stop();
+
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_cpy = stopped.clone();
+        supervisor.spawn("Late", move |task| async move {
+            task.cancelled().await;
+            stopped_cpy.store(true, Ordering::SeqCst);
+        });
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert!(stopped.load(Ordering::SeqCst));
+        assert!(report.unfinished.is_empty());
+        assert_eq!(report.failure, None);
+    }
+
+    #[tokio::test]
+    async fn tasks_spawned_while_shutting_down_are_awaited() {
+        let supervisor = Supervisor::new();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_cpy = stopped.clone();
+
+        supervisor.spawn("Parent", move |task| async move {
+            task.cancelled().await;
+            task.supervisor().spawn("Child", move |task| async move {
+                task.cancelled().await;
+                tokio::time::sleep(Duration::from_millis(10)).await;
+                stopped_cpy.store(true, Ordering::SeqCst);
+            });
+        });
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert!(stopped.load(Ordering::SeqCst));
+        assert!(report.unfinished.is_empty());
+    }
+
+    #[tokio::test]
+    async fn first_failure_is_the_cause() {
+        let supervisor = Supervisor::new();
+        let task = supervisor.task("Capture");
+
+        task.fail("Pipeline error");
+        supervisor.fail("Sender", "Connection lost");
+        assert!(task.is_cancelled());
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert_eq!(
+            report.failure,
+            Some(Failure {
+                task: "Capture".to_owned(),
+                reason: "Pipeline error".to_owned(),
+            })
+        );
+        assert_eq!(report.reason(), "Capture: Pipeline error");
+    }
+
+    #[tokio::test]
+    async fn failure_cancels_every_task() {
+        let supervisor = Supervisor::new();
+        supervisor.spawn("Failing", |task| async move { task.fail("Broken") });
+        supervisor.spawn("Waiting", |task| async move { task.cancelled().await });
+
+        tokio::time::timeout(DEADLINE, supervisor.cancelled())
+            .await
+            .expect("the failure did not cancel the session");
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert!(report.unfinished.is_empty());
+        assert_eq!(report.failure.map(|f| f.task), Some("Failing".to_owned()));
+    }
+
+    #[tokio::test]
+    async fn task_that_ignores_the_shutdown_is_aborted() {
+        let supervisor = Supervisor::new();
+        supervisor.spawn("Stopping", |task| async move { task.cancelled().await });
+        supervisor.spawn("Hanging", |_| std::future::pending::<()>());
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert_eq!(report.unfinished, vec!["Hanging".to_owned()]);
+        assert_eq!(report.failure, None);
+        assert_eq!(
+            report.to_string(),
+            "stopped normally | tasks not stopped in time: Hanging"
+        );
+    }
+
+    #[tokio::test]
+    async fn panicking_task_is_reported_as_failed() {
+        let supervisor = Supervisor::new();
+        supervisor.spawn("Panicking", |_| async {
+            panic!("task panicked on purpose")
+        });
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert_eq!(
+            report.failure,
+            Some(Failure {
+                task: "Panicking".to_owned(),
+                reason: "task panicked: task panicked on purpose".to_owned(),
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn panic_cancels_the_other_tasks_right_away() {
+        let supervisor = Supervisor::new();
+        let stopped = Arc::new(AtomicBool::new(false));
+        let stopped_cpy = stopped.clone();
+
+        supervisor.spawn("Waiting", move |task| async move {
+            task.cancelled().await;
+            stopped_cpy.store(true, Ordering::SeqCst);
+        });
+        supervisor.spawn("Panicking", |_| async {
+            panic!("task panicked on purpose")
+        });
+
+        tokio::time::timeout(DEADLINE, supervisor.cancelled())
+            .await
+            .expect("the panic did not cancel the session");
+        assert!(supervisor.failure().is_some());
+
+        let report = supervisor.shutdown(DEADLINE).await;
+        assert!(stopped.load(Ordering::SeqCst));
+        assert!(report.unfinished.is_empty());
+        assert_eq!(report.failure.map(|f| f.task), Some("Panicking".to_owned()));
+    }
+}
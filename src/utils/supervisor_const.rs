@@ -0,0 +1,2 @@
+// Seconds the tasks of a session have to stop once it is cancelled
+pub const SHUTDOWN_DEADLINE: u64 = 5;
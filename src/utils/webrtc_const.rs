@@ -3,10 +3,18 @@ pub const ENCODE_BUFFER_SIZE: usize = 960;
 pub const STREAM_TRACK_ID: &str = "webrtc-rs";
 pub const STUN_ADRESS: &str = "stun:stun.l.google.com:19302";
 pub const TURN_ADRESS: &str = "turn:ec2-18-230-20-253.sa-east-1.compute.amazonaws.com";
+// Lifetime of the TURN REST credentials generated for each session, in seconds
+pub const TURN_CREDENTIAL_TTL: u64 = 86400;
+// User sent in the TURN REST credentials when no turn_user is configured
+pub const TURN_REST_DEFAULT_USER: &str = "cgrs";
 
-//TODO: ocultar credenciales
-pub const TURN_USER: &str = "username1";
-pub const TURN_PASS: &str = "key1";
+// Seconds the peers have to recover a lost connection before an ICE restart is sent
+pub const RECONNECT_GRACE_PERIOD: u64 = 3;
+// Seconds after which a lost connection ends the session
+pub const RECONNECT_TIMEOUT: u64 = 30;
+
+// Seconds between the stream statistics sent to the front end
+pub const STREAM_STATS_INTERVAL: u64 = 5;
 
 // AUDIO
 pub const AUDIO_SAMPLE_RATE: u32 = 48000;
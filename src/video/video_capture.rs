@@ -1,17 +1,23 @@
-use gstreamer::{glib, Element};
+use gstreamer::{glib, prelude::*, Element};
 
 use std::collections::HashMap;
 
-use super::video_const::ENCODER_BITRATE;
+use super::video_const::X264_KEY_INT_MAX;
 
 /// Creates GStreamer elements required for the video capture pipeline.
 ///
+/// # Arguments
+///
+/// * `window_handle` - The handle of the window to capture.
+/// * `bitrate` - The bitrate of the encoder in kbit/sec.
+///
 /// # Returns
 ///  A Result containing:
 /// * A `HashMap` of Gstreamer elements in case of success.
 /// * A `glib::BoolError` in case of error
 pub fn create_elements(
     window_handle: u64,
+    bitrate: u32,
 ) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
     let mut elements = HashMap::new();
     // Create the elements
@@ -36,7 +42,7 @@ pub fn create_elements(
         .property_from_str("usage", "ultra-low-latency")
         .property(
             "bitrate",
-            <gstreamer::glib::Value as From<u32>>::from(ENCODER_BITRATE),
+            <gstreamer::glib::Value as From<u32>>::from(bitrate),
         )
         .build()
     {
@@ -61,3 +67,102 @@ pub fn create_elements(
 
     Ok(elements)
 }
+
+/// Creates GStreamer elements for the video capture pipeline encoded in software with
+/// `x264enc`, used where no hardware encoder is available.
+///
+/// # Arguments
+///
+/// * `source` - The element that produces the raw video, see the `*_source` functions.
+/// * `bitrate` - The bitrate of the encoder in kbit/sec.
+///
+/// # Returns
+///  A Result containing:
+/// * A `HashMap` of Gstreamer elements in case of success.
+/// * A `glib::BoolError` in case of error
+pub fn create_software_elements(
+    source: Element,
+    bitrate: u32,
+) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+    let mut elements = HashMap::new();
+
+    let queue = gstreamer::ElementFactory::make("queue")
+        .name("video_capture_queue")
+        .build()?;
+
+    let videoconvert = gstreamer::ElementFactory::make("videoconvert")
+        .name("videoconvert")
+        .build()?;
+
+    let x264enc = gstreamer::ElementFactory::make("x264enc")
+        .name("x264enc")
+        .property_from_str("tune", "zerolatency")
+        .property_from_str("speed-preset", "ultrafast")
+        .property("bitrate", bitrate)
+        .property("key-int-max", X264_KEY_INT_MAX)
+        .build()?;
+
+    let rtph264pay = gstreamer::ElementFactory::make("rtph264pay")
+        .name("rtph264pay")
+        .property("config-interval", -1)
+        .build()?;
+
+    elements.insert("src", source);
+    elements.insert("queue", queue);
+    elements.insert("convert", videoconvert);
+    elements.insert("enc", x264enc);
+    elements.insert("pay", rtph264pay);
+
+    Ok(elements)
+}
+
+/// Creates an X11 screen capture source.
+///
+/// # Arguments
+///
+/// * `window_handle` - The id of the window to capture, or 0 to capture the whole screen.
+pub fn x11_source(window_handle: u64) -> Result<Element, glib::BoolError> {
+    let mut builder = gstreamer::ElementFactory::make("ximagesrc")
+        .name("ximagesrc")
+        .property("show-pointer", true)
+        .property("use-damage", false);
+    if window_handle != 0 {
+        builder = builder.property("xid", window_handle);
+    }
+    builder.build()
+}
+
+/// Creates a PipeWire screen capture source, used on Wayland sessions.
+pub fn pipewire_source() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("pipewiresrc")
+        .name("pipewiresrc")
+        .property("do-timestamp", true)
+        .build()
+}
+
+/// Creates a live test pattern source, used to test the pipeline without a screen.
+///
+/// # Arguments
+///
+/// * `pattern` - The `videotestsrc` pattern, e.g. `smpte` or `ball`.
+pub fn test_source(pattern: &str) -> Result<Element, glib::BoolError> {
+    let source = gstreamer::ElementFactory::make("videotestsrc")
+        .name("videotestsrc")
+        .property("is-live", true)
+        .build()?;
+
+    // Checked first because setting an unknown value panics
+    let known = source
+        .find_property("pattern")
+        .and_then(|pspec| glib::EnumClass::with_type(pspec.value_type()))
+        .is_some_and(|class| class.value_by_nick(pattern).is_some());
+    if !known {
+        return Err(glib::bool_error!(
+            "Unknown videotestsrc pattern {}",
+            pattern
+        ));
+    }
+    source.set_property_from_str("pattern", pattern);
+
+    Ok(source)
+}
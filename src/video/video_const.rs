@@ -2,6 +2,14 @@
 pub const VIDEO_CAPTURE_PIPELINE_NAME: &str = "VIDEO CAPTURE";
 pub const GSTREAMER_FRAMES: i32 = 50;
 pub const ENCODER_BITRATE: u32 = 6000;
+// Frames between key frames of the software encoder
+pub const X264_KEY_INT_MAX: u32 = 60;
+// Default pattern and resolution of the test capture source
+pub const TEST_PATTERN: &str = "smpte";
+pub const TEST_WIDTH: i32 = 1280;
+pub const TEST_HEIGHT: i32 = 720;
 
 //VIDEO PLAYER CONSTS
 pub const VIDEO_PLAYER_PIPELINE_NAME: &str = "VIDEO PLAYER";
+// Tags of the video, the title is shown by the sinks that open a window
+pub const VIDEO_PLAYER_TAGS: &str = "title=Cloud-Gaming-Rental-Service";
@@ -2,6 +2,8 @@ use std::collections::HashMap;
 
 use gstreamer::{glib, Element};
 
+use super::video_const::VIDEO_PLAYER_TAGS;
+
 /// Creates the elements for the video player pipeline.
 ///
 /// # Returns
@@ -28,7 +30,7 @@ pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolErr
 
     let taginject = gstreamer::ElementFactory::make("taginject")
         .name("taginject")
-        .property("tags", "title=Cloud-Gaming-Rental-Service")
+        .property("tags", VIDEO_PLAYER_TAGS)
         .build()
         .expect("Could not create d3d11videosink element.");
 
@@ -48,3 +50,79 @@ pub fn create_elements() -> Result<HashMap<&'static str, Element>, glib::BoolErr
 
     Ok(elements)
 }
+
+/// Creates the elements for the video player pipeline decoding in software, used where
+/// Direct3D 11 is not available.
+///
+/// `avdec_h264` is used if present, otherwise `openh264dec`.
+///
+/// # Arguments
+///
+/// * `sink` - The element that shows the video, see the `*_sink` functions.
+///
+/// # Returns
+///
+/// A Result containing a HashMap with the elements if the operation was successful, otherwise an Error is returned.
+pub fn create_software_elements(
+    sink: Element,
+) -> Result<HashMap<&'static str, Element>, glib::BoolError> {
+    let mut elements = HashMap::new();
+
+    let rtph264depay = gstreamer::ElementFactory::make("rtph264depay")
+        .name("rtph264depay")
+        .build()?;
+
+    let h264parse = gstreamer::ElementFactory::make("h264parse")
+        .name("h264parse")
+        .build()?;
+
+    let h264dec = if let Ok(dec) = gstreamer::ElementFactory::make("avdec_h264")
+        .name("avdec_h264")
+        .build()
+    {
+        dec
+    } else {
+        gstreamer::ElementFactory::make("openh264dec")
+            .name("openh264dec")
+            .build()?
+    };
+
+    let queue = gstreamer::ElementFactory::make("queue")
+        .name("video_player_queue")
+        .build()?;
+
+    let taginject = gstreamer::ElementFactory::make("taginject")
+        .name("taginject")
+        .property("tags", VIDEO_PLAYER_TAGS)
+        .build()?;
+
+    elements.insert("depay", rtph264depay);
+    elements.insert("parse", h264parse);
+    elements.insert("dec", h264dec);
+    elements.insert("queue", queue);
+    elements.insert("taginject", taginject);
+    elements.insert("sink", sink);
+
+    Ok(elements)
+}
+
+/// Creates a sink that picks the video output of the platform.
+pub fn auto_sink() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("autovideosink")
+        .name("autovideosink")
+        .build()
+}
+
+/// Creates an OpenGL sink.
+pub fn gl_sink() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("glimagesink")
+        .name("glimagesink")
+        .build()
+}
+
+/// Creates a sink that drops the video, used to run the receiver without a display.
+pub fn fake_sink() -> Result<Element, glib::BoolError> {
+    gstreamer::ElementFactory::make("fakesink")
+        .name("video_fakesink")
+        .build()
+}
@@ -2,9 +2,11 @@ use base64::prelude::BASE64_STANDARD;
 use base64::Engine;
 use std::io::{Error, ErrorKind};
 use std::sync::Arc;
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver};
 use webrtc::api::interceptor_registry::register_default_interceptors;
 use webrtc::api::media_engine::{MediaEngine, MIME_TYPE_H264, MIME_TYPE_OPUS};
 use webrtc::api::{APIBuilder, API};
+use webrtc::ice_transport::ice_candidate::{RTCIceCandidate, RTCIceCandidateInit};
 use webrtc::ice_transport::ice_server::RTCIceServer;
 use webrtc::interceptor::registry::Registry;
 use webrtc::peer_connection::configuration::RTCConfiguration;
@@ -14,11 +16,13 @@ use webrtc::rtp_transceiver::rtp_codec::{
     RTCRtpCodecCapability, RTCRtpCodecParameters, RTPCodecType,
 };
 
+use crate::config::node_config::WebRtcConfig;
+use crate::utils::node_error::NodeError;
 use crate::utils::webrtc_const::{
     AUDIO_CHANNELS, AUDIO_PAYLOAD_TYPE, AUDIO_SAMPLE_RATE, VIDEO_CHANNELS, VIDEO_PAYLOAD_TYPE,
     VIDEO_SAMPLE_RATE,
 };
-use crate::utils::webrtc_const::{TURN_ADRESS, TURN_PASS, TURN_USER};
+use crate::webrtcommunication::turn_credentials::TurnCredentials;
 
 /// Represents the WebRtc connection with other peer
 ///
@@ -28,8 +32,8 @@ pub struct Communication {
     peer_connection: Arc<RTCPeerConnection>,
 }
 impl Communication {
-    /// Create new Comunication, needs a correct stun server adress to work
-    pub async fn new(stun_adress: String) -> Result<Self, Error> {
+    /// Create new Comunication, needs correct stun and turn servers in the config to work
+    pub async fn new(webrtc_config: &WebRtcConfig) -> Result<Self, NodeError> {
         let api = create_api()?;
 
         // Config SIN TURN SERVER
@@ -41,59 +45,95 @@ impl Communication {
         //     ..Default::default()
         // };
 
+        let mut ice_servers = vec![RTCIceServer {
+            urls: vec![webrtc_config.stun_address.to_owned()],
+            ..Default::default()
+        }];
+
         //Config con TURN SERVER nuestro
-        let config = RTCConfiguration {
-            ice_servers: vec![
-                RTCIceServer {
-                    urls: vec![stun_adress.to_owned()],
-                    ..Default::default()
-                },
-                RTCIceServer {
-                    urls: vec![TURN_ADRESS.to_owned()],
-                    username: TURN_USER.to_owned(),
-                    credential: TURN_PASS.to_owned(),
+        match TurnCredentials::from_config(webrtc_config) {
+            Some(credentials) => {
+                let (username, credential) = credentials.resolve().map_err(NodeError::Io)?;
+                ice_servers.push(RTCIceServer {
+                    urls: vec![webrtc_config.turn_address.to_owned()],
+                    username,
+                    credential,
                     credential_type:
                         webrtc::ice_transport::ice_credential_type::RTCIceCredentialType::Password,
-                },
-            ],
+                });
+            }
+            None => log::warn!("COMMUNICATION | No TURN credentials configured, using STUN only"),
+        }
+
+        let config = RTCConfiguration {
+            ice_servers,
             ..Default::default()
         };
 
-        let peer_connection = Arc::new(if let Ok(val) = api.new_peer_connection(config).await {
-            val
-        } else {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error creating peer connection",
-            ));
-        });
+        let peer_connection = Arc::new(
+            api.new_peer_connection(config)
+                .await
+                .map_err(|e| NodeError::WebRtc("Error creating peer connection", e))?,
+        );
 
         Ok(Self { peer_connection })
     }
     /// Waits to recibe an sdp string offer to setting the pc remote description
-    pub async fn set_sdp(&self, sdp: String) -> Result<(), Error> {
+    pub async fn set_sdp(&self, sdp: String) -> Result<(), NodeError> {
         //let line = must_read_stdin()?;
         let desc_data = decode(sdp.as_str())?;
         let offer: RTCSessionDescription =
             serde_json::from_str::<RTCSessionDescription>(&desc_data)?;
         // Set the remote SessionDescription
-        if self
-            .peer_connection
+        self.peer_connection
             .set_remote_description(offer)
             .await
-            .is_err()
-        {
-            return Err(Error::new(
-                ErrorKind::Other,
-                "Error setting remote description",
-            ));
-        };
-        Ok(())
+            .map_err(|e| NodeError::WebRtc("Error setting remote description", e))
     }
 
     pub fn get_peer(&self) -> Arc<RTCPeerConnection> {
         self.peer_connection.clone()
     }
+
+    /// Forwards the local ICE candidates, serialized as JSON, to the returned channel.
+    ///
+    /// Must be called before setting the local description so no candidate is missed.
+    /// `None` is sent once gathering is complete.
+    pub fn local_candidates(&self) -> UnboundedReceiver<Option<String>> {
+        let (tx, rx) = unbounded_channel();
+        self.peer_connection
+            .on_ice_candidate(Box::new(move |c: Option<RTCIceCandidate>| {
+                let candidate = match c.map(|c| c.to_json()) {
+                    Some(Ok(init)) => match serde_json::to_string(&init) {
+                        Ok(json) => Some(json),
+                        Err(e) => {
+                            log::error!("COMMUNICATION | Error serializing candidate: {e}");
+                            return Box::pin(async {});
+                        }
+                    },
+                    Some(Err(e)) => {
+                        log::error!("COMMUNICATION | Error converting candidate: {e}");
+                        return Box::pin(async {});
+                    }
+                    None => None,
+                };
+                let _ = tx.send(candidate);
+                Box::pin(async {})
+            }));
+        rx
+    }
+
+    /// Adds a remote ICE candidate received through signaling.
+    ///
+    /// # Arguments
+    /// * `candidate` - The candidate serialized as JSON.
+    pub async fn add_remote_candidate(&self, candidate: &str) -> Result<(), NodeError> {
+        let init = serde_json::from_str::<RTCIceCandidateInit>(candidate)?;
+        self.peer_connection
+            .add_ice_candidate(init)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error adding ICE candidate", e))
+    }
 }
 
 /// Creates the API object used for WebRTC communication.
@@ -103,9 +143,9 @@ impl Communication {
 /// # Returns
 /// A Result containing the configured WebRTC API on success. Otherwise
 /// error is returned
-fn create_api() -> Result<API, Error> {
+fn create_api() -> Result<API, NodeError> {
     let mut m = MediaEngine::default();
-    if let Err(_val) = m.register_codec(
+    if let Err(e) = m.register_codec(
         RTCRtpCodecParameters {
             capability: RTCRtpCodecCapability {
                 mime_type: MIME_TYPE_OPUS.to_owned(),
@@ -119,10 +159,10 @@ fn create_api() -> Result<API, Error> {
         },
         RTPCodecType::Audio,
     ) {
-        return Err(Error::new(ErrorKind::Other, "Error registering OPUS codec"));
+        return Err(NodeError::WebRtc("Error registering OPUS codec", e));
     }
 
-    if let Err(_val) = m.register_codec(
+    if let Err(e) = m.register_codec(
         RTCRtpCodecParameters {
             capability: RTCRtpCodecCapability {
                 mime_type: MIME_TYPE_H264.to_owned(),
@@ -136,20 +176,14 @@ fn create_api() -> Result<API, Error> {
         },
         RTPCodecType::Video,
     ) {
-        return Err(Error::new(ErrorKind::Other, "Error registering H264 codec"));
+        return Err(NodeError::WebRtc("Error registering H264 codec", e));
     }
 
     let mut registry = Registry::new();
 
     // Use the default set of Interceptors
-    if let Ok(val) = register_default_interceptors(registry, &mut m) {
-        registry = val;
-    } else {
-        return Err(Error::new(
-            ErrorKind::Other,
-            "Error registering default interceptors",
-        ));
-    }
+    registry = register_default_interceptors(registry, &mut m)
+        .map_err(|e| NodeError::WebRtc("Error registering default interceptors", e))?;
 
     // Create the API object with the MediaEngine
     let api = APIBuilder::new()
@@ -12,6 +12,7 @@ use crate::utils::latency_const::{
     LATENCY_CHANNEL_LABEL, LOOP_LATENCY_TIME, MAX_SNTP_RETRY, SNTP_POOL_ADDR, SNTP_SEND_SLEEP,
     UDP_SOCKET_ADDR, UDP_SOCKET_TIMEOUT,
 };
+use crate::utils::node_error::NodeError;
 
 const LATENCY_CHECK: bool = false;
 
@@ -22,16 +23,11 @@ pub struct Latency {}
 
 impl Latency {
     /// Start the latency in the sender side, create a data channel and send the local time
-    pub async fn start_latency_sender(pc: Arc<RTCPeerConnection>) -> Result<(), Error> {
-        let latency_channel = match pc.create_data_channel(LATENCY_CHANNEL_LABEL, None).await {
-            Ok(ch) => ch,
-            Err(_) => {
-                return Err(Error::new(
-                    ErrorKind::Other,
-                    "Error creating latency data channel",
-                ))
-            }
-        };
+    pub async fn start_latency_sender(pc: Arc<RTCPeerConnection>) -> Result<(), NodeError> {
+        let latency_channel = pc
+            .create_data_channel(LATENCY_CHANNEL_LABEL, None)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating latency data channel", e))?;
         log::debug!("LATENCY | Latency Data channel created");
         if LATENCY_CHECK {
             let socket = create_socket(UDP_SOCKET_ADDR, Duration::from_secs(UDP_SOCKET_TIMEOUT))?;
@@ -1,2 +1,6 @@
 pub mod communication;
 pub mod latency;
+pub mod reconnect;
+pub mod stats;
+pub mod trickle;
+pub mod turn_credentials;
@@ -0,0 +1,283 @@
+use std::time::Duration;
+use tokio::time::{sleep_until, Instant};
+use webrtc::peer_connection::offer_answer_options::RTCOfferOptions;
+use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+use webrtc::peer_connection::RTCPeerConnection;
+
+use super::communication::{encode, Communication};
+use crate::config::node_config::ReconnectConfig;
+use crate::utils::node_error::NodeError;
+use crate::websocketprotocol::signaling_transport::SignalingTransport;
+
+/// Changes of the peer connection that matter once the session has started.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConnectionEvent {
+    /// The connection was disconnected or failed.
+    Lost,
+    /// The connection is up again.
+    Restored,
+}
+
+/// Keeps track of a connection being recovered.
+///
+/// When the connection is lost the peers are given a grace period to recover by themselves.
+/// After it an ICE restart is due, and if the connection is not restored before the timeout
+/// the session must be torn down.
+pub struct Reconnect {
+    grace_period: Duration,
+    timeout: Duration,
+    restart_at: Option<Instant>,
+    deadline: Option<Instant>,
+}
+
+impl Reconnect {
+    pub fn new(config: &ReconnectConfig) -> Reconnect {
+        Reconnect {
+            grace_period: Duration::from_secs(config.grace_period),
+            timeout: Duration::from_secs(config.timeout),
+            restart_at: None,
+            deadline: None,
+        }
+    }
+
+    /// Updates the recovery state with a new connection event.
+    pub fn on_event(&mut self, event: ConnectionEvent) {
+        match event {
+            ConnectionEvent::Lost => {
+                if self.deadline.is_none() {
+                    let now = Instant::now();
+                    log::warn!(
+                        "RECONNECT | Connection lost, waiting up to {:?} to recover",
+                        self.timeout
+                    );
+                    self.restart_at = Some(now + self.grace_period);
+                    self.deadline = Some(now + self.timeout);
+                }
+            }
+            ConnectionEvent::Restored => {
+                if self.deadline.is_some() {
+                    log::info!("RECONNECT | Connection restored");
+                }
+                self.restart_at = None;
+                self.deadline = None;
+            }
+        }
+    }
+
+    /// Completes when the grace period is over and an ICE restart should be attempted.
+    /// Never completes if the connection is not being recovered.
+    pub async fn restart_due(&self) {
+        wait_until(self.restart_at).await
+    }
+
+    /// Marks the ICE restart as done, so `restart_due` does not complete again.
+    pub fn restart_sent(&mut self) {
+        self.restart_at = None;
+    }
+
+    /// Completes when the connection could not be recovered in time.
+    /// Never completes if the connection is not being recovered.
+    pub async fn expired(&self) {
+        wait_until(self.deadline).await
+    }
+
+    /// Stops tracking the recovery once the timeout has been handled.
+    pub fn give_up(&mut self) {
+        self.restart_at = None;
+        self.deadline = None;
+    }
+}
+
+async fn wait_until(instant: Option<Instant>) {
+    match instant {
+        Some(instant) => sleep_until(instant).await,
+        None => std::future::pending().await,
+    }
+}
+
+/// Creates an ICE restart offer and sends it to the client.
+///
+/// The offer is sent with all its candidates, the answer arrives later through the
+/// signaling transport as any other client sdp.
+///
+/// # Arguments
+///
+/// * `comunication` - The connection to restart.
+/// * `ws` - Transport used to send the offer.
+/// * `client_name` - Name of the client to send the offer to.
+pub async fn restart_ice(
+    comunication: &Communication,
+    ws: &mut dyn SignalingTransport,
+    client_name: &str,
+) -> Result<(), NodeError> {
+    let pc = comunication.get_peer();
+    let options = RTCOfferOptions {
+        ice_restart: true,
+        ..Default::default()
+    };
+    let offer = pc
+        .create_offer(Some(options))
+        .await
+        .map_err(|e| NodeError::WebRtc("Error creating restart offer", e))?;
+
+    let b64 = set_local_description(&pc, offer).await?;
+    log::info!("RECONNECT | Sending ICE restart offer to {}", client_name);
+    ws.send_sdp_to_client(client_name, &b64, false)
+        .await
+        .map_err(NodeError::Signaling)
+}
+
+/// Answers an offer received once the session has started, such as an ICE restart.
+///
+/// # Arguments
+///
+/// * `comunication` - The connection the offer is for.
+/// * `ws` - Transport used to send the answer.
+/// * `offerer_name` - Name of the offerer to send the answer to.
+/// * `sdp` - The offer as received through signaling.
+pub async fn answer_offer(
+    comunication: &Communication,
+    ws: &mut dyn SignalingTransport,
+    offerer_name: &str,
+    sdp: String,
+) -> Result<(), NodeError> {
+    comunication.set_sdp(sdp).await?;
+
+    let pc = comunication.get_peer();
+    let answer = pc
+        .create_answer(None)
+        .await
+        .map_err(|e| NodeError::WebRtc("Error creating answer", e))?;
+
+    let b64 = set_local_description(&pc, answer).await?;
+    log::info!("RECONNECT | Sending answer to {}", offerer_name);
+    ws.send_sdp_to_offerer(offerer_name, &b64)
+        .await
+        .map_err(NodeError::Signaling)
+}
+
+/// Sets the local description and waits for every candidate to be gathered.
+///
+/// # Returns
+/// The local description encoded to be sent through signaling.
+async fn set_local_description(
+    pc: &RTCPeerConnection,
+    desc: RTCSessionDescription,
+) -> Result<String, NodeError> {
+    let mut gather_complete = pc.gathering_complete_promise().await;
+    pc.set_local_description(desc)
+        .await
+        .map_err(|e| NodeError::WebRtc("Error setting local description", e))?;
+    let _ = gather_complete.recv().await;
+
+    match pc.local_description().await {
+        Some(local_desc) => Ok(encode(&serde_json::to_string(&local_desc)?)),
+        None => Err(NodeError::WebRtc(
+            "Generate local_description failed",
+            webrtc::Error::new("missing local description".to_owned()),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::future::Future;
+    use tokio::time::{sleep, timeout};
+
+    const GRACE_PERIOD: Duration = Duration::from_secs(3);
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    fn reconnect() -> Reconnect {
+        Reconnect::new(&ReconnectConfig {
+            grace_period: GRACE_PERIOD.as_secs(),
+            timeout: TIMEOUT.as_secs(),
+        })
+    }
+
+    /// Whether the future is still pending after an hour.
+    async fn never_completes(future: impl Future<Output = ()>) -> bool {
+        timeout(Duration::from_secs(3600), future).await.is_err()
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn nothing_is_due_while_connected() {
+        let reconnect = reconnect();
+
+        assert!(never_completes(reconnect.restart_due()).await);
+        assert!(never_completes(reconnect.expired()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lost_connection_is_restarted_then_expires() {
+        let mut reconnect = reconnect();
+        let start = Instant::now();
+        reconnect.on_event(ConnectionEvent::Lost);
+
+        reconnect.restart_due().await;
+        assert_eq!(start.elapsed(), GRACE_PERIOD);
+        reconnect.restart_sent();
+
+        reconnect.expired().await;
+        assert_eq!(start.elapsed(), TIMEOUT);
+        assert!(never_completes(reconnect.restart_due()).await);
+
+        reconnect.give_up();
+        assert!(never_completes(reconnect.expired()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restored_before_timeout_cancels_the_recovery() {
+        let mut reconnect = reconnect();
+        reconnect.on_event(ConnectionEvent::Lost);
+
+        reconnect.restart_due().await;
+        reconnect.restart_sent();
+        sleep(Duration::from_secs(10)).await;
+        reconnect.on_event(ConnectionEvent::Restored);
+
+        assert!(never_completes(reconnect.restart_due()).await);
+        assert!(never_completes(reconnect.expired()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn restored_during_grace_period_skips_the_restart() {
+        let mut reconnect = reconnect();
+        reconnect.on_event(ConnectionEvent::Lost);
+
+        sleep(GRACE_PERIOD / 2).await;
+        reconnect.on_event(ConnectionEvent::Restored);
+
+        assert!(never_completes(reconnect.restart_due()).await);
+        assert!(never_completes(reconnect.expired()).await);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lost_again_keeps_the_first_deadline() {
+        let mut reconnect = reconnect();
+        let start = Instant::now();
+        reconnect.on_event(ConnectionEvent::Lost);
+
+        sleep(Duration::from_secs(5)).await;
+        reconnect.on_event(ConnectionEvent::Lost);
+
+        reconnect.expired().await;
+        assert_eq!(start.elapsed(), TIMEOUT);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn lost_after_restored_starts_a_new_grace_period() {
+        let mut reconnect = reconnect();
+        reconnect.on_event(ConnectionEvent::Lost);
+        sleep(Duration::from_secs(10)).await;
+        reconnect.on_event(ConnectionEvent::Restored);
+
+        let start = Instant::now();
+        reconnect.on_event(ConnectionEvent::Lost);
+
+        reconnect.restart_due().await;
+        assert_eq!(start.elapsed(), GRACE_PERIOD);
+        reconnect.expired().await;
+        assert_eq!(start.elapsed(), TIMEOUT);
+    }
+}
@@ -0,0 +1,62 @@
+use std::sync::Arc;
+use std::time::Duration;
+use webrtc::peer_connection::RTCPeerConnection;
+use webrtc::stats::StatsReportType;
+
+use crate::front_connection::front_event::{FrontEvent, FrontEventSender};
+use crate::services::session_state::SessionWatcher;
+use crate::utils::supervisor::Task;
+use crate::utils::webrtc_const::STREAM_STATS_INTERVAL;
+
+/// Periodically reports the statistics of the peer connection to the front end while the
+/// session is streaming.
+///
+/// # Arguments
+///
+/// * `pc` - The RTCPeerConnection to read the statistics from.
+/// * `session` - Used to wait until the session is streaming.
+/// * `events` - Used to send the statistics to the front end.
+/// * `task` - Handle of the supervised task, used for graceful shutdown.
+pub async fn report_stream_stats(
+    pc: Arc<RTCPeerConnection>,
+    mut session: SessionWatcher,
+    events: FrontEventSender,
+    task: Task,
+) {
+    tokio::select! {
+        _ = task.cancelled() => return,
+        streaming = session.wait_streaming() => {
+            if !streaming {
+                return;
+            }
+        }
+    }
+
+    let mut interval = tokio::time::interval(Duration::from_secs(STREAM_STATS_INTERVAL));
+    loop {
+        tokio::select! {
+            _ = task.cancelled() => return,
+            _ = interval.tick() => {
+                if let Some(stats) = stream_stats(&pc).await {
+                    events.send(stats);
+                }
+            }
+        }
+    }
+}
+
+/// Reads the statistics of the candidate pair in use.
+///
+/// # Returns
+/// A `streamStats` event, or `None` if no candidate pair has been nominated yet.
+async fn stream_stats(pc: &RTCPeerConnection) -> Option<FrontEvent> {
+    let report = pc.get_stats().await;
+    report.reports.values().find_map(|stats| match stats {
+        StatsReportType::CandidatePair(pair) if pair.nominated => Some(FrontEvent::StreamStats {
+            rtt_ms: pair.current_round_trip_time * 1000.0,
+            bytes_sent: pair.bytes_sent,
+            bytes_received: pair.bytes_received,
+        }),
+        _ => None,
+    })
+}
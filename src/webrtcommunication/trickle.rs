@@ -0,0 +1,224 @@
+use std::future::Future;
+use std::io::{Error, ErrorKind};
+use tokio::sync::mpsc::UnboundedReceiver;
+
+use super::communication::Communication;
+use crate::utils::node_error::NodeError;
+use crate::websocketprotocol::signaling_message::SignalingMessage;
+use crate::websocketprotocol::signaling_transport::SignalingTransport;
+
+/// Exchanges ICE candidates with the other peer until `done` completes.
+///
+/// Local candidates are sent as `iceCandidate` messages and the ones received are added to
+/// the peer connection. The remote description must be set before calling this function.
+///
+/// # Arguments
+///
+/// * `comunication` - The connection the candidates belong to.
+/// * `ws` - Transport used to exchange the candidates.
+/// * `peer` - Name of the other peer.
+/// * `local_candidates` - Channel returned by `Communication::local_candidates`.
+/// * `done` - Future that completes when the exchange is no longer needed, usually when
+///   the peers are connected.
+///
+/// # Returns
+/// The output of `done`, or an error if the session ended while exchanging candidates.
+pub async fn exchange_candidates<F: Future>(
+    comunication: &Communication,
+    ws: &mut dyn SignalingTransport,
+    peer: &str,
+    local_candidates: &mut UnboundedReceiver<Option<String>>,
+    done: F,
+) -> Result<F::Output, NodeError> {
+    tokio::pin!(done);
+    let mut gathering = true;
+
+    loop {
+        tokio::select! {
+            output = &mut done => {
+                return Ok(output);
+            }
+            candidate = local_candidates.recv(), if gathering => {
+                match candidate {
+                    Some(Some(candidate)) => {
+                        ws.send_ice_candidate(peer, &candidate)
+                            .await
+                            .map_err(NodeError::Signaling)?;
+                    }
+                    _ => {
+                        log::info!("TRICKLE | Local gathering complete");
+                        gathering = false;
+                    }
+                }
+            }
+            msg = ws.receive() => {
+                match msg {
+                    Ok(SignalingMessage::IceCandidate { candidate, .. }) => {
+                        if let Err(e) = comunication.add_remote_candidate(&candidate).await {
+                            log::warn!("TRICKLE | Invalid remote candidate: {e}");
+                        }
+                    }
+                    Ok(SignalingMessage::NotifEndSession) => {
+                        return Err(NodeError::Signaling(Error::new(
+                            ErrorKind::ConnectionAborted,
+                            "Session ended while exchanging candidates",
+                        )));
+                    }
+                    Ok(msg) => {
+                        log::info!("TRICKLE | Received unexpected message: {}", msg.msg_type());
+                    }
+                    Err(e) if e.kind() == ErrorKind::InvalidData => {
+                        log::info!("TRICKLE | Received invalid message: {}", e);
+                    }
+                    Err(e) => return Err(NodeError::Signaling(e)),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::sync::watch;
+    use webrtc::peer_connection::peer_connection_state::RTCPeerConnectionState;
+    use webrtc::peer_connection::sdp::session_description::RTCSessionDescription;
+
+    use crate::config::node_config::WebRtcConfig;
+    use crate::webrtcommunication::communication::encode;
+    use crate::websocketprotocol::loopback::LoopbackTransport;
+
+    const OFFERER: &str = "offerer";
+    const CLIENT: &str = "client";
+    const CONNECTION_TIMEOUT: Duration = Duration::from_secs(20);
+
+    /// Returns a future that completes once the peer connection is connected.
+    fn connected(comunication: &Communication) -> impl Future<Output = ()> {
+        let (tx, mut rx) = watch::channel(RTCPeerConnectionState::New);
+        comunication
+            .get_peer()
+            .on_peer_connection_state_change(Box::new(move |s: RTCPeerConnectionState| {
+                let _ = tx.send(s);
+                Box::pin(async {})
+            }));
+
+        async move {
+            while *rx.borrow_and_update() != RTCPeerConnectionState::Connected {
+                if rx.changed().await.is_err() {
+                    return;
+                }
+            }
+        }
+    }
+
+    /// Encodes a description the way the nodes send it through signaling.
+    ///
+    /// The description returned by `create_offer` or `create_answer` is sent instead of the
+    /// local description, so it holds no candidate and they can only arrive trickled.
+    fn encode_description(desc: &RTCSessionDescription) -> String {
+        encode(&serde_json::to_string(desc).unwrap())
+    }
+
+    /// Runs the offerer side until the peers are connected.
+    async fn offer(ws: &mut LoopbackTransport) -> Result<bool, NodeError> {
+        let comunication = Communication::new(&WebRtcConfig::default()).await?;
+        let pc = comunication.get_peer();
+        pc.create_data_channel("trickle", None)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating data channel", e))?;
+        let connected = connected(&comunication);
+
+        ws.init_offer(OFFERER).await?;
+        let client = ws.wait_for_game_solicitude().await?;
+        assert!(client.trickle);
+
+        let mut local_candidates = comunication.local_candidates();
+        let offer = pc
+            .create_offer(None)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating offer", e))?;
+        let sdp = encode_description(&offer);
+        pc.set_local_description(offer)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error setting local description", e))?;
+        ws.send_sdp_to_client(&client.client_name, &sdp, true)
+            .await?;
+
+        let answer = ws.wait_for_client_sdp().await?;
+        comunication.set_sdp(answer).await?;
+
+        let done = tokio::time::timeout(CONNECTION_TIMEOUT, connected);
+        let exchange = exchange_candidates(
+            &comunication,
+            ws,
+            &client.client_name,
+            &mut local_candidates,
+            done,
+        );
+        let connected = exchange.await?.is_ok();
+        pc.close()
+            .await
+            .map_err(|e| NodeError::WebRtc("Error closing peer connection", e))?;
+        Ok(connected)
+    }
+
+    /// Runs the client side until the peers are connected.
+    async fn answer(ws: &mut LoopbackTransport) -> Result<bool, NodeError> {
+        let comunication = Communication::new(&WebRtcConfig::default()).await?;
+        let pc = comunication.get_peer();
+        let connected = connected(&comunication);
+
+        ws.init_client(CLIENT, OFFERER, "game", "30", true).await?;
+        let (offer, trickle) = ws.wait_for_offerer_sdp().await?;
+        assert!(trickle);
+        comunication.set_sdp(offer).await?;
+
+        let mut local_candidates = comunication.local_candidates();
+        let answer = pc
+            .create_answer(None)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error creating answer", e))?;
+        let sdp = encode_description(&answer);
+        pc.set_local_description(answer)
+            .await
+            .map_err(|e| NodeError::WebRtc("Error setting local description", e))?;
+        ws.send_sdp_to_offerer(OFFERER, &sdp).await?;
+
+        let done = tokio::time::timeout(CONNECTION_TIMEOUT, connected);
+        let exchange = exchange_candidates(&comunication, ws, OFFERER, &mut local_candidates, done);
+        let connected = exchange.await?.is_ok();
+        pc.close()
+            .await
+            .map_err(|e| NodeError::WebRtc("Error closing peer connection", e))?;
+        Ok(connected)
+    }
+
+    #[tokio::test]
+    async fn peers_connect_with_trickled_candidates() {
+        let (mut offerer, mut client) = LoopbackTransport::pair();
+
+        let (offerer, client) = tokio::join!(offer(&mut offerer), answer(&mut client));
+
+        assert!(offerer.unwrap(), "offerer did not connect");
+        assert!(client.unwrap(), "client did not connect");
+    }
+
+    #[tokio::test]
+    async fn end_of_session_stops_the_exchange() {
+        let (mut ws, mut other) = LoopbackTransport::pair();
+        let comunication = Communication::new(&WebRtcConfig::default()).await.unwrap();
+        let mut local_candidates = comunication.local_candidates();
+
+        other.force_stop_session(CLIENT).await.unwrap();
+        let exchange = exchange_candidates(
+            &comunication,
+            &mut ws,
+            CLIENT,
+            &mut local_candidates,
+            std::future::pending::<()>(),
+        );
+
+        assert!(matches!(exchange.await, Err(NodeError::Signaling(_))));
+    }
+}
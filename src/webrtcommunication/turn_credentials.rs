@@ -0,0 +1,155 @@
+use base64::prelude::BASE64_STANDARD;
+use base64::Engine;
+use hmac::{Hmac, Mac};
+use sha1::Sha1;
+use std::io::{Error, ErrorKind};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use crate::config::node_config::WebRtcConfig;
+use crate::utils::webrtc_const::TURN_REST_DEFAULT_USER;
+
+type HmacSha1 = Hmac<Sha1>;
+
+/// Credentials used to authenticate against the TURN server.
+#[derive(Debug, Clone)]
+pub enum TurnCredentials {
+    /// Long-term username and password.
+    Static { username: String, password: String },
+    /// TURN REST API credentials, derived from a secret shared with the TURN server.
+    Rest {
+        shared_secret: String,
+        username: String,
+        ttl: Duration,
+    },
+}
+
+impl TurnCredentials {
+    /// Builds the credentials from the configuration.
+    ///
+    /// A shared secret takes precedence over a username and password.
+    ///
+    /// # Returns
+    /// `None` if the configuration has no TURN credentials.
+    pub fn from_config(config: &WebRtcConfig) -> Option<TurnCredentials> {
+        if let Some(secret) = &config.turn_shared_secret {
+            return Some(TurnCredentials::Rest {
+                shared_secret: secret.to_owned(),
+                username: config
+                    .turn_user
+                    .clone()
+                    .unwrap_or_else(|| TURN_REST_DEFAULT_USER.to_owned()),
+                ttl: Duration::from_secs(config.turn_credential_ttl),
+            });
+        }
+
+        match (&config.turn_user, &config.turn_pass) {
+            (Some(username), Some(password)) => Some(TurnCredentials::Static {
+                username: username.to_owned(),
+                password: password.to_owned(),
+            }),
+            _ => None,
+        }
+    }
+
+    /// Returns the username and password to use for a new session.
+    ///
+    /// REST credentials are generated on every call and expire after the configured ttl.
+    ///
+    /// # Returns
+    /// The username and password, or an error if the expiry of REST credentials cannot be
+    /// computed.
+    pub fn resolve(&self) -> Result<(String, String), Error> {
+        match self {
+            TurnCredentials::Static { username, password } => {
+                Ok((username.to_owned(), password.to_owned()))
+            }
+            TurnCredentials::Rest {
+                shared_secret,
+                username,
+                ttl,
+            } => {
+                let expiry = expiry(SystemTime::now(), *ttl)?;
+                Ok(rest_credentials(shared_secret, username, expiry))
+            }
+        }
+    }
+}
+
+/// Returns the Unix timestamp, in seconds, at which credentials issued at `now` expire.
+///
+/// # Returns
+/// The timestamp, or an `InvalidInput` error if it is before the Unix epoch or cannot be
+/// represented, since the credentials would be rejected by the TURN server.
+fn expiry(now: SystemTime, ttl: Duration) -> Result<u64, Error> {
+    now.checked_add(ttl)
+        .and_then(|t| t.duration_since(UNIX_EPOCH).ok())
+        .map(|d| d.as_secs())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidInput,
+                format!("Invalid TURN credential expiry, ttl of {} s", ttl.as_secs()),
+            )
+        })
+}
+
+/// Generates TURN REST API credentials.
+///
+/// The username is `expiry:username` and the password is the base64 encoded
+/// HMAC-SHA1 of that username, keyed with the secret shared with the TURN server.
+///
+/// # Arguments
+///
+/// * `shared_secret` - The secret configured in the TURN server.
+/// * `username` - The name of the user the credentials are issued for.
+/// * `expiry` - Unix timestamp, in seconds, after which the credentials are rejected.
+///
+/// # Returns
+/// A tuple with the username and the password.
+pub fn rest_credentials(shared_secret: &str, username: &str, expiry: u64) -> (String, String) {
+    let rest_username = format!("{}:{}", expiry, username);
+
+    // HMAC accepts keys of any length, so this never fails
+    let mut mac =
+        HmacSha1::new_from_slice(shared_secret.as_bytes()).expect("HMAC can take key of any size");
+    mac.update(rest_username.as_bytes());
+    let password = BASE64_STANDARD.encode(mac.finalize().into_bytes());
+
+    (rest_username, password)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn rest_credentials_match_known_vector() {
+        // Generated with Python's hmac module
+        let (username, password) = rest_credentials("turn-secret", "cgrs", 1_700_000_000);
+        assert_eq!(username, "1700000000:cgrs");
+        assert_eq!(password, "LA/jzt2IwUp5JXZ2/rrL7EyxY0A=");
+    }
+
+    #[test]
+    fn rest_credentials_change_with_the_expiry() {
+        let (_, first) = rest_credentials("turn-secret", "cgrs", 1_700_000_000);
+        let (_, second) = rest_credentials("turn-secret", "cgrs", 1_700_000_001);
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn expiry_adds_the_ttl() {
+        let now = UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let expiry = expiry(now, Duration::from_secs(86_400)).unwrap();
+        assert_eq!(expiry, 1_700_086_400);
+    }
+
+    #[test]
+    fn expiry_out_of_range_is_an_error() {
+        assert_eq!(
+            expiry(SystemTime::now(), Duration::MAX).unwrap_err().kind(),
+            ErrorKind::InvalidInput
+        );
+        let before_epoch = UNIX_EPOCH - Duration::from_secs(60);
+        assert!(expiry(before_epoch, Duration::from_secs(1)).is_err());
+    }
+}
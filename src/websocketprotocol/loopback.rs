@@ -0,0 +1,244 @@
+use async_trait::async_trait;
+use std::io::{Error, ErrorKind};
+use tokio::sync::mpsc::{unbounded_channel, UnboundedReceiver, UnboundedSender};
+
+use super::signaling_message::SignalingMessage;
+use super::signaling_transport::SignalingTransport;
+
+/// In-process signaling transport.
+///
+/// A pair of `LoopbackTransport` behaves like two nodes connected to the intermediary
+/// server: what one end sends is translated the same way the server does and delivered to
+/// the other end. It allows running the offer/answer flow without network access.
+pub struct LoopbackTransport {
+    /// Name registered by this end, sent as the origin of its candidates.
+    name: Option<String>,
+    tx: Option<UnboundedSender<SignalingMessage>>,
+    rx: UnboundedReceiver<SignalingMessage>,
+}
+
+impl LoopbackTransport {
+    /// Creates two connected ends, one for the sender and one for the receiver.
+    pub fn pair() -> (LoopbackTransport, LoopbackTransport) {
+        let (tx_a, rx_a) = unbounded_channel();
+        let (tx_b, rx_b) = unbounded_channel();
+
+        (
+            LoopbackTransport {
+                name: None,
+                tx: Some(tx_b),
+                rx: rx_a,
+            },
+            LoopbackTransport {
+                name: None,
+                tx: Some(tx_a),
+                rx: rx_b,
+            },
+        )
+    }
+}
+
+#[async_trait]
+impl SignalingTransport for LoopbackTransport {
+    async fn send(&mut self, msg: SignalingMessage) -> Result<(), Error> {
+        let tx = match &self.tx {
+            Some(tx) => tx,
+            None => return Err(Error::new(ErrorKind::NotConnected, "Loopback closed")),
+        };
+
+        if let SignalingMessage::InitOfferer { username }
+        | SignalingMessage::InitClient { username, .. } = &msg
+        {
+            self.name = Some(username.clone());
+        }
+
+        let msg_type = msg.msg_type();
+        if let Some(relayed) = relay(msg, self.name.as_deref()) {
+            if tx.send(relayed).is_err() {
+                return Err(Error::new(
+                    ErrorKind::Other,
+                    format!("Error sending {} message", msg_type),
+                ));
+            }
+        }
+        Ok(())
+    }
+
+    async fn receive(&mut self) -> Result<SignalingMessage, Error> {
+        match self.rx.recv().await {
+            Some(msg) => Ok(msg),
+            None => Err(Error::new(ErrorKind::Other, "Error receiving message")),
+        }
+    }
+
+    async fn close_connection(&mut self) -> Result<(), Error> {
+        self.tx = None;
+        Ok(())
+    }
+}
+
+/// Translates a message the way the intermediary server does before delivering it to the
+/// other peer. Returns `None` for messages the server does not forward.
+///
+/// The game path is not known by the loopback, so the game name is forwarded in its place.
+///
+/// # Arguments
+///
+/// * `msg` - The message sent.
+/// * `origin` - Name registered by the end that sent it, if any.
+fn relay(msg: SignalingMessage, origin: Option<&str>) -> Option<SignalingMessage> {
+    match msg {
+        SignalingMessage::InitOfferer { .. } | SignalingMessage::StartSession { .. } => None,
+        SignalingMessage::InitClient {
+            username,
+            game_name,
+            minutes,
+            trickle,
+            ..
+        } => Some(SignalingMessage::SdpRequestFrom {
+            client_name: username,
+            game_path: game_name.clone(),
+            game_name,
+            minutes,
+            trickle,
+        }),
+        SignalingMessage::OffererSdp { sdp, trickle, .. } => {
+            Some(SignalingMessage::SdpOfferer { sdp, trickle })
+        }
+        SignalingMessage::ClientSdp { sdp, .. } => Some(SignalingMessage::SdpClient { sdp }),
+        SignalingMessage::ForceStopSession { .. } => Some(SignalingMessage::NotifEndSession),
+        // The receiver is told who sent the candidate, not who it was sent to
+        SignalingMessage::IceCandidate { candidate, .. } => match origin {
+            Some(origin) => Some(SignalingMessage::IceCandidate {
+                peer: origin.to_owned(),
+                candidate,
+            }),
+            None => {
+                log::warn!("LOOPBACK | Dropping candidate from unregistered node");
+                None
+            }
+        },
+        other => Some(other),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::websocketprotocol::signaling_transport::SessionSignal;
+
+    const OFFERER: &str = "offerer";
+    const CLIENT: &str = "client";
+
+    /// Runs the offerer side of the negotiation, returning the answer of the client.
+    async fn offer(ws: &mut LoopbackTransport) -> Result<String, Error> {
+        ws.init_offer(OFFERER).await?;
+        let client = ws.wait_for_game_solicitude().await?;
+        assert_eq!(client.client_name, CLIENT);
+        assert_eq!(client.game_name, "game");
+        assert_eq!(client.minutes, "30");
+        assert!(client.trickle);
+
+        ws.send_sdp_to_client(&client.client_name, "offer sdp", true)
+            .await?;
+        let answer = ws.wait_for_client_sdp().await?;
+        ws.start_session(OFFERER, &client.client_name, &client.minutes)
+            .await?;
+        Ok(answer)
+    }
+
+    /// Runs the client side of the negotiation, returning the offer received.
+    async fn answer(ws: &mut LoopbackTransport) -> Result<(String, bool), Error> {
+        ws.init_client(CLIENT, OFFERER, "game", "30", true).await?;
+        let offer = ws.wait_for_offerer_sdp().await?;
+        ws.send_sdp_to_offerer(OFFERER, "answer sdp").await?;
+        Ok(offer)
+    }
+
+    #[tokio::test]
+    async fn sender_and_receiver_negotiate() {
+        let (mut offerer, mut client) = LoopbackTransport::pair();
+
+        let (answer, offer) = tokio::join!(offer(&mut offerer), answer(&mut client));
+
+        assert_eq!(answer.unwrap(), "answer sdp");
+        assert_eq!(offer.unwrap(), ("offer sdp".to_owned(), true));
+    }
+
+    #[tokio::test]
+    async fn candidates_arrive_from_their_origin() {
+        let (mut offerer, mut client) = LoopbackTransport::pair();
+        offerer.init_offer(OFFERER).await.unwrap();
+        client
+            .init_client(CLIENT, OFFERER, "game", "30", false)
+            .await
+            .unwrap();
+        offerer.wait_for_game_solicitude().await.unwrap();
+
+        offerer
+            .send_ice_candidate(CLIENT, "candidate")
+            .await
+            .unwrap();
+        assert_eq!(
+            client.receive().await.unwrap(),
+            SignalingMessage::IceCandidate {
+                peer: OFFERER.to_owned(),
+                candidate: "candidate".to_owned(),
+            }
+        );
+
+        client
+            .send_ice_candidate(OFFERER, "candidate")
+            .await
+            .unwrap();
+        assert_eq!(
+            offerer.receive().await.unwrap(),
+            SignalingMessage::IceCandidate {
+                peer: CLIENT.to_owned(),
+                candidate: "candidate".to_owned(),
+            }
+        );
+    }
+
+    #[tokio::test]
+    async fn candidates_of_unregistered_ends_are_dropped() {
+        let (mut offerer, mut client) = LoopbackTransport::pair();
+
+        offerer
+            .send_ice_candidate(CLIENT, "candidate")
+            .await
+            .unwrap();
+        offerer.force_stop_session(OFFERER).await.unwrap();
+
+        // The end of the session is the first message delivered
+        assert_eq!(
+            client.receive().await.unwrap(),
+            SignalingMessage::NotifEndSession
+        );
+    }
+
+    #[tokio::test]
+    async fn forced_stop_ends_the_session_of_the_other_end() {
+        let (mut offerer, mut client) = LoopbackTransport::pair();
+
+        client.force_stop_session(CLIENT).await.unwrap();
+
+        assert!(matches!(
+            offerer.wait_for_session_event().await,
+            Ok(SessionSignal::EndSession)
+        ));
+    }
+
+    #[tokio::test]
+    async fn closed_end_cannot_send() {
+        let (mut offerer, mut client) = LoopbackTransport::pair();
+
+        offerer.close_connection().await.unwrap();
+
+        assert_eq!(
+            offerer.init_offer(OFFERER).await.unwrap_err().kind(),
+            ErrorKind::NotConnected
+        );
+        assert!(client.receive().await.is_err());
+    }
+}
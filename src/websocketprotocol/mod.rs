@@ -1,2 +1,5 @@
+pub mod loopback;
+pub mod signaling_message;
+pub mod signaling_transport;
 pub mod socket_protocol;
 pub mod socket_protocol_const;
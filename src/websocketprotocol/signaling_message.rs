@@ -0,0 +1,511 @@
+use std::fmt;
+use std::io::{Error, ErrorKind};
+
+use websockets::Frame;
+
+use super::socket_protocol_const::*;
+use crate::utils::message_fields::{Fields, MissingField};
+
+/// Every message exchanged with the intermediary server.
+///
+/// The wire representation is the pipe-delimited text format understood by the server,
+/// `<type>|<field>|<field>...`. When the message is encoded with a version other than
+/// [`LEGACY_PROTOCOL_VERSION`] a `v<version>|` header is prepended.
+///
+/// Optional flags, such as trickle ICE support, are appended as trailing fields only when
+/// set, so servers that do not know them keep receiving the same messages.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SignalingMessage {
+    // Sent by the node
+    InitOfferer {
+        username: String,
+    },
+    OffererSdp {
+        client_name: String,
+        sdp: String,
+        trickle: bool,
+    },
+    InitClient {
+        username: String,
+        offerer_username: String,
+        game_name: String,
+        minutes: String,
+        trickle: bool,
+    },
+    ClientSdp {
+        offerer_username: String,
+        sdp: String,
+    },
+    StartSession {
+        offerer: String,
+        client: String,
+        minutes: String,
+    },
+    ForceStopSession {
+        username: String,
+    },
+    // Sent and received by the node. When sent `peer` is the destination, when received
+    // it is the origin of the candidate.
+    IceCandidate {
+        peer: String,
+        candidate: String,
+    },
+    // Received by the node
+    SdpRequestFrom {
+        client_name: String,
+        game_name: String,
+        game_path: String,
+        minutes: String,
+        trickle: bool,
+    },
+    SdpClient {
+        sdp: String,
+    },
+    SdpOfferer {
+        sdp: String,
+        trickle: bool,
+    },
+    NotifEndSession,
+}
+
+/// Reasons why a text frame could not be turned into a [`SignalingMessage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseError {
+    /// The frame was empty.
+    Empty,
+    /// The frame was not a text frame.
+    NotText,
+    /// The version header could not be read.
+    InvalidVersion(String),
+    /// The version header is newer than the one supported by this node.
+    UnsupportedVersion(u8),
+    /// The message type is not part of the protocol.
+    UnknownType(String),
+    /// The message is missing one of its fields.
+    MissingField {
+        msg_type: &'static str,
+        field: &'static str,
+    },
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseError::Empty => write!(f, "empty signaling message"),
+            ParseError::NotText => write!(f, "signaling message is not a text frame"),
+            ParseError::InvalidVersion(v) => write!(f, "invalid protocol version header: {v}"),
+            ParseError::UnsupportedVersion(v) => write!(f, "unsupported protocol version: {v}"),
+            ParseError::UnknownType(t) => write!(f, "unknown signaling message type: {t}"),
+            ParseError::MissingField { msg_type, field } => {
+                write!(f, "{msg_type} message is missing field {field}")
+            }
+        }
+    }
+}
+
+impl std::error::Error for ParseError {}
+
+impl From<ParseError> for Error {
+    fn from(e: ParseError) -> Self {
+        Error::new(ErrorKind::InvalidData, e)
+    }
+}
+
+impl SignalingMessage {
+    /// Returns the message type as sent on the wire.
+    pub fn msg_type(&self) -> &'static str {
+        match self {
+            SignalingMessage::InitOfferer { .. } => INIT_OFFERER_MSG,
+            SignalingMessage::OffererSdp { .. } => OFFERER_SDP_MSG,
+            SignalingMessage::InitClient { .. } => INIT_CLIENT_MSG,
+            SignalingMessage::ClientSdp { .. } => CLIENT_SDP_MSG,
+            SignalingMessage::StartSession { .. } => START_SESSION_MSG,
+            SignalingMessage::ForceStopSession { .. } => FORCE_STOP_SESSION_MSG,
+            SignalingMessage::IceCandidate { .. } => ICE_CANDIDATE_MSG,
+            SignalingMessage::SdpRequestFrom { .. } => SDP_REQUEST_FROM_MSG,
+            SignalingMessage::SdpClient { .. } => SDP_CLIENT_MSG,
+            SignalingMessage::SdpOfferer { .. } => SDP_OFFERER_MSG,
+            SignalingMessage::NotifEndSession => NOTIF_END_SESSION_MSG,
+        }
+    }
+
+    /// Encodes the message using the protocol version of this node.
+    pub fn encode(&self) -> String {
+        self.encode_with_version(PROTOCOL_VERSION)
+    }
+
+    /// Encodes the message for the given protocol version.
+    ///
+    /// The legacy version is encoded without header so the current server keeps working.
+    pub fn encode_with_version(&self, version: u8) -> String {
+        let (fields, trickle): (Vec<&str>, bool) = match self {
+            SignalingMessage::InitOfferer { username } => (vec![username], false),
+            SignalingMessage::OffererSdp {
+                client_name,
+                sdp,
+                trickle,
+            } => (vec![client_name, sdp], *trickle),
+            SignalingMessage::InitClient {
+                username,
+                offerer_username,
+                game_name,
+                minutes,
+                trickle,
+            } => (
+                vec![username, offerer_username, game_name, minutes],
+                *trickle,
+            ),
+            SignalingMessage::ClientSdp {
+                offerer_username,
+                sdp,
+            } => (vec![offerer_username, sdp], false),
+            SignalingMessage::StartSession {
+                offerer,
+                client,
+                minutes,
+            } => (vec![offerer, client, minutes], false),
+            SignalingMessage::ForceStopSession { username } => (vec![username], false),
+            SignalingMessage::IceCandidate { peer, candidate } => (vec![peer, candidate], false),
+            SignalingMessage::SdpRequestFrom {
+                client_name,
+                game_name,
+                game_path,
+                minutes,
+                trickle,
+            } => (vec![client_name, game_name, game_path, minutes], *trickle),
+            SignalingMessage::SdpClient { sdp } => (vec![sdp], false),
+            SignalingMessage::SdpOfferer { sdp, trickle } => (vec![sdp], *trickle),
+            SignalingMessage::NotifEndSession => (vec![], false),
+        };
+
+        let mut msg = String::new();
+        if version != LEGACY_PROTOCOL_VERSION {
+            msg.push_str(&format!("{}{}{}", VERSION_PREFIX, version, SEPARATOR));
+        }
+        msg.push_str(self.msg_type());
+        for field in fields {
+            msg.push(SEPARATOR);
+            msg.push_str(field);
+        }
+        if trickle {
+            msg.push(SEPARATOR);
+            msg.push_str(TRICKLE_FLAG);
+        }
+        msg
+    }
+
+    /// Decodes a text frame into a message.
+    ///
+    /// Trailing fields not known by this version of the protocol are ignored.
+    ///
+    /// # Returns
+    /// A tuple with the protocol version of the frame and the decoded message.
+    pub fn decode(text: &str) -> Result<(u8, SignalingMessage), ParseError> {
+        let text = text.trim_end_matches(['\n', '\r']);
+        if text.is_empty() {
+            return Err(ParseError::Empty);
+        }
+
+        let mut parts: Vec<&str> = text.split(SEPARATOR).collect();
+        let version = match parts[0].strip_prefix(VERSION_PREFIX) {
+            Some(v) if is_version(v) => {
+                let version = v
+                    .parse::<u8>()
+                    .map_err(|_| ParseError::InvalidVersion(v.to_string()))?;
+                if version > PROTOCOL_VERSION || version == 0 {
+                    return Err(ParseError::UnsupportedVersion(version));
+                }
+                parts.remove(0);
+                version
+            }
+            _ => LEGACY_PROTOCOL_VERSION,
+        };
+
+        let msg_type = match parts.first() {
+            Some(t) if !t.is_empty() => *t,
+            _ => return Err(ParseError::Empty),
+        };
+        let fields = Fields::new(msg_type, MESSAGE_TYPES, &parts[1..]);
+
+        let msg = match msg_type {
+            INIT_OFFERER_MSG => SignalingMessage::InitOfferer {
+                username: fields.get(0, "username")?,
+            },
+            OFFERER_SDP_MSG => SignalingMessage::OffererSdp {
+                client_name: fields.get(0, "client_name")?,
+                sdp: fields.get(1, "sdp")?,
+                trickle: fields.flag(2, TRICKLE_FLAG),
+            },
+            INIT_CLIENT_MSG => SignalingMessage::InitClient {
+                username: fields.get(0, "username")?,
+                offerer_username: fields.get(1, "offerer_username")?,
+                game_name: fields.get(2, "game_name")?,
+                minutes: fields.get(3, "minutes")?,
+                trickle: fields.flag(4, TRICKLE_FLAG),
+            },
+            CLIENT_SDP_MSG => SignalingMessage::ClientSdp {
+                offerer_username: fields.get(0, "offerer_username")?,
+                sdp: fields.get(1, "sdp")?,
+            },
+            START_SESSION_MSG => SignalingMessage::StartSession {
+                offerer: fields.get(0, "offerer")?,
+                client: fields.get(1, "client")?,
+                minutes: fields.get(2, "minutes")?,
+            },
+            FORCE_STOP_SESSION_MSG => SignalingMessage::ForceStopSession {
+                username: fields.get(0, "username")?,
+            },
+            ICE_CANDIDATE_MSG => SignalingMessage::IceCandidate {
+                peer: fields.get(0, "peer")?,
+                candidate: fields.rest(1, "candidate", SEPARATOR)?,
+            },
+            SDP_REQUEST_FROM_MSG => SignalingMessage::SdpRequestFrom {
+                client_name: fields.get(0, "client_name")?,
+                game_name: fields.get(1, "game_name")?,
+                game_path: fields.get(2, "game_path")?,
+                minutes: fields.get(3, "minutes")?,
+                trickle: fields.flag(4, TRICKLE_FLAG),
+            },
+            SDP_CLIENT_MSG => SignalingMessage::SdpClient {
+                sdp: fields.get(0, "sdp")?,
+            },
+            SDP_OFFERER_MSG => SignalingMessage::SdpOfferer {
+                sdp: fields.get(0, "sdp")?,
+                trickle: fields.flag(1, TRICKLE_FLAG),
+            },
+            NOTIF_END_SESSION_MSG => SignalingMessage::NotifEndSession,
+            other => return Err(ParseError::UnknownType(other.to_string())),
+        };
+
+        Ok((version, msg))
+    }
+
+    /// Decodes a websocket frame into a message.
+    ///
+    /// # Returns
+    /// The protocol version and the message, or `ParseError::NotText` if the frame is not
+    /// a text frame.
+    pub fn decode_frame(frame: &Frame) -> Result<(u8, SignalingMessage), ParseError> {
+        match frame.as_text() {
+            Some((text, _, _)) => SignalingMessage::decode(text),
+            None => Err(ParseError::NotText),
+        }
+    }
+}
+
+/// Every message type of the protocol, so errors can name the type as `'static`.
+const MESSAGE_TYPES: &[&str] = &[
+    INIT_OFFERER_MSG,
+    OFFERER_SDP_MSG,
+    INIT_CLIENT_MSG,
+    CLIENT_SDP_MSG,
+    START_SESSION_MSG,
+    FORCE_STOP_SESSION_MSG,
+    ICE_CANDIDATE_MSG,
+    SDP_REQUEST_FROM_MSG,
+    SDP_CLIENT_MSG,
+    SDP_OFFERER_MSG,
+    NOTIF_END_SESSION_MSG,
+];
+
+impl From<MissingField> for ParseError {
+    fn from(missing: MissingField) -> Self {
+        ParseError::MissingField {
+            msg_type: missing.kind,
+            field: missing.field,
+        }
+    }
+}
+
+fn is_version(v: &str) -> bool {
+    !v.is_empty() && v.chars().all(|c| c.is_ascii_digit())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn every_variant(trickle: bool) -> Vec<SignalingMessage> {
+        vec![
+            SignalingMessage::InitOfferer {
+                username: "offerer".to_owned(),
+            },
+            SignalingMessage::OffererSdp {
+                client_name: "client".to_owned(),
+                sdp: "offer sdp".to_owned(),
+                trickle,
+            },
+            SignalingMessage::InitClient {
+                username: "client".to_owned(),
+                offerer_username: "offerer".to_owned(),
+                game_name: "game".to_owned(),
+                minutes: "30".to_owned(),
+                trickle,
+            },
+            SignalingMessage::ClientSdp {
+                offerer_username: "offerer".to_owned(),
+                sdp: "answer sdp".to_owned(),
+            },
+            SignalingMessage::StartSession {
+                offerer: "offerer".to_owned(),
+                client: "client".to_owned(),
+                minutes: "30".to_owned(),
+            },
+            SignalingMessage::ForceStopSession {
+                username: "client".to_owned(),
+            },
+            SignalingMessage::IceCandidate {
+                peer: "client".to_owned(),
+                candidate:
+                    "{\"candidate\":\"candidate:1 1 udp 2122260223 10.0.0.1 50000 typ host\"}"
+                        .to_owned(),
+            },
+            SignalingMessage::SdpRequestFrom {
+                client_name: "client".to_owned(),
+                game_name: "game".to_owned(),
+                game_path: "C:\\games\\game.exe".to_owned(),
+                minutes: "30".to_owned(),
+                trickle,
+            },
+            SignalingMessage::SdpClient {
+                sdp: "answer sdp".to_owned(),
+            },
+            SignalingMessage::SdpOfferer {
+                sdp: "offer sdp".to_owned(),
+                trickle,
+            },
+            SignalingMessage::NotifEndSession,
+        ]
+    }
+
+    #[test]
+    fn every_variant_round_trips() {
+        for trickle in [false, true] {
+            for msg in every_variant(trickle) {
+                let decoded = SignalingMessage::decode(&msg.encode());
+                assert_eq!(decoded, Ok((PROTOCOL_VERSION, msg)));
+            }
+        }
+    }
+
+    #[test]
+    fn legacy_version_has_no_header() {
+        let msg = SignalingMessage::InitOfferer {
+            username: "offerer".to_owned(),
+        };
+        assert_eq!(
+            msg.encode_with_version(LEGACY_PROTOCOL_VERSION),
+            "initOfferer|offerer"
+        );
+    }
+
+    #[test]
+    fn version_header_is_prepended_and_read() {
+        let msg = SignalingMessage::SdpClient {
+            sdp: "answer sdp".to_owned(),
+        };
+        assert_eq!(msg.encode_with_version(2), "v2|sdpClient|answer sdp");
+        assert_eq!(
+            SignalingMessage::decode(&format!("v{}|sdpClient|answer sdp", PROTOCOL_VERSION)),
+            Ok((PROTOCOL_VERSION, msg))
+        );
+    }
+
+    #[test]
+    fn newer_or_zero_version_is_rejected() {
+        let newer = PROTOCOL_VERSION + 1;
+        assert_eq!(
+            SignalingMessage::decode(&format!("v{}|notifEndSession", newer)),
+            Err(ParseError::UnsupportedVersion(newer))
+        );
+        assert_eq!(
+            SignalingMessage::decode("v0|notifEndSession"),
+            Err(ParseError::UnsupportedVersion(0))
+        );
+        assert_eq!(
+            SignalingMessage::decode("v300|notifEndSession"),
+            Err(ParseError::InvalidVersion("300".to_owned()))
+        );
+    }
+
+    #[test]
+    fn trickle_is_a_trailing_field_only_when_set() {
+        let offer = |trickle| SignalingMessage::SdpOfferer {
+            sdp: "offer sdp".to_owned(),
+            trickle,
+        };
+        assert_eq!(offer(false).encode(), "sdpOfferer|offer sdp");
+        assert_eq!(offer(true).encode(), "sdpOfferer|offer sdp|trickle");
+    }
+
+    #[test]
+    fn unknown_trailing_fields_are_ignored() {
+        assert_eq!(
+            SignalingMessage::decode("sdpOfferer|offer sdp|somethingElse"),
+            Ok((
+                LEGACY_PROTOCOL_VERSION,
+                SignalingMessage::SdpOfferer {
+                    sdp: "offer sdp".to_owned(),
+                    trickle: false,
+                }
+            ))
+        );
+    }
+
+    #[test]
+    fn candidate_may_contain_the_separator() {
+        let msg = SignalingMessage::IceCandidate {
+            peer: "offerer".to_owned(),
+            candidate: "a|b|c".to_owned(),
+        };
+        assert_eq!(
+            SignalingMessage::decode(&msg.encode()),
+            Ok((PROTOCOL_VERSION, msg))
+        );
+    }
+
+    #[test]
+    fn missing_field_names_the_field() {
+        assert_eq!(
+            SignalingMessage::decode("initClient|client|offerer|game"),
+            Err(ParseError::MissingField {
+                msg_type: INIT_CLIENT_MSG,
+                field: "minutes",
+            })
+        );
+        assert_eq!(
+            SignalingMessage::decode("iceCandidate|offerer"),
+            Err(ParseError::MissingField {
+                msg_type: ICE_CANDIDATE_MSG,
+                field: "candidate",
+            })
+        );
+    }
+
+    #[test]
+    fn unknown_type_is_rejected() {
+        assert_eq!(
+            SignalingMessage::decode("somethingElse|field"),
+            Err(ParseError::UnknownType("somethingElse".to_owned()))
+        );
+    }
+
+    #[test]
+    fn empty_message_is_rejected() {
+        assert_eq!(SignalingMessage::decode(""), Err(ParseError::Empty));
+        assert_eq!(SignalingMessage::decode("\r\n"), Err(ParseError::Empty));
+        assert_eq!(SignalingMessage::decode("v1|"), Err(ParseError::Empty));
+    }
+
+    #[test]
+    fn only_text_frames_are_decoded() {
+        assert_eq!(
+            SignalingMessage::decode_frame(&Frame::text("notifEndSession".to_owned())),
+            Ok((LEGACY_PROTOCOL_VERSION, SignalingMessage::NotifEndSession))
+        );
+        assert_eq!(
+            SignalingMessage::decode_frame(&Frame::binary(b"notifEndSession".to_vec())),
+            Err(ParseError::NotText)
+        );
+    }
+}
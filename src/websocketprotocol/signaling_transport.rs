@@ -0,0 +1,187 @@
+use std::io::{Error, ErrorKind};
+
+use async_trait::async_trait;
+
+use super::signaling_message::SignalingMessage;
+
+/// Represents the info of the client trying to connect to a sender service
+#[derive(Debug)]
+pub struct ClientInfo {
+    pub client_name: String,
+    pub game_name: String,
+    pub game_path: String,
+    pub minutes: String,
+    /// Whether the client accepts trickled ICE candidates.
+    pub trickle: bool,
+}
+
+/// Signaling messages that can arrive once the session has started.
+#[derive(Debug)]
+pub enum SessionSignal {
+    /// The server ended the session.
+    EndSession,
+    /// A new offer from the offerer, e.g. an ICE restart.
+    Offer(String),
+    /// The answer of the client to a new offer.
+    Answer(String),
+    /// A trickled ICE candidate that arrived late.
+    Candidate(String),
+}
+
+/// Channel used to exchange signaling messages with the other peer.
+///
+/// Implementors only need to provide `send`, `receive` and `close_connection`, the
+/// offer/answer flow is built on top of them.
+#[async_trait]
+pub trait SignalingTransport: Send {
+    /// Sends a message through the transport.
+    async fn send(&mut self, msg: SignalingMessage) -> Result<(), Error>;
+
+    /// Receives the next message from the transport.
+    async fn receive(&mut self) -> Result<SignalingMessage, Error>;
+
+    /// Closes the transport. No more messages can be sent after this call.
+    async fn close_connection(&mut self) -> Result<(), Error>;
+
+    async fn init_offer(&mut self, username: &str) -> Result<(), Error> {
+        self.send(SignalingMessage::InitOfferer {
+            username: username.to_owned(),
+        })
+        .await
+    }
+
+    async fn wait_for_game_solicitude(&mut self) -> Result<ClientInfo, Error> {
+        match self.receive().await? {
+            SignalingMessage::SdpRequestFrom {
+                client_name,
+                game_name,
+                game_path,
+                minutes,
+                trickle,
+            } => Ok(ClientInfo {
+                client_name,
+                game_name,
+                game_path,
+                minutes,
+                trickle,
+            }),
+            _ => Err(Error::new(ErrorKind::InvalidData, "Should be sdp request.")),
+        }
+    }
+
+    /// Sends the offer to the client. `trickle` tells the client that the candidates of the
+    /// offerer will follow as `iceCandidate` messages.
+    async fn send_sdp_to_client(
+        &mut self,
+        client_name: &str,
+        sdp: &str,
+        trickle: bool,
+    ) -> Result<(), Error> {
+        self.send(SignalingMessage::OffererSdp {
+            client_name: client_name.to_owned(),
+            sdp: sdp.to_owned(),
+            trickle,
+        })
+        .await
+    }
+
+    async fn wait_for_client_sdp(&mut self) -> Result<String, Error> {
+        match self.receive().await? {
+            SignalingMessage::SdpClient { sdp } => Ok(sdp),
+            _ => Err(Error::new(ErrorKind::InvalidData, "Should be client sdp")),
+        }
+    }
+
+    async fn init_client(
+        &mut self,
+        username: &str,
+        offerer_username: &str,
+        game_name: &str,
+        minutes: &str,
+        trickle: bool,
+    ) -> Result<(), Error> {
+        self.send(SignalingMessage::InitClient {
+            username: username.to_owned(),
+            offerer_username: offerer_username.to_owned(),
+            game_name: game_name.to_owned(),
+            minutes: minutes.to_owned(),
+            trickle,
+        })
+        .await
+    }
+
+    /// Waits for the offer. Returns the sdp and whether the offerer trickles its candidates.
+    async fn wait_for_offerer_sdp(&mut self) -> Result<(String, bool), Error> {
+        match self.receive().await? {
+            SignalingMessage::SdpOfferer { sdp, trickle } => Ok((sdp, trickle)),
+            _ => Err(Error::new(ErrorKind::InvalidData, "Should be offerer sdp")),
+        }
+    }
+
+    async fn send_sdp_to_offerer(
+        &mut self,
+        offerer_username: &str,
+        sdp: &str,
+    ) -> Result<(), Error> {
+        self.send(SignalingMessage::ClientSdp {
+            offerer_username: offerer_username.to_owned(),
+            sdp: sdp.to_owned(),
+        })
+        .await
+    }
+
+    async fn start_session(
+        &mut self,
+        offerer: &str,
+        client: &str,
+        minutes: &str,
+    ) -> Result<(), Error> {
+        self.send(SignalingMessage::StartSession {
+            offerer: offerer.to_owned(),
+            client: client.to_owned(),
+            minutes: minutes.to_owned(),
+        })
+        .await
+    }
+
+    async fn force_stop_session(&mut self, username: &str) -> Result<(), Error> {
+        self.send(SignalingMessage::ForceStopSession {
+            username: username.to_owned(),
+        })
+        .await
+    }
+
+    async fn send_ice_candidate(&mut self, peer: &str, candidate: &str) -> Result<(), Error> {
+        self.send(SignalingMessage::IceCandidate {
+            peer: peer.to_owned(),
+            candidate: candidate.to_owned(),
+        })
+        .await
+    }
+
+    /// Waits for the next message relevant to a running session.
+    async fn wait_for_session_event(&mut self) -> Result<SessionSignal, Error> {
+        loop {
+            match self.receive().await {
+                Ok(SignalingMessage::NotifEndSession) => return Ok(SessionSignal::EndSession),
+                Ok(SignalingMessage::SdpOfferer { sdp, .. }) => {
+                    return Ok(SessionSignal::Offer(sdp))
+                }
+                Ok(SignalingMessage::SdpClient { sdp }) => return Ok(SessionSignal::Answer(sdp)),
+                Ok(SignalingMessage::IceCandidate { candidate, .. }) => {
+                    return Ok(SessionSignal::Candidate(candidate))
+                }
+                Ok(msg) => {
+                    log::info!(
+                        "wait_for_session_event | Received unexpected message: {}",
+                        msg.msg_type()
+                    );
+                }
+                Err(e) if e.kind() == ErrorKind::InvalidData => {
+                    log::info!("wait_for_session_event | Received invalid message: {}", e);
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
@@ -8,8 +8,20 @@ pub const CLIENT_SDP_MSG: &str = "clientSdp";
 pub const START_SESSION_MSG: &str = "startSession";
 pub const FORCE_STOP_SESSION_MSG: &str = "forceStopSession";
 
+//SOCKET SEND AND RECEIVE MESSAGES
+pub const ICE_CANDIDATE_MSG: &str = "iceCandidate";
+
 //SOCKET RECEIVE MESSAGES
 pub const SDP_REQUEST_FROM_MSG: &str = "sdpRequestFrom";
 pub const SDP_CLIENT_MSG: &str = "sdpClient";
 pub const SDP_OFFERER_MSG: &str = "sdpOfferer";
 pub const NOTIF_END_SESSION_MSG: &str = "notifEndSession";
+
+//PROTOCOL VERSIONING
+// Version spoken by the current intermediary server, encoded without header
+pub const LEGACY_PROTOCOL_VERSION: u8 = 1;
+pub const PROTOCOL_VERSION: u8 = 1;
+pub const VERSION_PREFIX: &str = "v";
+pub const SEPARATOR: char = '|';
+// Trailing field advertising trickle ICE support
+pub const TRICKLE_FLAG: &str = "trickle";
@@ -0,0 +1,105 @@
+"""End to end streaming test.
+
+Runs a local signaling server, an offering node with the test capture backend and a
+headless client node that replays input.example.jsonl as its input, and checks that the
+stream reaches the client. Requires the
+binaries built with `cargo build --bins` and the GStreamer base, good and ugly plugins
+plus gst-libav or openh264.
+"""
+import json
+import os
+import signal
+import subprocess
+import sys
+import threading
+import time
+
+TARGET_DIR = os.environ.get("CARGO_TARGET_DIR", "target")
+BIN_DIR = os.path.join(TARGET_DIR, sys.argv[1] if len(sys.argv) > 1 else "debug")
+SERVER_ADDR = "127.0.0.1:8090"
+OVERRIDES = ["--set", f"signaling.url=ws://{SERVER_ADDR}"]
+
+# Media received before the stream is considered working, a few seconds of video
+MIN_BYTES_RECEIVED = 100_000
+TIMEOUT = 90
+
+
+def start(*args):
+    return subprocess.Popen(
+        [os.path.join(BIN_DIR, args[0]), *args[1:]],
+        stdout=subprocess.PIPE,
+        text=True,
+    )
+
+
+def wait_stream(client):
+    """Reads the client events until enough media was received. Returns the events."""
+    events = []
+    # Ends the client if the stream does not arrive, which closes its output
+    timer = threading.Timer(TIMEOUT, client.kill)
+    timer.start()
+    for line in client.stdout:
+        try:
+            event = json.loads(line)
+        except json.JSONDecodeError:
+            continue  # Not every line printed by the node is an event
+        print(f"Event received: {event}")
+        events.append(event)
+        if event["event"] in ("error", "sessionEnded"):
+            break
+        if event["event"] == "streamStats" and event["bytesReceived"] >= MIN_BYTES_RECEIVED:
+            break
+    timer.cancel()
+    return events
+
+
+def stop(process):
+    if process.poll() is None:
+        process.send_signal(signal.SIGINT)
+    try:
+        process.wait(timeout=15)
+    except subprocess.TimeoutExpired:
+        process.kill()
+
+
+if __name__ == "__main__":
+    server = start("signaling-server", "--addr", SERVER_ADDR)
+    time.sleep(1)
+    offerer = start(
+        "cgrs", *OVERRIDES, "--set", "capture.backend=test", "offer", "--user", "offerer"
+    )
+    time.sleep(3)
+    client = start(
+        "cgrs",
+        *OVERRIDES,
+        "--set",
+        "player.backend=headless",
+        "--set",
+        "input.source=script",
+        "--set",
+        "input.script_file=input.example.jsonl",
+        "play",
+        "--user",
+        "client",
+        "--host",
+        "offerer",
+        "--game",
+        "test",
+        "--minutes",
+        "5",
+    )
+
+    try:
+        events = wait_stream(client)
+    finally:
+        for process in (client, offerer, server):
+            stop(process)
+
+    kinds = [event["event"] for event in events]
+    assert "peerConnected" in kinds, f"Nodes did not connect: {kinds}"
+    received = max(
+        (event["bytesReceived"] for event in events if event["event"] == "streamStats"),
+        default=0,
+    )
+    assert received >= MIN_BYTES_RECEIVED, f"Only {received} bytes received"
+    print(f"Stream received, {received} bytes")
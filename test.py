@@ -1,30 +1,100 @@
+import json
+import os
 import socket
+import sys
 
-def send_string_to_port(host, port, message):
-    try:
-        # Create a socket object
-        client_socket = socket.socket(socket.AF_INET, socket.SOCK_STREAM)
+# File where the node writes the token at startup, see front.token_file
+TOKEN_FILE = os.environ.get("CGRS_FRONT_TOKEN", "cgrs.front.token")
 
-        # Connect to the server (replace 'localhost' with the actual server address)
-        client_socket.connect((host, port))
+# Fields required by each event sent by the node, see FrontEvent
+EVENT_FIELDS = {
+    "sessionStarting": {},
+    "peerConnected": {},
+    "streamStats": {"rttMs": (int, float), "bytesSent": int, "bytesReceived": int},
+    "sessionEnded": {"reason": str},
+    "error": {"code": str, "message": str},
+    "status": {"state": str, "paused": bool},
+}
 
-        # Send the message
+ERROR_CODES = {
+    "signaling",
+    "webrtc",
+    "gstreamer",
+    "game_launch",
+    "front_protocol",
+    "input",
+    "config",
+    "io",
+}
+
+
+def check_event(line):
+    """Parses an event line and asserts it follows the schema. Returns the event."""
+    event = json.loads(line)
+    assert isinstance(event, dict), f"Event is not an object: {line}"
+    kind = event.get("event")
+    assert kind in EVENT_FIELDS, f"Unknown event: {line}"
+    for field, field_type in EVENT_FIELDS[kind].items():
+        assert field in event, f"Event {kind} is missing {field}: {line}"
+        assert isinstance(event[field], field_type), f"Invalid {field} in {kind}: {line}"
+    if kind == "error":
+        assert event["code"] in ERROR_CODES, f"Unknown error code: {line}"
+    return event
+
+
+def read_token():
+    with open(TOKEN_FILE, encoding="utf-8") as token_file:
+        return token_file.read().strip()
+
+
+def check_rejected(host, port, message):
+    """Sends a message without authenticating and checks the node rejects it."""
+    with socket.create_connection((host, port)) as client_socket:
         client_socket.sendall(message.encode())
+        reader = client_socket.makefile("r", encoding="utf-8")
+        event = check_event(reader.readline())
+        assert event["event"] == "error", f"Unauthenticated message accepted: {event}"
+        assert event["code"] == "front_protocol", f"Unexpected error: {event}"
+        assert reader.readline() == "", "Connection not closed after rejection"
+    print(f"Unauthenticated message rejected: {event}")
+
+
+def run_session(host, port, message):
+    """Sends a message to the node and checks every event until the session ends."""
+    with socket.create_connection((host, port)) as client_socket:
+        client_socket.sendall(f"auth|{read_token()}\n".encode())
+        client_socket.sendall(message.encode())
+        print(f"Message '{message.strip()}' sent successfully to {host}:{port}")
+
+        reader = client_socket.makefile("r", encoding="utf-8")
+        events = []
+        for line in reader:
+            event = check_event(line)
+            print(f"Event received: {event}")
+            events.append(event["event"])
+            if event["event"] == "sessionEnded":
+                break
+
+    assert events, "No events received"
+    assert events[0] == "sessionStarting", f"First event was {events[0]}"
+    assert events[-1] == "sessionEnded", "Session did not end"
+    if "peerConnected" in events:
+        assert events.index("peerConnected") < events.index("sessionEnded")
+    for i, kind in enumerate(events):
+        if kind == "streamStats":
+            assert "peerConnected" in events[:i], "Stats received before connecting"
+    print(f"All {len(events)} events are valid")
 
-        # Close the connection
-        client_socket.close()
-        print(f"Message '{message}' sent successfully to {host}:{port}")
-    except Exception as e:
-        print(f"Error sending message: {e}")
 
 # Example usage
 if __name__ == "__main__":
     server_host = "localhost"  # Replace with the actual server address
     server_port = 2930  # Replace with the desired port numbers
     #message_to_send = "startGameWithUser|axel|ElLeon|Stranded Deep\n"
-    message_to_send = "startGameWithUser|axel|franco_god|Cuphead\n"
+    message_to_send = "startGameWithUser|axel|franco_god|Cuphead|10\n"
     #message_to_send = "startOffering|franco_god\n"
+    if len(sys.argv) > 1:
+        message_to_send = sys.argv[1] + "\n"
 
-    #server_port = 3132
-    #message_to_send = "disconnect\n"
-    send_string_to_port(server_host, server_port, message_to_send)
\ No newline at end of file
+    check_rejected(server_host, server_port, message_to_send)
+    run_session(server_host, server_port, message_to_send)